@@ -163,8 +163,14 @@ pub fn arm_swp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
     let src = cpu.registers.read(rm);
 
     let (tmp, wait_load) = memory.load32(base, AccessType::NonSeq);
-    cpu.registers.write(rd, tmp);
+    if util::unlikely!(memory.take_abort()) {
+        return cpu.data_abort(memory) + wait_load;
+    }
     let wait_store = memory.store32(base, src, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return cpu.data_abort(memory) + wait_load + wait_store;
+    }
+    cpu.registers.write(rd, tmp);
 
     memory.stall(Cycles::ONE);
     Cycles::ONE + wait_load + wait_store
@@ -180,8 +186,14 @@ pub fn arm_swpb(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
     let src = cpu.registers.read(rm) as u8;
 
     let (tmp, wait_load) = memory.load8(base, AccessType::NonSeq);
-    cpu.registers.write(rd, tmp as u32);
+    if util::unlikely!(memory.take_abort()) {
+        return cpu.data_abort(memory) + wait_load;
+    }
     let wait_store = memory.store8(base, src, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return cpu.data_abort(memory) + wait_load + wait_store;
+    }
+    cpu.registers.write(rd, tmp as u32);
 
     memory.stall(Cycles::ONE);
     Cycles::ONE + wait_load + wait_store
@@ -193,8 +205,8 @@ fn arm_swi(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
 }
 
 /// UNDEFINED INSTR
-fn arm_undefined(_cpu: &mut Cpu, _memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    todo!("unimplemented")
+fn arm_undefined(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
+    cpu.exception_internal(CpuException::Undefined, memory)
 }
 
 #[allow(dead_code)]
@@ -49,13 +49,25 @@ macro_rules! arm_gen_bdt {
                 cpu.registers.write_mode(CpuMode::User);
             }
 
+            let original_pc = cpu.registers.read(15);
+            let mut aborted = false;
             let mut cycles = Cycles::ZERO;
             let mut access_type = AccessType::NonSeq;
             for reg in 0..16 {
                 if (register_list & (1 << reg)) != 0 {
                     addr = addr.wrapping_add(4);
+                    let original_value = cpu.registers.read(reg);
                     cycles += $transfer(cpu, memory, reg, addr, access_type);
 
+                    // The aborted register and the ones after it are left alone.
+                    if util::unlikely!(memory.take_abort()) {
+                        if $transfer_type == LOAD {
+                            cpu.registers.write(reg, original_value);
+                        }
+                        aborted = true;
+                        break;
+                    }
+
                     if access_type == AccessType::NonSeq {
                         access_type = AccessType::Seq;
 
@@ -86,6 +98,17 @@ macro_rules! arm_gen_bdt {
                 }
             }
 
+            // An aborted transfer restores the base register and never loads R15, even if they are
+            // in the register list.
+            if util::unlikely!(aborted) {
+                cpu.registers.write(rn, base);
+                cpu.registers.write(15, original_pc);
+                if $s_bit && ($transfer_type == STORE || (register_list & (1 << 15)) == 0) {
+                    cpu.registers.write_mode(last_mode);
+                }
+                return cycles + cpu.data_abort(memory);
+            }
+
             if $s_bit && $transfer_type == LOAD && (register_list & (1 << 15)) != 0 {
                 // if the S-bit is set in an LDM instruction and R15 is in the transfer list
                 // then SPSR_<mode> is transferred to CPSR at the same time as R15 is loaded (the end
@@ -1,174 +1,263 @@
-//! Stubs for ARM _instructions that have yet to be implemented.
+//! Coprocessor instructions. These are forwarded to the [`Coprocessor`](crate::Coprocessor)
+//! attached to the CPU, or raise the undefined instruction exception if there isn't one or it
+//! doesn't respond.
 
-use super::super::{Cpu, Cycles, Memory};
+use super::super::{AccessType, Cpu, CpuException, Cycles, Memory};
+use util::bits::Bits as _;
 
 // Perform coprocessor data operation
-pub fn arm_cdp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `cdp` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
+pub fn arm_cdp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    let accepted = cpu.coprocessors[instr.bits(8, 11) as usize]
+        .as_mut()
+        .is_some_and(|cp| {
+            cp.cdp(
+                instr.bits(20, 23),
+                instr.bits(12, 15),
+                instr.bits(16, 19),
+                instr.bits(0, 3),
+                instr.bits(5, 7),
+            )
+        });
+
+    if !accepted {
+        return cpu.exception_internal(CpuException::Undefined, memory);
+    }
     Cycles::ZERO
 }
 
+// Write coprocessor register from ARM register
+pub fn arm_mcr(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    let rd = instr.bits(12, 15);
+    // The PC is 12 bytes ahead when it is used as the source register.
+    let value = if rd == 15 {
+        cpu.registers.read(15).wrapping_add(4)
+    } else {
+        cpu.registers.read(rd)
+    };
+
+    let accepted = cpu.coprocessors[instr.bits(8, 11) as usize]
+        .as_mut()
+        .is_some_and(|cp| {
+            cp.mcr(
+                instr.bits(21, 23),
+                instr.bits(16, 19),
+                instr.bits(0, 3),
+                instr.bits(5, 7),
+                value,
+            )
+        });
+
+    if !accepted {
+        return cpu.exception_internal(CpuException::Undefined, memory);
+    }
+
+    // The final cycle is for transferring the value to the coprocessor.
+    memory.stall(Cycles::ONE);
+    Cycles::ONE
+}
+
+// Read coprocessor register to ARM register
+pub fn arm_mrc(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    let value = cpu.coprocessors[instr.bits(8, 11) as usize]
+        .as_mut()
+        .and_then(|cp| {
+            cp.mrc(
+                instr.bits(21, 23),
+                instr.bits(16, 19),
+                instr.bits(0, 3),
+                instr.bits(5, 7),
+            )
+        });
+
+    let value = match value {
+        Some(value) => value,
+        None => return cpu.exception_internal(CpuException::Undefined, memory),
+    };
+
+    // Reading into R15 only sets the N, Z, C, and V flags from the top 4 bits.
+    let rd = instr.bits(12, 15);
+    if rd == 15 {
+        let cpsr = cpu.registers.read_cpsr();
+        cpu.registers
+            .write_cpsr((cpsr & !0xF0000000) | (value & 0xF0000000));
+    } else {
+        cpu.registers.write(rd, value);
+    }
+
+    // One internal cycle and one cycle for transferring the value from the coprocessor.
+    memory.stall(Cycles::from(2u32));
+    Cycles::from(2u32)
+}
+
+/// Returns the address of the first word transferred by LDC/STC and the value that should be
+/// written back to the base register, if any.
+fn transfer_addresses(cpu: &Cpu, instr: u32) -> (u32, Option<u32>) {
+    let base = cpu.registers.read(instr.bits(16, 19));
+    let offset = instr.bits(0, 7) << 2;
+    let offset_address = if instr.is_bit_set(23) {
+        base.wrapping_add(offset)
+    } else {
+        base.wrapping_sub(offset)
+    };
+
+    let pre_indexed = instr.is_bit_set(24);
+    let writeback = instr.is_bit_set(21);
+    match (pre_indexed, writeback) {
+        (true, false) => (offset_address, None),
+        (true, true) => (offset_address, Some(offset_address)),
+        (false, true) => (base, Some(offset_address)),
+        // Unindexed: bits 7-0 are available for coprocessor use.
+        (false, false) => (base, None),
+    }
+}
+
+/// Load coprocessor data from memory
+fn arm_ldc(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    let number = instr.bits(8, 11) as usize;
+    let crd = instr.bits(12, 15);
+    let word_count = cpu.coprocessors[number]
+        .as_mut()
+        .and_then(|cp| cp.ldc(instr.is_bit_set(22), crd));
+
+    let word_count = match word_count {
+        Some(word_count) => word_count,
+        None => return cpu.exception_internal(CpuException::Undefined, memory),
+    };
+
+    let (mut address, writeback) = transfer_addresses(cpu, instr);
+    let mut cycles = Cycles::ZERO;
+    let mut access_type = AccessType::NonSeq;
+    for index in 0..word_count {
+        let (value, wait) = memory.load32(address, access_type);
+        cycles += wait;
+        if util::unlikely!(memory.take_abort()) {
+            return cycles + cpu.data_abort(memory);
+        }
+        if let Some(cp) = cpu.coprocessors[number].as_mut() {
+            cp.ldc_word(crd, index, value);
+        }
+        address = address.wrapping_add(4);
+        access_type = AccessType::Seq;
+    }
+
+    if let Some(writeback) = writeback {
+        cpu.registers.write(instr.bits(16, 19), writeback);
+    }
+
+    cycles
+}
+
+/// Store coprocessor data to memory
+fn arm_stc(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    let number = instr.bits(8, 11) as usize;
+    let crd = instr.bits(12, 15);
+    let word_count = cpu.coprocessors[number]
+        .as_mut()
+        .and_then(|cp| cp.stc(instr.is_bit_set(22), crd));
+
+    let word_count = match word_count {
+        Some(word_count) => word_count,
+        None => return cpu.exception_internal(CpuException::Undefined, memory),
+    };
+
+    let (mut address, writeback) = transfer_addresses(cpu, instr);
+    let mut cycles = Cycles::ZERO;
+    let mut access_type = AccessType::NonSeq;
+    for index in 0..word_count {
+        let value = cpu.coprocessors[number]
+            .as_mut()
+            .map_or(0, |cp| cp.stc_word(crd, index));
+        cycles += memory.store32(address, value, access_type);
+        if util::unlikely!(memory.take_abort()) {
+            return cycles + cpu.data_abort(memory);
+        }
+        address = address.wrapping_add(4);
+        access_type = AccessType::Seq;
+    }
+
+    if let Some(writeback) = writeback {
+        cpu.registers.write(instr.bits(16, 19), writeback);
+    }
+
+    cycles
+}
+
 // Load coprocessor data from memory, Negative offset
-pub fn arm_ldc_ofm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_ofm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Positive offset
-pub fn arm_ldc_ofp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_ofp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Pre-decrement
-pub fn arm_ldc_prm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_prm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Pre-increment
-pub fn arm_ldc_prp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_prp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Post-decrement
-pub fn arm_ldc_ptm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_ptm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Post-increment
-pub fn arm_ldc_ptp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_ptp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Unindexed, bits 7-0 available for copro use
-pub fn arm_ldc_unm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_unm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Load coprocessor data from memory, Unindexed, bits 7-0 available for copro use
-pub fn arm_ldc_unp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `ldc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
-}
-
-// Write coprocessor register from ARM register
-pub fn arm_mcr(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `mcr` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
-}
-
-// Read coprocessor register to ARM register
-pub fn arm_mrc(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `mrc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_ldc_unp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_ldc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Negative offset
-pub fn arm_stc_ofm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_ofm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Positive offset
-pub fn arm_stc_ofp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_ofp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Pre-decrement
-pub fn arm_stc_prm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_prm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Pre-increment
-pub fn arm_stc_prp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_prp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Post-decrement
-pub fn arm_stc_ptm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_ptm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Post-increment
-pub fn arm_stc_ptp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_ptp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Unindexed, bits 7-0 available for copro use
-pub fn arm_stc_unm(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_unm(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
 
 // Store coprocessor data to memory, Unindexed, bits 7-0 available for copro use
-pub fn arm_stc_unp(cpu: &mut Cpu, memory: &mut dyn Memory, _instr: u32) -> Cycles {
-    log::warn!(
-        "unsupported instruction `stc` at 0x{:08X}",
-        cpu.registers.read(15).wrapping_sub(8)
-    );
-    Cycles::ZERO
+pub fn arm_stc_unp(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
+    arm_stc(cpu, memory, instr)
 }
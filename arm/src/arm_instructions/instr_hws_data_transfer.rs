@@ -20,7 +20,9 @@ macro_rules! arm_gen_hwsdt {
             let rd = instr.bits(12, 15);
             let rn = instr.bits(16, 19);
             let offset = $get_offset(cpu, instr);
-            let mut addr = cpu.registers.read(rn);
+            let base = cpu.registers.read(rn);
+            let original_rd = cpu.registers.read(rd);
+            let mut addr = base;
 
             // pre-indexing
             if $indexing == PRE {
@@ -48,6 +50,15 @@ macro_rules! arm_gen_hwsdt {
 
             let mut cycles = $transfer(cpu, memory, rd, addr);
 
+            // Undo the writeback and load if the transfer aborted.
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(rn, base);
+                if $transfer_type == LOAD {
+                    cpu.registers.write(rd, original_rd);
+                }
+                return cycles + cpu.data_abort(memory);
+            }
+
             if $transfer_type == LOAD {
                 if rd == 15 || ($writeback == WRITEBACK && rn == 15) {
                     let dest_pc = cpu.registers.read(15);
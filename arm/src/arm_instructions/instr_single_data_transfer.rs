@@ -18,7 +18,6 @@ const NO_WRITEBACK: bool = false;
 const USER_MODE: bool = true;
 const NO_USER_MODE: bool = false;
 
-// #TODO handle data aborts by correctly jumping to the data abort exception vector.
 macro_rules! arm_gen_sdt {
     ($name:ident, $transfer:expr, $transfer_type:expr, $data_size:expr, $get_offset:expr, $direction:expr, $indexing:expr, $writeback:expr, $user_mode:expr) => {
         pub fn $name(cpu: &mut Cpu, memory: &mut dyn Memory, instr: u32) -> Cycles {
@@ -32,7 +31,9 @@ macro_rules! arm_gen_sdt {
             }
 
             let offset = $get_offset(cpu, instr);
-            let mut addr = cpu.registers.read(rn);
+            let base = cpu.registers.read(rn);
+            let original_rd = cpu.registers.read(rd);
+            let mut addr = base;
 
             // pre-indexing
             if $indexing == PRE {
@@ -60,12 +61,25 @@ macro_rules! arm_gen_sdt {
 
             let mut cycles = $transfer(cpu, memory, rd, addr);
 
+            // Undo the writeback and load if the transfer aborted.
+            let aborted = memory.take_abort();
+            if util::unlikely!(aborted) {
+                cpu.registers.write(rn, base);
+                if $transfer_type == LOAD {
+                    cpu.registers.write(rd, original_rd);
+                }
+            }
+
             // Switch back to our original mode if the "T Bit" is set and we weren't originally in
             // user mode.
             if $user_mode && original_mode != CpuMode::User {
                 cpu.registers.write_mode(original_mode);
             }
 
+            if util::unlikely!(aborted) {
+                return cycles + cpu.data_abort(memory);
+            }
+
             if $transfer_type == LOAD {
                 if rd == 15 || ($writeback == WRITEBACK && rn == 15) {
                     let dest_pc = cpu.registers.read(15);
@@ -0,0 +1,654 @@
+//! A small assembler for the ARMv4T instruction set. It supports the subset of GNU as syntax
+//! that the CPU tests use: ARM and THUMB instructions, labels, `.equ` constants, literal pools
+//! for `ldr rX, =expr` and simple data directives. The `.text`, `.data` and `.rodata` sections
+//! are laid out one after another starting at address 0, each aligned to a word boundary.
+
+mod arm;
+mod expr;
+mod operand;
+mod thumb;
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::Isa;
+use expr::Env;
+use operand::{split_operands, Literal};
+
+/// Assembles `source` starting in the given instruction set and returns the bytes of the
+/// assembled program, which should be loaded at address 0.
+pub fn assemble(isa: Isa, source: &str) -> Result<Vec<u8>, Error> {
+    let mut assembler = Assembler::new(isa);
+    for (idx, line) in source.lines().enumerate() {
+        assembler.line = idx + 1;
+        if !assembler.parse_line(line)? {
+            break;
+        }
+    }
+    assembler.finish()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+enum Section {
+    Text = 0,
+    Data = 1,
+    Rodata = 2,
+}
+
+impl Section {
+    const ALL: [Section; 3] = [Section::Text, Section::Data, Section::Rodata];
+}
+
+/// Where the value of `ldr rX, =expr` comes from, decided during the first pass.
+#[derive(Copy, Clone, Debug)]
+enum LiteralRef {
+    Constant(u32),
+    /// The offset of the entry in its literal pool from the start of the section. This is
+    /// only known once the pool has been placed.
+    Pool(Option<u32>),
+}
+
+enum StatementKind<'s> {
+    Instruction {
+        isa: Isa,
+        mnemonic: &'s str,
+        operands: &'s str,
+        literal: Option<LiteralRef>,
+    },
+    /// Values of `size` bytes each.
+    Data {
+        size: u32,
+        values: Vec<&'s str>,
+    },
+    Fill {
+        len: u32,
+        value: u8,
+    },
+}
+
+struct Statement<'s> {
+    line: usize,
+    section: Section,
+    /// The offset of the statement from the start of its section.
+    offset: u32,
+    kind: StatementKind<'s>,
+}
+
+/// Literals waiting to be placed in the next pool of a section.
+#[derive(Default)]
+struct PendingPool<'s> {
+    /// The expression of each entry along with the statements that load it.
+    entries: Vec<(PoolKey<'s>, &'s str, Vec<usize>)>,
+}
+
+/// Identical literals share a pool entry.
+#[derive(PartialEq, Eq)]
+enum PoolKey<'s> {
+    Value(u32),
+    Expr(&'s str),
+}
+
+/// Collects statements and symbols in the first pass, after which every statement's address
+/// is known and they can be encoded.
+struct Assembler<'s> {
+    line: usize,
+    isa: Isa,
+    section: Section,
+    sizes: [u32; 3],
+    pools: [PendingPool<'s>; 3],
+    statements: Vec<Statement<'s>>,
+    /// Labels along with their section and offset.
+    labels: HashMap<&'s str, (Section, u32)>,
+    constants: HashMap<String, i64>,
+}
+
+impl<'s> Assembler<'s> {
+    fn new(isa: Isa) -> Self {
+        Assembler {
+            line: 0,
+            isa,
+            section: Section::Text,
+            sizes: [0; 3],
+            pools: Default::default(),
+            statements: Vec::new(),
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error {
+            line: self.line,
+            message: message.into(),
+        }
+    }
+
+    fn offset(&self) -> u32 {
+        self.sizes[self.section as usize]
+    }
+
+    fn push(&mut self, size: u32, kind: StatementKind<'s>) {
+        self.statements.push(Statement {
+            line: self.line,
+            section: self.section,
+            offset: self.offset(),
+            kind,
+        });
+        self.sizes[self.section as usize] += size;
+    }
+
+    fn align(&mut self, alignment: u32, value: u8) {
+        let len = self.offset().wrapping_neg() & (alignment - 1);
+        if len != 0 {
+            self.push(len, StatementKind::Fill { len, value });
+        }
+    }
+
+    /// Evaluates an expression that may only use `.equ` constants.
+    fn constant(&self, expr: &str) -> Result<i64, Error> {
+        // Addresses aren't known yet so expressions using `.` are rejected by checking whether
+        // the result depends on it.
+        let eval = |address| {
+            let env = Env {
+                symbols: &self.constants,
+                address,
+            };
+            env.eval(expr).map_err(|message| self.error(message))
+        };
+        let value = eval(0)?;
+        if eval(4)? != value {
+            return Err(self.error(format!("`{expr}` must be a constant")));
+        }
+        Ok(value)
+    }
+
+    /// Handles a single line of source. Returns false after `.end`.
+    fn parse_line(&mut self, line: &'s str) -> Result<bool, Error> {
+        let line = strip_comment(line);
+        let mut rest = line.trim();
+
+        while let Some((label, after)) = split_label(rest) {
+            let location = (self.section, self.offset());
+            if self.labels.insert(label, location).is_some() {
+                return Err(self.error(format!("`{label}` is already defined")));
+            }
+            rest = after.trim_start();
+        }
+        if rest.is_empty() {
+            return Ok(true);
+        }
+
+        let (name, operands) = rest
+            .split_once(char::is_whitespace)
+            .map_or((rest, ""), |(name, operands)| (name, operands.trim()));
+
+        if name.starts_with('.') {
+            self.directive(name, operands)
+        } else {
+            self.instruction(name, operands)?;
+            Ok(true)
+        }
+    }
+
+    fn directive(&mut self, name: &'s str, operands: &'s str) -> Result<bool, Error> {
+        match name.to_ascii_lowercase().as_str() {
+            ".text" => self.section = Section::Text,
+            ".data" => self.section = Section::Data,
+            ".section" => {
+                let section = split_operands(operands).first().copied().unwrap_or("");
+                self.section = if section.starts_with(".text") {
+                    Section::Text
+                } else if section.starts_with(".data") {
+                    Section::Data
+                } else if section.starts_with(".rodata") {
+                    Section::Rodata
+                } else {
+                    return Err(self.error(format!("unsupported section `{section}`")));
+                };
+            }
+
+            ".arm" => self.isa = Isa::Arm,
+            ".thumb" => self.isa = Isa::Thumb,
+            ".code" => match self.constant(operands)? {
+                32 => self.isa = Isa::Arm,
+                16 => self.isa = Isa::Thumb,
+                _ => return Err(self.error("expected `.code 16` or `.code 32`")),
+            },
+
+            ".word" | ".long" | ".4byte" => self.data(4, operands),
+            ".hword" | ".short" | ".2byte" => self.data(2, operands),
+            ".byte" => self.data(1, operands),
+
+            ".space" | ".skip" => {
+                let operands = split_operands(operands);
+                let len = self.constant(operands.first().copied().unwrap_or(""))?;
+                let value = match operands.get(1) {
+                    Some(value) => self.constant(value)? as u8,
+                    None => 0,
+                };
+                if len < 0 {
+                    return Err(self.error("negative size"));
+                }
+                self.push(
+                    len as u32,
+                    StatementKind::Fill {
+                        len: len as u32,
+                        value,
+                    },
+                );
+            }
+
+            // On ARM `.align` takes a power of 2 like `.p2align`.
+            ".align" | ".p2align" | ".balign" => {
+                let operands = split_operands(operands);
+                let amount = match operands.first() {
+                    Some(amount) => self.constant(amount)?,
+                    None => 2,
+                };
+                let alignment = if name.eq_ignore_ascii_case(".balign") {
+                    amount
+                } else {
+                    1i64.checked_shl(amount as u32).unwrap_or(0)
+                };
+                if alignment <= 0 || alignment > 1 << 16 || alignment & (alignment - 1) != 0 {
+                    return Err(self.error("invalid alignment"));
+                }
+                let value = match operands.get(1) {
+                    Some(value) => self.constant(value)? as u8,
+                    None => 0,
+                };
+                self.align(alignment as u32, value);
+            }
+
+            ".ltorg" | ".pool" => self.flush_pool(),
+
+            ".equ" | ".set" => {
+                let (symbol, value) = operands
+                    .split_once(',')
+                    .ok_or_else(|| self.error("expected a symbol and a value"))?;
+                let value = self.constant(value)?;
+                self.constants.insert(symbol.trim().to_string(), value);
+            }
+
+            ".global" | ".globl" | ".type" | ".size" | ".thumb_func" | ".syntax" | ".cpu"
+            | ".arch" | ".func" | ".endfunc" => {}
+
+            ".end" => return Ok(false),
+
+            _ => return Err(self.error(format!("unsupported directive `{name}`"))),
+        }
+        Ok(true)
+    }
+
+    fn data(&mut self, size: u32, operands: &'s str) {
+        let values = split_operands(operands);
+        self.push(
+            size * values.len() as u32,
+            StatementKind::Data { size, values },
+        );
+    }
+
+    fn instruction(&mut self, mnemonic: &'s str, operands: &'s str) -> Result<(), Error> {
+        let (size, alignment) = match self.isa {
+            Isa::Arm => (4, 4),
+            Isa::Thumb => (thumb::size(mnemonic), 2),
+        };
+        if !self.offset().is_multiple_of(alignment) {
+            return Err(self.error("instruction is not aligned"));
+        }
+
+        // `ldr rX, =expr`
+        let literal_expr = split_operands(operands)
+            .get(1)
+            .filter(|_| mnemonic.eq_ignore_ascii_case("ldr"))
+            .and_then(|operand| operand.strip_prefix('='))
+            .map(str::trim);
+        let literal = literal_expr.map(|expr| self.literal(expr, operands));
+
+        if let (Some(expr), Some(LiteralRef::Pool(_))) = (literal_expr, literal) {
+            let key = match self.constant(expr) {
+                Ok(value) => PoolKey::Value(value as u32),
+                Err(_) => PoolKey::Expr(expr),
+            };
+            let statement = self.statements.len();
+            let pool = &mut self.pools[self.section as usize];
+            match pool.entries.iter_mut().find(|(k, _, _)| *k == key) {
+                Some((_, _, users)) => users.push(statement),
+                None => pool.entries.push((key, expr, vec![statement])),
+            }
+        }
+
+        self.push(
+            size,
+            StatementKind::Instruction {
+                isa: self.isa,
+                mnemonic,
+                operands,
+                literal,
+            },
+        );
+        Ok(())
+    }
+
+    /// Decides whether `ldr rX, =expr` can be replaced with a move. Like GNU as this is only
+    /// done for values known in the first pass.
+    fn literal(&self, expr: &str, operands: &str) -> LiteralRef {
+        let value = match self.constant(expr) {
+            Ok(value) => value as u32,
+            Err(_) => return LiteralRef::Pool(None),
+        };
+        let fits = match self.isa {
+            Isa::Arm => {
+                arm::encode_immediate(value).is_some() || arm::encode_immediate(!value).is_some()
+            }
+            Isa::Thumb => {
+                let low = split_operands(operands)
+                    .first()
+                    .is_some_and(|rd| operand::low_register(rd).is_ok());
+                low && value <= 0xFF
+            }
+        };
+        if fits {
+            LiteralRef::Constant(value)
+        } else {
+            LiteralRef::Pool(None)
+        }
+    }
+
+    /// Places the pending literals of the current section.
+    fn flush_pool(&mut self) {
+        let entries = std::mem::take(&mut self.pools[self.section as usize].entries);
+        if entries.is_empty() {
+            return;
+        }
+
+        self.align(4, 0);
+        for (_, expr, users) in entries {
+            let offset = self.offset();
+            for user in users {
+                if let StatementKind::Instruction {
+                    ref mut literal, ..
+                } = self.statements[user].kind
+                {
+                    *literal = Some(LiteralRef::Pool(Some(offset)));
+                }
+            }
+            self.push(
+                4,
+                StatementKind::Data {
+                    size: 4,
+                    values: vec![expr],
+                },
+            );
+        }
+    }
+
+    fn finish(mut self) -> Result<Vec<u8>, Error> {
+        for section in Section::ALL {
+            self.section = section;
+            self.flush_pool();
+        }
+
+        // The text section is padded to a word boundary and each section starts on one.
+        let text_size = align4(self.sizes[Section::Text as usize]);
+        let data_base = text_size;
+        let rodata_base = align4(data_base + self.sizes[Section::Data as usize]);
+        let bases = [0, data_base, rodata_base];
+        let total = if self.sizes[Section::Rodata as usize] > 0 {
+            rodata_base + self.sizes[Section::Rodata as usize]
+        } else if self.sizes[Section::Data as usize] > 0 {
+            data_base + self.sizes[Section::Data as usize]
+        } else {
+            text_size
+        };
+
+        let mut symbols = self.constants.clone();
+        for (&label, &(section, offset)) in &self.labels {
+            symbols.insert(label.to_string(), (bases[section as usize] + offset) as i64);
+        }
+
+        let mut output = vec![0u8; total as usize];
+        for statement in &self.statements {
+            let address = bases[statement.section as usize] + statement.offset;
+            let env = Env {
+                symbols: &symbols,
+                address,
+            };
+            let error = |message: String| Error {
+                line: statement.line,
+                message,
+            };
+            let out = &mut output[address as usize..];
+
+            match statement.kind {
+                StatementKind::Instruction {
+                    isa,
+                    mnemonic,
+                    operands,
+                    literal,
+                } => {
+                    let literal = literal.map(|literal| match literal {
+                        LiteralRef::Constant(value) => Literal::Constant(value),
+                        LiteralRef::Pool(offset) => Literal::Pool(
+                            bases[statement.section as usize]
+                                + offset.expect("literal pools are placed before encoding"),
+                        ),
+                    });
+                    let operands = split_operands(operands);
+                    match isa {
+                        Isa::Arm => {
+                            let opcode =
+                                arm::encode(mnemonic, &operands, &env, literal).map_err(error)?;
+                            out[..4].copy_from_slice(&opcode.to_le_bytes());
+                        }
+                        Isa::Thumb => {
+                            let (first, second) =
+                                thumb::encode(mnemonic, &operands, &env, literal).map_err(error)?;
+                            out[..2].copy_from_slice(&first.to_le_bytes());
+                            if let Some(second) = second {
+                                out[2..4].copy_from_slice(&second.to_le_bytes());
+                            }
+                        }
+                    }
+                }
+
+                StatementKind::Data { size, ref values } => {
+                    for (idx, value) in values.iter().enumerate() {
+                        let env = Env {
+                            symbols: &symbols,
+                            address: address + idx as u32 * size,
+                        };
+                        let value = env.eval(value).map_err(error)?;
+                        let min = -(1i64 << (size * 8 - 1));
+                        let max = (1i64 << (size * 8)) - 1;
+                        if !(min..=max).contains(&value) {
+                            return Err(error(format!("{value} doesn't fit in {size} bytes")));
+                        }
+                        let start = (idx as u32 * size) as usize;
+                        out[start..start + size as usize]
+                            .copy_from_slice(&value.to_le_bytes()[..size as usize]);
+                    }
+                }
+
+                StatementKind::Fill { len, value } => out[..len as usize].fill(value),
+            }
+        }
+
+        Ok(output)
+    }
+}
+
+fn align4(value: u32) -> u32 {
+    (value + 3) & !3
+}
+
+/// Removes `@` and `//` comments from a line.
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find('@'), line.find("//")]
+        .into_iter()
+        .flatten()
+        .min()
+        .unwrap_or(line.len());
+    &line[..end]
+}
+
+/// Splits `label: rest` into the label and the rest of the line.
+fn split_label(line: &str) -> Option<(&str, &str)> {
+    let (label, rest) = line.split_once(':')?;
+    let mut chars = label.chars();
+    let valid = chars.next().is_some_and(expr::is_symbol_start) && chars.all(expr::is_symbol_char);
+    valid.then_some((label, rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn words(isa: Isa, source: &str) -> Vec<u32> {
+        let bytes = assemble(isa, source).unwrap();
+        bytes
+            .chunks(4)
+            .map(|chunk| {
+                let mut word = [0; 4];
+                word[..chunk.len()].copy_from_slice(chunk);
+                u32::from_le_bytes(word)
+            })
+            .collect()
+    }
+
+    fn halfwords(source: &str) -> Vec<u16> {
+        let bytes = assemble(Isa::Thumb, source).unwrap();
+        bytes
+            .chunks(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect()
+    }
+
+    #[test]
+    fn arm_encodings() {
+        let source = "
+            mov     r0, #5
+            ldr     r0, [r1, #4]
+            adds    r0, r0, r0
+            bx      r1
+            push    {r4, lr}
+            mrs     r2, cpsr
+            msr     cpsr_f, #0x90000000
+            swi     #6
+            umulls  r4, r5, r2, r3
+            ldrh    r1, [r2, #-2]!
+            stmfd   sp!, {r0-r3}
+            movs    r0, r1, lsr #32
+        ";
+        assert_eq!(
+            words(Isa::Arm, source),
+            [
+                0xE3A00005, 0xE5910004, 0xE0900000, 0xE12FFF11, 0xE92D4010, 0xE10F2000, 0xE328F209,
+                0xEF000006, 0xE0954392, 0xE17210B2, 0xE92D000F, 0xE1B00021,
+            ]
+        );
+    }
+
+    #[test]
+    fn thumb_encodings() {
+        let source = "
+            mov     r0, #5
+            add     r0, r1, r2
+            bx      lr
+            push    {r4, lr}
+            ldr     r0, [r1, #4]
+            mov     r8, r0
+            lsl     r1, r2, #3
+            neg     r0, r1
+            add     sp, #-8
+            swi     #6
+        ";
+        assert_eq!(
+            halfwords(source),
+            [0x2005, 0x1888, 0x4770, 0xB510, 0x6848, 0x4680, 0x00D1, 0x4248, 0xB082, 0xDF06]
+        );
+    }
+
+    #[test]
+    fn labels_and_branches() {
+        let source = "
+        start:
+            b       end
+            bl      start
+        end: beq    start
+        ";
+        assert_eq!(
+            words(Isa::Arm, source),
+            [0xEA000000, 0xEBFFFFFD, 0x0AFFFFFC]
+        );
+
+        let source = "
+        start:
+            bne     end
+            bl      start
+        end:
+            b       start
+        ";
+        assert_eq!(halfwords(source), [0xD101, 0xF7FF, 0xFFFD, 0xE7FB]);
+    }
+
+    #[test]
+    fn literal_pools() {
+        let source = "
+            ldr     r0, =0xFF000000
+            ldr     r1, =0xFFFFFFF6
+            ldr     r2, =0x12345678
+            ldr     r3, =value
+            ldr     r4, =0x12345678
+        value: .word 7
+        ";
+        assert_eq!(
+            words(Isa::Arm, source),
+            [0xE3A004FF, 0xE3E01009, 0xE59F2008, 0xE59F3008, 0xE59F4000, 7, 0x12345678, 0x14,]
+        );
+
+        let source = "
+            ldr     r0, =200
+            ldr     r1, =300
+            nop
+        ";
+        assert_eq!(halfwords(source), [0x20C8, 0x4901, 0x46C0, 0x0000, 300, 0]);
+    }
+
+    #[test]
+    fn sections_and_constants() {
+        let source = "
+            .equ    COUNT, 3
+            .data
+        var: .hword COUNT * 2
+            .text
+            ldr     r0, =var
+            mov     r1, #COUNT
+        ";
+        assert_eq!(words(Isa::Arm, source), [0xE59F0000, 0xE3A01003, 0x0C, 6]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = assemble(Isa::Arm, "mov r0, #1\nfoo r0\n").unwrap_err();
+        assert_eq!(err.line, 2);
+        assert_eq!(err.to_string(), "line 2: unknown instruction `foo`");
+
+        let err = assemble(Isa::Arm, "mov r0, #0x101").unwrap_err();
+        assert_eq!(err.message, "invalid constant `#0x101`");
+    }
+}
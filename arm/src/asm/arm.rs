@@ -0,0 +1,583 @@
+//! Encodes ARM instructions.
+
+use super::expr::Env;
+use super::operand::{
+    branch_offset, condition, immediate, is_immediate, register, register_list, Address, Literal,
+    Offset, Shift, ShiftType,
+};
+
+const AND: u32 = 0x0;
+const EOR: u32 = 0x1;
+const SUB: u32 = 0x2;
+const RSB: u32 = 0x3;
+const ADD: u32 = 0x4;
+const ADC: u32 = 0x5;
+const SBC: u32 = 0x6;
+const RSC: u32 = 0x7;
+const TST: u32 = 0x8;
+const TEQ: u32 = 0x9;
+const CMP: u32 = 0xA;
+const CMN: u32 = 0xB;
+const ORR: u32 = 0xC;
+const MOV: u32 = 0xD;
+const BIC: u32 = 0xE;
+const MVN: u32 = 0xF;
+
+/// Base mnemonics with the suffixes that they accept (other than a condition).
+const MNEMONICS: &[(&str, &[&str])] = &[
+    ("and", &["s"]),
+    ("eor", &["s"]),
+    ("sub", &["s"]),
+    ("rsb", &["s"]),
+    ("add", &["s"]),
+    ("adc", &["s"]),
+    ("sbc", &["s"]),
+    ("rsc", &["s"]),
+    ("tst", &["s"]),
+    ("teq", &["s"]),
+    ("cmp", &["s"]),
+    ("cmn", &["s"]),
+    ("orr", &["s"]),
+    ("mov", &["s"]),
+    ("bic", &["s"]),
+    ("mvn", &["s"]),
+    ("lsl", &["s"]),
+    ("lsr", &["s"]),
+    ("asr", &["s"]),
+    ("ror", &["s"]),
+    ("rrx", &["s"]),
+    ("mul", &["s"]),
+    ("mla", &["s"]),
+    ("umull", &["s"]),
+    ("umlal", &["s"]),
+    ("smull", &["s"]),
+    ("smlal", &["s"]),
+    ("ldr", &["b", "t", "bt", "h", "sb", "sh"]),
+    ("str", &["b", "t", "bt", "h"]),
+    ("ldm", &["ia", "ib", "da", "db", "fd", "ed", "fa", "ea"]),
+    ("stm", &["ia", "ib", "da", "db", "fd", "ed", "fa", "ea"]),
+    ("push", &[]),
+    ("pop", &[]),
+    ("swp", &["b"]),
+    ("swi", &[]),
+    ("svc", &[]),
+    ("mrs", &[]),
+    ("msr", &[]),
+    ("adr", &[]),
+    ("nop", &[]),
+    ("bx", &[]),
+    ("bl", &[]),
+    ("b", &[]),
+];
+
+/// Splits a mnemonic into its base, condition and suffix. Both pre-UAL (`ldreqb`) and UAL
+/// (`ldrbeq`) ordering of the condition and suffix are accepted.
+fn parse_mnemonic(mnemonic: &str) -> Option<(&'static str, u32, &'static str)> {
+    let mut candidates: Vec<_> = MNEMONICS
+        .iter()
+        .filter(|(base, _)| mnemonic.starts_with(base))
+        .collect();
+    // Longer mnemonics first so that `bls` isn't parsed as `bl` + `s` and so on.
+    candidates.sort_by_key(|(base, _)| std::cmp::Reverse(base.len()));
+
+    for &&(base, suffixes) in &candidates {
+        let rest = &mnemonic[base.len()..];
+        for &suffix in std::iter::once(&"").chain(suffixes.iter()) {
+            let cond = if let Some(cond) = rest.strip_suffix(suffix) {
+                cond
+            } else if let Some(cond) = rest.strip_prefix(suffix) {
+                cond
+            } else {
+                continue;
+            };
+
+            if cond.is_empty() {
+                return Some((base, 0xE, suffix));
+            }
+            if let Some(cond) = condition(cond) {
+                return Some((base, cond, suffix));
+            }
+        }
+    }
+    None
+}
+
+/// Encodes a value as an 8-bit immediate rotated right by an even amount, preferring the
+/// smallest rotation.
+pub(super) fn encode_immediate(value: u32) -> Option<u32> {
+    (0..32)
+        .step_by(2)
+        .find(|&rotation| value.rotate_left(rotation) <= 0xFF)
+        .map(|rotation| value.rotate_left(rotation) | (rotation / 2) << 8)
+}
+
+/// Returns an immediate operand as a 32-bit value, allowing negative values.
+fn immediate32(s: &str, env: &Env) -> Result<u32, String> {
+    let value = immediate(s, env)?;
+    if (-(1 << 31)..(1 << 32)).contains(&value) {
+        Ok(value as u32)
+    } else {
+        Err(format!("immediate `{s}` is out of range"))
+    }
+}
+
+fn expect_operands(operands: &[&str], count: usize) -> Result<(), String> {
+    if operands.len() == count {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected {count} operands, found {}",
+            operands.len()
+        ))
+    }
+}
+
+fn is_shift(s: &str) -> bool {
+    let s = s.trim();
+    s.eq_ignore_ascii_case("rrx")
+        || s.get(..3)
+            .and_then(ShiftType::parse)
+            .is_some_and(|_| s.len() == 3 || !s.as_bytes()[3].is_ascii_alphanumeric())
+}
+
+pub(super) fn encode(
+    mnemonic: &str,
+    operands: &[&str],
+    env: &Env,
+    literal: Option<Literal>,
+) -> Result<u32, String> {
+    let mnemonic = mnemonic.to_ascii_lowercase();
+    let (base, cond, suffix) =
+        parse_mnemonic(&mnemonic).ok_or_else(|| format!("unknown instruction `{mnemonic}`"))?;
+    let s = suffix == "s";
+
+    let opcode = match base {
+        "and" => data_processing(AND, s, operands, env)?,
+        "eor" => data_processing(EOR, s, operands, env)?,
+        "sub" => data_processing(SUB, s, operands, env)?,
+        "rsb" => data_processing(RSB, s, operands, env)?,
+        "add" => data_processing(ADD, s, operands, env)?,
+        "adc" => data_processing(ADC, s, operands, env)?,
+        "sbc" => data_processing(SBC, s, operands, env)?,
+        "rsc" => data_processing(RSC, s, operands, env)?,
+        "tst" => data_processing(TST, true, operands, env)?,
+        "teq" => data_processing(TEQ, true, operands, env)?,
+        "cmp" => data_processing(CMP, true, operands, env)?,
+        "cmn" => data_processing(CMN, true, operands, env)?,
+        "orr" => data_processing(ORR, s, operands, env)?,
+        "mov" => data_processing(MOV, s, operands, env)?,
+        "bic" => data_processing(BIC, s, operands, env)?,
+        "mvn" => data_processing(MVN, s, operands, env)?,
+        "lsl" | "lsr" | "asr" | "ror" | "rrx" => shift(base, s, operands, env)?,
+        "nop" => {
+            expect_operands(operands, 0)?;
+            0x01A00000
+        }
+
+        "mul" => {
+            expect_operands(operands, 3)?;
+            let [rd, rm, rs] = [operands[0], operands[1], operands[2]].map(register);
+            0x00000090 | (s as u32) << 20 | rd? << 16 | rs? << 8 | rm?
+        }
+        "mla" => {
+            expect_operands(operands, 4)?;
+            let [rd, rm, rs, rn] =
+                [operands[0], operands[1], operands[2], operands[3]].map(register);
+            0x00200090 | (s as u32) << 20 | rd? << 16 | rn? << 12 | rs? << 8 | rm?
+        }
+        "umull" | "umlal" | "smull" | "smlal" => {
+            expect_operands(operands, 4)?;
+            let [rdlo, rdhi, rm, rs] =
+                [operands[0], operands[1], operands[2], operands[3]].map(register);
+            let signed = base.starts_with('s') as u32;
+            let accumulate = base.ends_with("lal") as u32;
+            0x00800090
+                | signed << 22
+                | accumulate << 21
+                | (s as u32) << 20
+                | rdhi? << 16
+                | rdlo? << 12
+                | rs? << 8
+                | rm?
+        }
+
+        "ldr" | "str" => {
+            let load = base == "ldr";
+            if operands.is_empty() {
+                return Err("expected a register and an address".into());
+            }
+            let rd = register(operands[0])?;
+            let address = Address::parse(&operands[1..], env, literal)?;
+            match suffix {
+                "" | "b" | "t" | "bt" => {
+                    if let Address::Literal(Literal::Constant(value)) = address {
+                        if suffix.is_empty() {
+                            return Ok(cond << 28 | move_immediate(rd, value)?);
+                        }
+                    }
+                    let byte = suffix.starts_with('b');
+                    let translate = suffix.ends_with('t');
+                    single_transfer(load, byte, translate, rd, address, env.address)?
+                }
+                _ => halfword_transfer(load, suffix, rd, address, env.address)?,
+            }
+        }
+
+        "ldm" | "stm" => {
+            expect_operands(operands, 2)?;
+            let load = base == "ldm";
+            let (base_register, writeback) = match operands[0].trim().strip_suffix('!') {
+                Some(rn) => (register(rn)?, true),
+                None => (register(operands[0])?, false),
+            };
+            let (registers, caret) = register_list(operands[1])?;
+            let (pre, up) = match (load, suffix) {
+                (_, "" | "ia") | (true, "fd") | (false, "ea") => (false, true),
+                (_, "ib") | (true, "ed") | (false, "fa") => (true, true),
+                (_, "da") | (true, "fa") | (false, "ed") => (false, false),
+                (_, "db") | (true, "ea") | (false, "fd") => (true, false),
+                _ => unreachable!(),
+            };
+            0x08000000
+                | (pre as u32) << 24
+                | (up as u32) << 23
+                | (caret as u32) << 22
+                | (writeback as u32) << 21
+                | (load as u32) << 20
+                | base_register << 16
+                | registers as u32
+        }
+
+        "push" | "pop" => {
+            expect_operands(operands, 1)?;
+            let (registers, _) = register_list(operands[0])?;
+            let push = base == "push";
+            // A single register is transferred with `str rX, [sp, #-4]!` or
+            // `ldr rX, [sp], #4` like GNU as does.
+            if registers.count_ones() == 1 {
+                let rd = registers.trailing_zeros();
+                if push {
+                    0x052D0004 | rd << 12
+                } else {
+                    0x049D0004 | rd << 12
+                }
+            } else if push {
+                0x092D0000 | registers as u32
+            } else {
+                0x08BD0000 | registers as u32
+            }
+        }
+
+        "swp" => {
+            expect_operands(operands, 3)?;
+            let rd = register(operands[0])?;
+            let rm = register(operands[1])?;
+            let rn = operands[2]
+                .trim()
+                .strip_prefix('[')
+                .and_then(|s| s.strip_suffix(']'))
+                .ok_or("expected `[rn]`")
+                .and_then(|rn| register(rn).map_err(|_| "expected `[rn]`"))?;
+            0x01000090 | ((suffix == "b") as u32) << 22 | rn << 16 | rd << 12 | rm
+        }
+
+        "swi" | "svc" => {
+            expect_operands(operands, 1)?;
+            let comment = immediate(operands[0], env)?;
+            if !(0..1 << 24).contains(&comment) {
+                return Err(format!("`{}` is out of range", operands[0]));
+            }
+            0x0F000000 | comment as u32
+        }
+
+        "mrs" => {
+            expect_operands(operands, 2)?;
+            let rd = register(operands[0])?;
+            let spsr = match operands[1].trim().to_ascii_lowercase().as_str() {
+                "cpsr" | "cpsr_all" => false,
+                "spsr" | "spsr_all" => true,
+                psr => return Err(format!("expected cpsr or spsr, found `{psr}`")),
+            };
+            0x010F0000 | (spsr as u32) << 22 | rd << 12
+        }
+
+        "msr" => {
+            expect_operands(operands, 2)?;
+            let (spsr, mask) = psr_fields(operands[0])?;
+            let source = if is_immediate(operands[1]) {
+                let value = immediate32(operands[1], env)?;
+                let value = encode_immediate(value)
+                    .ok_or_else(|| format!("invalid constant `{}`", operands[1]))?;
+                0x02000000 | value
+            } else {
+                register(operands[1])?
+            };
+            0x0120F000 | (spsr as u32) << 22 | mask << 16 | source
+        }
+
+        "adr" => {
+            expect_operands(operands, 2)?;
+            let rd = register(operands[0])?;
+            let target = env.eval(operands[1])? as u32;
+            let offset = target.wrapping_sub(env.address.wrapping_add(8)) as i32;
+            let (opcode, offset) = if offset >= 0 {
+                (ADD, offset as u32)
+            } else {
+                (SUB, offset.unsigned_abs())
+            };
+            let offset = encode_immediate(offset)
+                .ok_or_else(|| format!("`{}` is too far away for adr", operands[1]))?;
+            0x02000000 | opcode << 21 | 15 << 16 | rd << 12 | offset
+        }
+
+        "bx" => {
+            expect_operands(operands, 1)?;
+            0x012FFF10 | register(operands[0])?
+        }
+
+        "b" | "bl" => {
+            expect_operands(operands, 1)?;
+            let target = env.eval(operands[0])? as u32;
+            let offset = branch_offset(target, env.address.wrapping_add(8), 4, 24)?;
+            0x0A000000 | ((base == "bl") as u32) << 24 | offset
+        }
+
+        _ => unreachable!("unhandled mnemonic {base}"),
+    };
+
+    Ok(cond << 28 | opcode)
+}
+
+fn data_processing(opcode: u32, s: bool, operands: &[&str], env: &Env) -> Result<u32, String> {
+    if operands.len() < 2 {
+        return Err("expected at least 2 operands".into());
+    }
+
+    let (rd, rn, operand2) = match opcode {
+        MOV | MVN => (register(operands[0])?, 0, &operands[1..]),
+        TST | TEQ | CMP | CMN => (0, register(operands[0])?, &operands[1..]),
+        // `op rd, operand2` is short for `op rd, rd, operand2`.
+        _ if operands.len() == 2 || (operands.len() == 3 && is_shift(operands[2])) => {
+            let rd = register(operands[0])?;
+            (rd, rd, &operands[1..])
+        }
+        _ => (
+            register(operands[0])?,
+            register(operands[1])?,
+            &operands[2..],
+        ),
+    };
+
+    let (opcode, operand2) = if is_immediate(operand2[0]) {
+        if operand2.len() > 1 {
+            return Err("immediate operands can't be shifted".into());
+        }
+        let value = immediate32(operand2[0], env)?;
+        immediate_operand(opcode, value)
+            .ok_or_else(|| format!("invalid constant `{}`", operand2[0]))?
+    } else {
+        if operand2.len() > 2 {
+            return Err("too many operands".into());
+        }
+        let rm = register(operand2[0])?;
+        let shift = match operand2.get(1) {
+            Some(shift) => Shift::parse(shift, env)?,
+            None => Shift::NONE,
+        };
+        (opcode, shift.encode_arm() | rm)
+    };
+
+    Ok(opcode << 21 | (s as u32) << 20 | rn << 16 | rd << 12 | operand2)
+}
+
+/// Encodes an immediate operand, switching to the complementary instruction (e.g. `mvn` for
+/// `mov`) if the value can only be encoded that way. Returns the opcode and operand bits.
+fn immediate_operand(opcode: u32, value: u32) -> Option<(u32, u32)> {
+    if let Some(immediate) = encode_immediate(value) {
+        return Some((opcode, 0x02000000 | immediate));
+    }
+
+    let (opcode, value) = match opcode {
+        MOV => (MVN, !value),
+        MVN => (MOV, !value),
+        AND => (BIC, !value),
+        BIC => (AND, !value),
+        ADC => (SBC, !value),
+        SBC => (ADC, !value),
+        ADD => (SUB, value.wrapping_neg()),
+        SUB => (ADD, value.wrapping_neg()),
+        CMP => (CMN, value.wrapping_neg()),
+        CMN => (CMP, value.wrapping_neg()),
+        _ => return None,
+    };
+    encode_immediate(value).map(|immediate| (opcode, 0x02000000 | immediate))
+}
+
+/// `mov rd, #value` or `mvn rd, #~value` for literal loads of small constants.
+fn move_immediate(rd: u32, value: u32) -> Result<u32, String> {
+    let (opcode, operand2) =
+        immediate_operand(MOV, value).ok_or_else(|| format!("invalid constant 0x{value:X}"))?;
+    Ok(opcode << 21 | rd << 12 | operand2)
+}
+
+/// Encodes the `lsl rd, rm, #n` style shift instructions as moves.
+fn shift(kind: &str, s: bool, operands: &[&str], env: &Env) -> Result<u32, String> {
+    let rd = register(operands.first().ok_or("expected a register")?)?;
+    let (rm, amount) = match operands.len() {
+        2 if kind == "rrx" => (register(operands[1])?, None),
+        2 => (rd, Some(operands[1])),
+        3 if kind != "rrx" => (register(operands[1])?, Some(operands[2])),
+        _ => return Err("invalid operands".into()),
+    };
+    let shift = match amount {
+        Some(amount) => Shift::parse(&format!("{kind} {amount}"), env)?,
+        None => Shift::Rrx,
+    };
+    Ok(MOV << 21 | (s as u32) << 20 | rd << 12 | shift.encode_arm() | rm)
+}
+
+/// Returns the offset from the PC to `target` as an immediate offset.
+fn pc_relative(target: u32, address: u32) -> Offset {
+    let offset = target.wrapping_sub(address.wrapping_add(8)) as i32;
+    Offset::Immediate {
+        value: offset.unsigned_abs(),
+        subtract: offset < 0,
+    }
+}
+
+fn single_transfer(
+    load: bool,
+    byte: bool,
+    translate: bool,
+    rd: u32,
+    address: Address,
+    instruction_address: u32,
+) -> Result<u32, String> {
+    let (base, offset, pre, writeback) = match address {
+        Address::PreIndexed { .. } if translate => {
+            return Err("translated transfers must be post-indexed".into());
+        }
+        Address::PreIndexed {
+            base,
+            offset,
+            writeback,
+        } => (base, offset, true, writeback),
+        Address::PostIndexed { base, offset } => (base, offset, false, translate),
+        Address::Label(target) | Address::Literal(Literal::Pool(target)) => {
+            (15, pc_relative(target, instruction_address), true, false)
+        }
+        Address::Literal(Literal::Constant(_)) => {
+            return Err("literal loads must load a word".into());
+        }
+    };
+
+    let (offset, subtract) = match offset {
+        Offset::Immediate { value, subtract } => {
+            if value > 0xFFF {
+                return Err(format!("offset {value} is out of range"));
+            }
+            (value, subtract)
+        }
+        Offset::Register {
+            shift: Shift::Register(..),
+            ..
+        } => {
+            return Err("offsets can't be shifted by a register".into());
+        }
+        Offset::Register {
+            rm,
+            shift,
+            subtract,
+        } => (0x02000000 | shift.encode_arm() | rm, subtract),
+    };
+
+    Ok(0x04000000
+        | (pre as u32) << 24
+        | (!subtract as u32) << 23
+        | (byte as u32) << 22
+        | (writeback as u32) << 21
+        | (load as u32) << 20
+        | base << 16
+        | rd << 12
+        | offset)
+}
+
+fn halfword_transfer(
+    load: bool,
+    suffix: &str,
+    rd: u32,
+    address: Address,
+    instruction_address: u32,
+) -> Result<u32, String> {
+    let sh = match (load, suffix) {
+        (_, "h") => 0b01,
+        (true, "sb") => 0b10,
+        (true, "sh") => 0b11,
+        _ => return Err(format!("invalid transfer type `{suffix}`")),
+    };
+
+    let (base, offset, pre, writeback) = match address {
+        Address::PreIndexed {
+            base,
+            offset,
+            writeback,
+        } => (base, offset, true, writeback),
+        Address::PostIndexed { base, offset } => (base, offset, false, false),
+        Address::Label(target) | Address::Literal(Literal::Pool(target)) => {
+            (15, pc_relative(target, instruction_address), true, false)
+        }
+        Address::Literal(Literal::Constant(_)) => {
+            return Err("literal loads must load a word".into());
+        }
+    };
+
+    let (offset, subtract) = match offset {
+        Offset::Immediate { value, subtract } => {
+            if value > 0xFF {
+                return Err(format!("offset {value} is out of range"));
+            }
+            (1 << 22 | (value & 0xF0) << 4 | value & 0xF, subtract)
+        }
+        Offset::Register {
+            rm,
+            shift: Shift::NONE,
+            subtract,
+        } => (rm, subtract),
+        Offset::Register { .. } => return Err("halfword offsets can't be shifted".into()),
+    };
+
+    Ok(0x00000090
+        | (pre as u32) << 24
+        | (!subtract as u32) << 23
+        | (writeback as u32) << 21
+        | (load as u32) << 20
+        | base << 16
+        | rd << 12
+        | sh << 5
+        | offset)
+}
+
+/// Parses `cpsr_fc` style PSR names into whether they refer to the SPSR and the field mask.
+fn psr_fields(s: &str) -> Result<(bool, u32), String> {
+    let lower = s.trim().to_ascii_lowercase();
+    let (psr, fields) = lower.split_once('_').unwrap_or((&lower, ""));
+    let spsr = match psr {
+        "cpsr" => false,
+        "spsr" => true,
+        _ => return Err(format!("expected cpsr or spsr, found `{s}`")),
+    };
+
+    let mask = match fields {
+        "" | "all" => 0b1001,
+        "flg" => 0b1000,
+        "ctl" => 0b0001,
+        fields => fields.chars().try_fold(0, |mask, field| match field {
+            'c' => Ok(mask | 0b0001),
+            'x' => Ok(mask | 0b0010),
+            's' => Ok(mask | 0b0100),
+            'f' => Ok(mask | 0b1000),
+            _ => Err(format!("invalid PSR fields in `{s}`")),
+        })?,
+    };
+    Ok((spsr, mask))
+}
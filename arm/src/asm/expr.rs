@@ -0,0 +1,165 @@
+use std::collections::HashMap;
+
+/// Everything that an expression can refer to.
+pub(super) struct Env<'a> {
+    pub symbols: &'a HashMap<String, i64>,
+    /// The address of the current statement, used for `.`.
+    pub address: u32,
+}
+
+impl Env<'_> {
+    /// Evaluates an expression made of numbers, symbols, `.`, parentheses, unary `-`/`~` and
+    /// binary `+`, `-`, `*`, `<<`, `>>`, `&` and `|`.
+    pub fn eval(&self, expr: &str) -> Result<i64, String> {
+        let mut parser = Parser {
+            env: self,
+            src: expr.trim(),
+            pos: 0,
+        };
+        let value = parser.expr(0)?;
+        parser.skip_whitespace();
+        if parser.pos != parser.src.len() {
+            return Err(format!(
+                "unexpected `{}` in expression `{expr}`",
+                &parser.src[parser.pos..]
+            ));
+        }
+        Ok(value)
+    }
+}
+
+struct Parser<'e, 's> {
+    env: &'e Env<'e>,
+    src: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_, '_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.src[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.src[self.pos..].chars().next()
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        self.skip_whitespace();
+        if self.src[self.pos..].starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Parses binary operators with a precedence of at least `min_precedence`.
+    fn expr(&mut self, min_precedence: u8) -> Result<i64, String> {
+        const OPERATORS: [(&str, u8); 7] = [
+            ("|", 0),
+            ("&", 1),
+            ("<<", 2),
+            (">>", 2),
+            ("+", 3),
+            ("-", 3),
+            ("*", 4),
+        ];
+
+        let mut lhs = self.unary()?;
+        loop {
+            self.skip_whitespace();
+            let rest = &self.src[self.pos..];
+            let op = OPERATORS
+                .iter()
+                .find(|&&(op, precedence)| precedence >= min_precedence && rest.starts_with(op));
+            let (op, precedence) = match op {
+                Some(&op) => op,
+                None => return Ok(lhs),
+            };
+            self.pos += op.len();
+
+            let rhs = self.expr(precedence + 1)?;
+            lhs = match op {
+                "|" => lhs | rhs,
+                "&" => lhs & rhs,
+                "<<" => lhs.wrapping_shl(rhs as u32),
+                ">>" => lhs.wrapping_shr(rhs as u32),
+                "+" => lhs.wrapping_add(rhs),
+                "-" => lhs.wrapping_sub(rhs),
+                "*" => lhs.wrapping_mul(rhs),
+                _ => unreachable!(),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        if self.eat("-") {
+            return self.unary().map(i64::wrapping_neg);
+        }
+        if self.eat("~") {
+            return self.unary().map(|value| !value);
+        }
+        if self.eat("+") {
+            return self.unary();
+        }
+        if self.eat("(") {
+            let value = self.expr(0)?;
+            if !self.eat(")") {
+                return Err("expected `)`".into());
+            }
+            return Ok(value);
+        }
+
+        let start = self.pos;
+        match self.peek() {
+            Some(c) if c.is_ascii_digit() => {
+                let len = self.src[start..]
+                    .find(|c: char| !c.is_ascii_alphanumeric())
+                    .unwrap_or(self.src.len() - start);
+                self.pos += len;
+                parse_number(&self.src[start..self.pos])
+            }
+
+            Some(c) if is_symbol_start(c) => {
+                let len = self.src[start..]
+                    .find(|c: char| !is_symbol_char(c))
+                    .unwrap_or(self.src.len() - start);
+                self.pos += len;
+                let name = &self.src[start..self.pos];
+                if name == "." {
+                    return Ok(self.env.address as i64);
+                }
+                self.env
+                    .symbols
+                    .get(name)
+                    .copied()
+                    .ok_or_else(|| format!("undefined symbol `{name}`"))
+            }
+
+            Some(c) => Err(format!("unexpected `{c}` in expression")),
+            None => Err("expected an expression".into()),
+        }
+    }
+}
+
+fn parse_number(s: &str) -> Result<i64, String> {
+    let lower = s.to_ascii_lowercase();
+    let result = if let Some(hex) = lower.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = lower.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else {
+        lower.parse()
+    };
+    result.map_err(|_| format!("invalid number `{s}`"))
+}
+
+pub(super) fn is_symbol_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_' || c == '.' || c == '$'
+}
+
+pub(super) fn is_symbol_char(c: char) -> bool {
+    is_symbol_start(c) || c.is_ascii_digit()
+}
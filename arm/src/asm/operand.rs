@@ -0,0 +1,352 @@
+//! Parsing for the operands shared by ARM and THUMB instructions.
+
+use super::expr::Env;
+
+/// The value loaded by `ldr rX, =expr`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Literal {
+    /// The value is small enough to be loaded with a move instead.
+    Constant(u32),
+    /// The address of the value in a literal pool.
+    Pool(u32),
+}
+
+/// Splits operands on commas that aren't inside of brackets or braces.
+pub fn split_operands(operands: &str) -> Vec<&str> {
+    let operands = operands.trim();
+    if operands.is_empty() {
+        return Vec::new();
+    }
+
+    let mut result = Vec::new();
+    let mut depth = 0i32;
+    let mut start = 0;
+    for (idx, c) in operands.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(operands[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(operands[start..].trim());
+    result
+}
+
+pub fn parse_register(s: &str) -> Option<u32> {
+    let s = s.trim().to_ascii_lowercase();
+    let register = match s.as_str() {
+        "sb" => 9,
+        "sl" => 10,
+        "fp" => 11,
+        "ip" => 12,
+        "sp" => 13,
+        "lr" => 14,
+        "pc" => 15,
+        _ => s.strip_prefix('r')?.parse().ok()?,
+    };
+    (register < 16).then_some(register)
+}
+
+pub fn register(s: &str) -> Result<u32, String> {
+    parse_register(s).ok_or_else(|| format!("expected a register, found `{s}`"))
+}
+
+/// A register in r0-r7 for THUMB instructions that can't use the high registers.
+pub fn low_register(s: &str) -> Result<u32, String> {
+    match register(s)? {
+        register @ 0..=7 => Ok(register),
+        _ => Err(format!("expected a register in r0-r7, found `{s}`")),
+    }
+}
+
+/// Parses `#expr`. The `#` is optional.
+pub fn immediate(s: &str, env: &Env) -> Result<i64, String> {
+    let s = s.trim();
+    env.eval(s.strip_prefix('#').unwrap_or(s))
+}
+
+pub fn is_immediate(s: &str) -> bool {
+    s.trim_start().starts_with('#')
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShiftType {
+    Lsl = 0,
+    Lsr = 1,
+    Asr = 2,
+    Ror = 3,
+}
+
+impl ShiftType {
+    pub fn parse(s: &str) -> Option<ShiftType> {
+        match s.to_ascii_lowercase().as_str() {
+            "lsl" | "asl" => Some(ShiftType::Lsl),
+            "lsr" => Some(ShiftType::Lsr),
+            "asr" => Some(ShiftType::Asr),
+            "ror" => Some(ShiftType::Ror),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shift {
+    Immediate(ShiftType, u32),
+    Register(ShiftType, u32),
+    Rrx,
+}
+
+impl Shift {
+    pub const NONE: Shift = Shift::Immediate(ShiftType::Lsl, 0);
+
+    /// Parses shifts like `lsl #2`, `asr r3` or `rrx`.
+    pub fn parse(s: &str, env: &Env) -> Result<Shift, String> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("rrx") {
+            return Ok(Shift::Rrx);
+        }
+
+        let split = s
+            .find(|c: char| c.is_whitespace() || c == '#')
+            .unwrap_or(s.len());
+        let (kind, amount) = s.split_at(split);
+        let kind = ShiftType::parse(kind).ok_or_else(|| format!("invalid shift `{s}`"))?;
+        let amount = amount.trim();
+
+        if let Some(register) = parse_register(amount) {
+            return Ok(Shift::Register(kind, register));
+        }
+
+        let amount = immediate(amount, env)?;
+        let valid = match kind {
+            ShiftType::Lsl => (0..=31).contains(&amount),
+            ShiftType::Lsr | ShiftType::Asr => (1..=32).contains(&amount),
+            ShiftType::Ror => (1..=31).contains(&amount),
+        };
+        if !valid {
+            return Err(format!("invalid shift amount in `{s}`"));
+        }
+        Ok(Shift::Immediate(kind, amount as u32))
+    }
+
+    /// Returns the 8 bits of the ARM shifter operand above Rm.
+    pub fn encode_arm(self) -> u32 {
+        match self {
+            // LSR #32 and ASR #32 are encoded as a shift by 0.
+            Shift::Immediate(kind, amount) => ((amount & 0x1F) << 7) | ((kind as u32) << 5),
+            Shift::Register(kind, rs) => (rs << 8) | ((kind as u32) << 5) | 0x10,
+            Shift::Rrx => (ShiftType::Ror as u32) << 5,
+        }
+    }
+}
+
+/// Parses a register list like `{r0-r3, lr}`. Returns the register bits and whether the list
+/// was followed by `^`.
+pub fn register_list(s: &str) -> Result<(u16, bool), String> {
+    let s = s.trim();
+    let (s, caret) = match s.strip_suffix('^') {
+        Some(s) => (s.trim(), true),
+        None => (s, false),
+    };
+    let inner = s
+        .strip_prefix('{')
+        .and_then(|s| s.strip_suffix('}'))
+        .ok_or_else(|| format!("expected a register list, found `{s}`"))?;
+
+    let mut registers = 0u16;
+    for item in inner.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (register(first)?, register(last)?);
+                if first > last {
+                    return Err(format!("invalid register range `{item}`"));
+                }
+                (first..=last).for_each(|r| registers |= 1 << r);
+            }
+            None => registers |= 1 << register(item)?,
+        }
+    }
+
+    if registers == 0 {
+        return Err("empty register list".into());
+    }
+    Ok((registers, caret))
+}
+
+/// An offset added to or subtracted from the base register of a memory access.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Offset {
+    Immediate {
+        value: u32,
+        subtract: bool,
+    },
+    Register {
+        rm: u32,
+        shift: Shift,
+        subtract: bool,
+    },
+}
+
+impl Offset {
+    const ZERO: Offset = Offset::Immediate {
+        value: 0,
+        subtract: false,
+    };
+
+    fn parse(s: &str, shift: Option<&str>, env: &Env) -> Result<Offset, String> {
+        let s = s.trim();
+        if is_immediate(s) {
+            if shift.is_some() {
+                return Err("immediate offsets can't be shifted".into());
+            }
+            let value = immediate(s, env)?;
+            // `#-0` is kept as a subtraction like GNU as does.
+            let negative_zero = value == 0 && s[1..].trim_start().starts_with('-');
+            return Ok(Offset::Immediate {
+                value: value.unsigned_abs() as u32,
+                subtract: value < 0 || negative_zero,
+            });
+        }
+
+        let (rm, subtract) = match s.strip_prefix('-') {
+            Some(rm) => (rm, true),
+            None => (s.strip_prefix('+').unwrap_or(s), false),
+        };
+        let shift = shift.map_or(Ok(Shift::NONE), |shift| Shift::parse(shift, env))?;
+        Ok(Offset::Register {
+            rm: register(rm)?,
+            shift,
+            subtract,
+        })
+    }
+}
+
+/// The address operand of a load or store.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Address {
+    /// `[rn, offset]` with optional writeback.
+    PreIndexed {
+        base: u32,
+        offset: Offset,
+        writeback: bool,
+    },
+    /// `[rn], offset`
+    PostIndexed { base: u32, offset: Offset },
+    /// A label, which is addressed relative to the PC.
+    Label(u32),
+    /// `=expr`
+    Literal(Literal),
+}
+
+impl Address {
+    /// Parses the operands of a load or store that come after the destination register.
+    pub fn parse(
+        operands: &[&str],
+        env: &Env,
+        literal: Option<Literal>,
+    ) -> Result<Address, String> {
+        let first = operands.first().ok_or("expected an address")?.trim();
+
+        if first.starts_with('=') {
+            if operands.len() > 1 {
+                return Err("unexpected operands after literal".into());
+            }
+            return literal
+                .map(Address::Literal)
+                .ok_or_else(|| "literal loads aren't supported here".into());
+        }
+
+        if !first.starts_with('[') {
+            if operands.len() > 1 {
+                return Err("unexpected operands after label".into());
+            }
+            return Ok(Address::Label(env.eval(first)? as u32));
+        }
+
+        let (inner, writeback) = match first.strip_suffix('!') {
+            Some(first) => (first.trim(), true),
+            None => (first, false),
+        };
+        let inner = inner
+            .strip_prefix('[')
+            .and_then(|s| s.strip_suffix(']'))
+            .ok_or_else(|| format!("invalid address `{first}`"))?;
+        let inner = split_operands(inner);
+        let base = register(inner[0])?;
+
+        if inner.len() > 1 {
+            if operands.len() > 1 {
+                return Err("unexpected operands after address".into());
+            }
+            if inner.len() > 3 {
+                return Err(format!("invalid address `{first}`"));
+            }
+            let offset = Offset::parse(inner[1], inner.get(2).copied(), env)?;
+            return Ok(Address::PreIndexed {
+                base,
+                offset,
+                writeback,
+            });
+        }
+
+        if operands.len() == 1 {
+            return Ok(Address::PreIndexed {
+                base,
+                offset: Offset::ZERO,
+                writeback,
+            });
+        }
+
+        if writeback || operands.len() > 3 {
+            return Err(format!("invalid address `{}`", operands.join(", ")));
+        }
+        let offset = Offset::parse(operands[1], operands.get(2).copied(), env)?;
+        Ok(Address::PostIndexed { base, offset })
+    }
+}
+
+/// Parses a condition code suffix.
+pub fn condition(s: &str) -> Option<u32> {
+    const CONDITIONS: [(&str, u32); 17] = [
+        ("eq", 0x0),
+        ("ne", 0x1),
+        ("cs", 0x2),
+        ("hs", 0x2),
+        ("cc", 0x3),
+        ("lo", 0x3),
+        ("mi", 0x4),
+        ("pl", 0x5),
+        ("vs", 0x6),
+        ("vc", 0x7),
+        ("hi", 0x8),
+        ("ls", 0x9),
+        ("ge", 0xA),
+        ("lt", 0xB),
+        ("gt", 0xC),
+        ("le", 0xD),
+        ("al", 0xE),
+    ];
+    CONDITIONS
+        .iter()
+        .find(|&&(name, _)| name == s)
+        .map(|&(_, cond)| cond)
+}
+
+/// Returns the offset of a branch target from the PC, checking that it fits in `bits` bits
+/// once divided by `align`.
+pub fn branch_offset(target: u32, pc: u32, align: u32, bits: u32) -> Result<u32, String> {
+    let offset = target.wrapping_sub(pc) as i32;
+    if offset % align as i32 != 0 {
+        return Err(format!("misaligned branch target 0x{target:08X}"));
+    }
+    let offset = offset / align as i32;
+    let limit = 1i32 << (bits - 1);
+    if !(-limit..limit).contains(&offset) {
+        return Err(format!("branch target 0x{target:08X} is out of range"));
+    }
+    Ok(offset as u32 & ((1 << bits) - 1))
+}
@@ -0,0 +1,402 @@
+//! Encodes THUMB instructions.
+
+use super::expr::Env;
+use super::operand::{
+    branch_offset, condition, immediate, is_immediate, low_register, parse_register, register,
+    register_list, Address, Literal, Offset, Shift,
+};
+
+/// Opcodes of the format 4 ALU operations.
+const ALU_OPERATIONS: [&str; 16] = [
+    "and", "eor", "lsl", "lsr", "asr", "adc", "sbc", "ror", "tst", "neg", "cmp", "cmn", "orr",
+    "mul", "bic", "mvn",
+];
+
+/// `mov r8, r8`
+const NOP: u16 = 0x46C0;
+
+/// Returns the size in bytes of a THUMB instruction.
+pub(super) fn size(mnemonic: &str) -> u32 {
+    if mnemonic.eq_ignore_ascii_case("bl") {
+        4
+    } else {
+        2
+    }
+}
+
+fn expect_operands(operands: &[&str], count: usize) -> Result<(), String> {
+    if operands.len() == count {
+        Ok(())
+    } else {
+        Err(format!(
+            "expected {count} operands, found {}",
+            operands.len()
+        ))
+    }
+}
+
+/// Returns an immediate that must be in `0..=max` and a multiple of `scale`, divided by
+/// `scale`.
+fn scaled_immediate(value: i64, scale: i64, max: i64) -> Result<u16, String> {
+    if !(0..=max).contains(&value) {
+        return Err(format!("immediate {value} is out of range"));
+    }
+    if value % scale != 0 {
+        return Err(format!("immediate {value} must be a multiple of {scale}"));
+    }
+    Ok((value / scale) as u16)
+}
+
+/// Encodes a THUMB instruction. `bl` is the only instruction that returns two halfwords.
+pub(super) fn encode(
+    mnemonic: &str,
+    operands: &[&str],
+    env: &Env,
+    literal: Option<Literal>,
+) -> Result<(u16, Option<u16>), String> {
+    let mnemonic = mnemonic.to_ascii_lowercase();
+
+    match mnemonic.as_str() {
+        "bl" => {
+            expect_operands(operands, 1)?;
+            let target = env.eval(operands[0])? as u32;
+            let offset = branch_offset(target, env.address.wrapping_add(4), 2, 22)?;
+            let high = 0xF000 | (offset >> 11) as u16;
+            let low = 0xF800 | (offset & 0x7FF) as u16;
+            return Ok((high, Some(low)));
+        }
+        "bx" => {
+            expect_operands(operands, 1)?;
+            return Ok((0x4700 | (register(operands[0])? as u16) << 3, None));
+        }
+        "b" => {
+            expect_operands(operands, 1)?;
+            let target = env.eval(operands[0])? as u32;
+            let offset = branch_offset(target, env.address.wrapping_add(4), 2, 11)?;
+            return Ok((0xE000 | offset as u16, None));
+        }
+        _ => {}
+    }
+
+    if let Some(cond) = mnemonic.strip_prefix('b').and_then(condition) {
+        if cond == 0xE {
+            return Err("`bal` isn't a valid THUMB instruction".into());
+        }
+        expect_operands(operands, 1)?;
+        let target = env.eval(operands[0])? as u32;
+        let offset = branch_offset(target, env.address.wrapping_add(4), 2, 8)?;
+        return Ok((0xD000 | (cond as u16) << 8 | offset as u16, None));
+    }
+
+    // Flag setting is implied for most THUMB instructions so the UAL `s` suffix is accepted and
+    // ignored.
+    let base = match mnemonic.strip_suffix('s') {
+        Some(base) if ALU_OPERATIONS.contains(&base) || ["add", "sub", "mov"].contains(&base) => {
+            base
+        }
+        _ => mnemonic.as_str(),
+    };
+
+    let opcode = match base {
+        "lsl" | "lsr" | "asr"
+            if operands.len() == 3 || is_immediate(operands.last().unwrap_or(&"")) =>
+        {
+            shift_immediate(base, operands, env)?
+        }
+        "add" | "sub" => add_sub(base == "sub", operands, env)?,
+        "mov" => mov(operands, env)?,
+        "cmp" => cmp(operands, env)?,
+        "mul" => {
+            // `mul rd, rs, rd` is accepted as well as `mul rd, rs`.
+            if operands.len() == 3 && register(operands[2])? != register(operands[0])? {
+                return Err("the destination must be the same as the last operand".into());
+            }
+            if operands.len() != 3 {
+                expect_operands(operands, 2)?;
+            }
+            alu(13, operands[0], operands[1])?
+        }
+        _ if ALU_OPERATIONS.contains(&base) => {
+            expect_operands(operands, 2)?;
+            let op = ALU_OPERATIONS.iter().position(|&op| op == base).unwrap();
+            alu(op as u16, operands[0], operands[1])?
+        }
+
+        "ldr" | "str" | "ldrb" | "strb" | "ldrh" | "strh" | "ldsb" | "ldrsb" | "ldsh" | "ldrsh" => {
+            transfer(base, operands, env, literal)?
+        }
+
+        "push" | "pop" => {
+            expect_operands(operands, 1)?;
+            let (registers, _) = register_list(operands[0])?;
+            let (extra, extra_bit) = if base == "push" {
+                (14, 0)
+            } else {
+                (15, 1 << 11)
+            };
+            if registers & !(0xFF | 1 << extra) != 0 {
+                return Err(format!("invalid register list for {base}"));
+            }
+            let r = (registers >> extra) & 1;
+            0xB400 | extra_bit | r << 8 | registers & 0xFF
+        }
+
+        "ldmia" | "stmia" | "ldm" | "stm" => {
+            expect_operands(operands, 2)?;
+            let rb = operands[0]
+                .trim()
+                .strip_suffix('!')
+                .ok_or("THUMB block transfers must use writeback")?;
+            let rb = low_register(rb)? as u16;
+            let (registers, _) = register_list(operands[1])?;
+            if registers > 0xFF {
+                return Err("THUMB block transfers can only use r0-r7".into());
+            }
+            let load = (base.starts_with("ldm") as u16) << 11;
+            0xC000 | load | rb << 8 | registers
+        }
+
+        "swi" | "svc" => {
+            expect_operands(operands, 1)?;
+            let comment = immediate(operands[0], env)?;
+            0xDF00 | scaled_immediate(comment, 1, 0xFF)?
+        }
+
+        "adr" => {
+            expect_operands(operands, 2)?;
+            let rd = low_register(operands[0])? as u16;
+            let target = env.eval(operands[1])? as u32;
+            let offset = target.wrapping_sub(env.address.wrapping_add(4) & !3) as i32;
+            0xA000 | rd << 8 | scaled_immediate(offset as i64, 4, 1020)?
+        }
+
+        "nop" => {
+            expect_operands(operands, 0)?;
+            NOP
+        }
+
+        _ => return Err(format!("unknown instruction `{mnemonic}`")),
+    };
+
+    Ok((opcode, None))
+}
+
+/// Format 4 ALU operations on two low registers.
+fn alu(op: u16, rd: &str, rs: &str) -> Result<u16, String> {
+    let rd = low_register(rd)? as u16;
+    let rs = low_register(rs)? as u16;
+    Ok(0x4000 | op << 6 | rs << 3 | rd)
+}
+
+/// Format 5 operations on two registers where at least one is a high register.
+fn hi_register(op: u16, rd: u32, rs: u32) -> u16 {
+    let h1 = (rd >> 3) as u16;
+    0x4400 | op << 8 | h1 << 7 | (rs as u16) << 3 | (rd & 7) as u16
+}
+
+/// `lsl rd, rs, #offset`
+fn shift_immediate(kind: &str, operands: &[&str], env: &Env) -> Result<u16, String> {
+    let (rd, rs, amount) = match operands.len() {
+        2 => (operands[0], operands[0], operands[1]),
+        3 => (operands[0], operands[1], operands[2]),
+        _ => return Err("invalid operands".into()),
+    };
+    let rd = low_register(rd)? as u16;
+    let rs = low_register(rs)? as u16;
+    let shift = Shift::parse(&format!("{kind} {amount}"), env)?;
+    let (op, amount) = match shift {
+        Shift::Immediate(kind, amount) => (kind as u16, amount as u16 & 0x1F),
+        _ => return Err("expected an immediate shift amount".into()),
+    };
+    Ok(op << 11 | amount << 6 | rs << 3 | rd)
+}
+
+fn add_sub(sub: bool, operands: &[&str], env: &Env) -> Result<u16, String> {
+    if operands.len() < 2 {
+        return Err("expected at least 2 operands".into());
+    }
+    let rd = register(operands[0])?;
+
+    // `add sp, #imm` and `add sp, sp, #imm`
+    if rd == 13 && is_immediate(operands[operands.len() - 1]) {
+        if operands.len() == 3 && register(operands[1])? != 13 {
+            return Err("expected sp".into());
+        }
+        let value = immediate(operands[operands.len() - 1], env)?;
+        let negative = (value < 0) != sub;
+        let value = scaled_immediate(value.abs(), 4, 508)?;
+        return Ok(0xB000 | (negative as u16) << 7 | value);
+    }
+
+    // `add rd, pc, #imm` and `add rd, sp, #imm`
+    if operands.len() == 3 && !sub {
+        if let Some(source @ (13 | 15)) = parse_register(operands[1]) {
+            let rd = low_register(operands[0])? as u16;
+            let value = immediate(operands[2], env)?;
+            let sp = (source == 13) as u16;
+            return Ok(0xA000 | sp << 11 | rd << 8 | scaled_immediate(value, 4, 1020)?);
+        }
+    }
+
+    let op = sub as u16;
+    match operands.len() {
+        2 if is_immediate(operands[1]) => {
+            let rd = low_register(operands[0])? as u16;
+            let value = immediate(operands[1], env)?;
+            Ok(0x3000 | op << 11 | rd << 8 | scaled_immediate(value, 1, 0xFF)?)
+        }
+        2 => {
+            let rs = register(operands[1])?;
+            if rd < 8 && rs < 8 {
+                Ok(0x1800 | op << 9 | (rs as u16) << 6 | (rd as u16) << 3 | rd as u16)
+            } else if sub {
+                Err("sub can't use high registers".into())
+            } else {
+                Ok(hi_register(0, rd, rs))
+            }
+        }
+        3 => {
+            let rd = low_register(operands[0])? as u16;
+            let rs = low_register(operands[1])? as u16;
+            if is_immediate(operands[2]) {
+                let value = immediate(operands[2], env)?;
+                if value > 7 && rd == rs {
+                    return Ok(0x3000 | op << 11 | rd << 8 | scaled_immediate(value, 1, 0xFF)?);
+                }
+                let value = scaled_immediate(value, 1, 7)?;
+                Ok(0x1C00 | op << 9 | value << 6 | rs << 3 | rd)
+            } else {
+                let rn = low_register(operands[2])? as u16;
+                Ok(0x1800 | op << 9 | rn << 6 | rs << 3 | rd)
+            }
+        }
+        _ => Err("too many operands".into()),
+    }
+}
+
+fn mov(operands: &[&str], env: &Env) -> Result<u16, String> {
+    expect_operands(operands, 2)?;
+    if is_immediate(operands[1]) {
+        let rd = low_register(operands[0])? as u16;
+        let value = immediate(operands[1], env)?;
+        return Ok(0x2000 | rd << 8 | scaled_immediate(value, 1, 0xFF)?);
+    }
+
+    let rd = register(operands[0])?;
+    let rs = register(operands[1])?;
+    if rd < 8 && rs < 8 {
+        // Moving between low registers is encoded as `add rd, rs, #0` like GNU as does.
+        Ok(0x1C00 | (rs as u16) << 3 | rd as u16)
+    } else {
+        Ok(hi_register(2, rd, rs))
+    }
+}
+
+fn cmp(operands: &[&str], env: &Env) -> Result<u16, String> {
+    expect_operands(operands, 2)?;
+    if is_immediate(operands[1]) {
+        let rd = low_register(operands[0])? as u16;
+        let value = immediate(operands[1], env)?;
+        return Ok(0x2800 | rd << 8 | scaled_immediate(value, 1, 0xFF)?);
+    }
+
+    let rd = register(operands[0])?;
+    let rs = register(operands[1])?;
+    if rd < 8 && rs < 8 {
+        alu(10, operands[0], operands[1])
+    } else {
+        Ok(hi_register(1, rd, rs))
+    }
+}
+
+/// Returns the offset of a PC-relative load from the word aligned PC.
+fn pc_relative(target: u32, address: u32) -> Result<u16, String> {
+    let offset = target.wrapping_sub(address.wrapping_add(4) & !3) as i32;
+    scaled_immediate(offset as i64, 4, 1020)
+        .map_err(|_| format!("0x{target:08X} can't be loaded relative to the PC"))
+}
+
+fn transfer(
+    base: &str,
+    operands: &[&str],
+    env: &Env,
+    literal: Option<Literal>,
+) -> Result<u16, String> {
+    if operands.is_empty() {
+        return Err("expected a register and an address".into());
+    }
+    let rd = low_register(operands[0])? as u16;
+    let load = base.starts_with("ld");
+    let address = Address::parse(&operands[1..], env, literal)?;
+
+    let (rb, offset) = match address {
+        Address::Literal(Literal::Constant(value)) if base == "ldr" => {
+            return Ok(0x2000 | rd << 8 | scaled_immediate(value as i64, 1, 0xFF)?);
+        }
+        Address::Label(target) | Address::Literal(Literal::Pool(target)) if base == "ldr" => {
+            return Ok(0x4800 | rd << 8 | pc_relative(target, env.address)?);
+        }
+        Address::PreIndexed {
+            writeback: false,
+            base,
+            offset,
+        } => (base, offset),
+        _ => return Err("unsupported addressing mode".into()),
+    };
+
+    match offset {
+        Offset::Register {
+            rm,
+            shift: Shift::NONE,
+            subtract: false,
+        } => {
+            if rb > 7 || rm > 7 {
+                return Err("register offset transfers can only use r0-r7".into());
+            }
+            let (rb, ro) = (rb as u16, rm as u16);
+            let op = match base {
+                "str" => 0x5000,
+                "strb" => 0x5400,
+                "ldr" => 0x5800,
+                "ldrb" => 0x5C00,
+                "strh" => 0x5200,
+                "ldrh" => 0x5A00,
+                "ldsb" | "ldrsb" => 0x5600,
+                "ldsh" | "ldrsh" => 0x5E00,
+                _ => unreachable!(),
+            };
+            Ok(op | ro << 6 | rb << 3 | rd)
+        }
+
+        Offset::Immediate {
+            value,
+            subtract: false,
+        } => {
+            let value = value as i64;
+            let load = (load as u16) << 11;
+            match (base, rb) {
+                ("ldr" | "str", 15) if load != 0 => {
+                    Ok(0x4800 | rd << 8 | scaled_immediate(value, 4, 1020)?)
+                }
+                ("ldr" | "str", 13) => {
+                    Ok(0x9000 | load | rd << 8 | scaled_immediate(value, 4, 1020)?)
+                }
+                _ => {
+                    if rb > 7 {
+                        return Err("base registers must be in r0-r7".into());
+                    }
+                    let rb = rb as u16;
+                    let (op, offset) = match base {
+                        "ldr" | "str" => (0x6000, scaled_immediate(value, 4, 124)?),
+                        "ldrb" | "strb" => (0x7000, scaled_immediate(value, 1, 31)?),
+                        "ldrh" | "strh" => (0x8000, scaled_immediate(value, 2, 62)?),
+                        _ => return Err(format!("{base} requires a register offset")),
+                    };
+                    Ok(op | load | offset << 6 | rb << 3 | rd)
+                }
+            }
+        }
+
+        _ => Err("unsupported offset".into()),
+    }
+}
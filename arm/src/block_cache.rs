@@ -0,0 +1,155 @@
+//! Cache of decoded straight-line runs of instructions used by [`Cpu::run`](crate::Cpu::run).
+//!
+//! Blocks are keyed by the address of their first instruction and are filled in as they are
+//! executed. Each block remembers the [`Memory::code_version`] of the pages it was decoded from
+//! and is thrown away once either of them changes, so code that is overwritten (e.g. code copied
+//! into IWRAM) is decoded again. Blocks in memory that doesn't track writes are instead checked
+//! against the opcodes that the pipeline actually fetches and are cut short at the first one that
+//! doesn't match.
+
+use super::{InstrFunction, Memory, CODE_PAGE_SIZE};
+use util::bits::Bits as _;
+
+/// Number of blocks that can be cached at once. Blocks that map to the same slot evict each
+/// other.
+const CACHE_SLOTS: usize = 1024;
+
+/// Maximum number of instructions in a single block.
+pub const MAX_BLOCK_LEN: usize = 64;
+
+// A block can only span the pages containing its first and last possible instructions.
+const _: () = assert!(MAX_BLOCK_LEN as u32 * 4 <= CODE_PAGE_SIZE);
+
+#[derive(Clone, Copy)]
+pub struct CachedInstr {
+    pub opcode: u32,
+    pub function: InstrFunction,
+    /// True if the instruction reads or writes memory (other than fetching) or otherwise has
+    /// side effects outside of the CPU.
+    pub accesses_memory: bool,
+}
+
+impl CachedInstr {
+    pub fn arm(opcode: u32, function: InstrFunction) -> Self {
+        CachedInstr {
+            opcode,
+            function,
+            accesses_memory: arm_accesses_memory(opcode),
+        }
+    }
+
+    pub fn thumb(opcode: u32, function: InstrFunction) -> Self {
+        CachedInstr {
+            opcode,
+            function,
+            accesses_memory: thumb_accesses_memory(opcode),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Slot {
+    /// Address of the first instruction with bit 0 set for THUMB blocks, or `None` if the slot
+    /// is empty.
+    key: Option<u32>,
+    /// Code versions of the pages the block was decoded from. See [`BlockCache::versions`].
+    versions: Option<[u32; 2]>,
+    instructions: Vec<CachedInstr>,
+}
+
+pub struct BlockCache {
+    slots: Box<[Slot]>,
+}
+
+impl BlockCache {
+    pub fn key(address: u32, thumb: bool) -> u32 {
+        address | thumb as u32
+    }
+
+    /// Returns the code versions of the pages that a block starting at `address` could have been
+    /// decoded from, or `None` if writes to either of them aren't tracked.
+    pub fn versions(memory: &dyn Memory, address: u32, thumb: bool) -> Option<[u32; 2]> {
+        let instr_size = if thumb { 2 } else { 4 };
+        let last = address.wrapping_add((MAX_BLOCK_LEN as u32 - 1) * instr_size);
+        let first = memory.code_version(address)?;
+        if address / CODE_PAGE_SIZE == last / CODE_PAGE_SIZE {
+            Some([first, first])
+        } else {
+            Some([first, memory.code_version(last)?])
+        }
+    }
+
+    /// Takes the instructions cached for the block starting at `key` out of the cache, or an
+    /// empty block if there aren't any or they were decoded from code that has since been
+    /// written to. They should be put back with [`BlockCache::insert`].
+    pub fn take(&mut self, key: u32, versions: Option<[u32; 2]>) -> Vec<CachedInstr> {
+        let slot = &mut self.slots[Self::slot_index(key)];
+        let mut instructions = std::mem::take(&mut slot.instructions);
+        if slot.key != Some(key) || slot.versions != versions {
+            instructions.clear();
+        }
+        instructions
+    }
+
+    /// Puts a block back into the cache. `versions` must be the code versions from before any of
+    /// its instructions were fetched.
+    pub fn insert(&mut self, key: u32, versions: Option<[u32; 2]>, instructions: Vec<CachedInstr>) {
+        let slot = &mut self.slots[Self::slot_index(key)];
+        slot.key = Some(key);
+        slot.versions = versions;
+        slot.instructions = instructions;
+    }
+
+    fn slot_index(key: u32) -> usize {
+        // THUMB instructions are only halfword aligned, so bit 1 is the lowest useful bit.
+        ((key >> 1) ^ (key >> 11)) as usize % CACHE_SLOTS
+    }
+}
+
+impl Default for BlockCache {
+    fn default() -> Self {
+        BlockCache {
+            slots: (0..CACHE_SLOTS).map(|_| Slot::default()).collect(),
+        }
+    }
+}
+
+/// Returns true for ARM single data transfers, halfword and signed data transfers, swaps, block
+/// data transfers, coprocessor instructions, and PSR writes.
+fn arm_accesses_memory(opcode: u32) -> bool {
+    let row = opcode.bits(20, 27);
+    let col = opcode.bits(4, 7);
+    match row {
+        // Data processing, multiplies, swaps, PSR transfers, and halfword transfers.
+        0x00..=0x1F => {
+            let halfword_transfer = col == 0xB || col == 0xD || col == 0xF;
+            let swap = col == 0x9 && row >= 0x10;
+            let msr = (row == 0x12 || row == 0x16) && col == 0x0;
+            halfword_transfer || swap || msr
+        }
+        // MSR with an immediate operand.
+        0x32 | 0x36 => true,
+        // Single and block data transfers.
+        0x40..=0x9F => true,
+        // Coprocessor instructions.
+        0xC0..=0xEF => true,
+        _ => false,
+    }
+}
+
+/// Returns true for THUMB loads, stores, pushes, and pops.
+fn thumb_accesses_memory(opcode: u32) -> bool {
+    let row = opcode.bits(12, 15);
+    let col = opcode.bits(8, 11);
+    match row {
+        // PC relative loads.
+        0x4 => col >= 0x8,
+        // Register offset, immediate offset, halfword, and SP relative transfers.
+        0x5..=0x9 => true,
+        // PUSH and POP.
+        0xB => col & 0x6 == 0x4,
+        // LDMIA and STMIA.
+        0xC => true,
+        _ => false,
+    }
+}
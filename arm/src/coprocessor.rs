@@ -0,0 +1,56 @@
+/// A coprocessor that can be attached to one of the CPU's 16 coprocessor slots using
+/// [`Cpu::set_coprocessor`](crate::Cpu::set_coprocessor).
+///
+/// Every method has a default implementation that doesn't respond to the instruction. When no
+/// coprocessor responds the ARM7TDMI takes the undefined instruction exception, which is also
+/// what happens for empty slots. Register numbers and opcode fields are passed in as they are
+/// encoded in the instruction.
+pub trait Coprocessor: Send {
+    /// CDP: performs a coprocessor data operation. Returns false if the operation isn't
+    /// supported.
+    fn cdp(&mut self, opcode1: u32, crd: u32, crn: u32, crm: u32, opcode2: u32) -> bool {
+        let _ = (opcode1, crd, crn, crm, opcode2);
+        false
+    }
+
+    /// MCR: writes a value from an ARM register to a coprocessor register. Returns false if the
+    /// register can't be written.
+    fn mcr(&mut self, opcode1: u32, crn: u32, crm: u32, opcode2: u32, value: u32) -> bool {
+        let _ = (opcode1, crn, crm, opcode2, value);
+        false
+    }
+
+    /// MRC: reads a coprocessor register into an ARM register. Returns `None` if the register
+    /// can't be read.
+    fn mrc(&mut self, opcode1: u32, crn: u32, crm: u32, opcode2: u32) -> Option<u32> {
+        let _ = (opcode1, crn, crm, opcode2);
+        None
+    }
+
+    /// LDC: returns the number of words that will be loaded into `crd`, or `None` if the load
+    /// isn't supported. `long` is the N bit of the instruction. Each word is then passed to
+    /// [`Coprocessor::ldc_word`].
+    fn ldc(&mut self, long: bool, crd: u32) -> Option<u32> {
+        let _ = (long, crd);
+        None
+    }
+
+    /// Receives the word at `index` of a transfer started by [`Coprocessor::ldc`].
+    fn ldc_word(&mut self, crd: u32, index: u32, value: u32) {
+        let _ = (crd, index, value);
+    }
+
+    /// STC: returns the number of words that will be stored from `crd`, or `None` if the store
+    /// isn't supported. `long` is the N bit of the instruction. Each word is then read using
+    /// [`Coprocessor::stc_word`].
+    fn stc(&mut self, long: bool, crd: u32) -> Option<u32> {
+        let _ = (long, crd);
+        None
+    }
+
+    /// Returns the word at `index` of a transfer started by [`Coprocessor::stc`].
+    fn stc_word(&mut self, crd: u32, index: u32) -> u32 {
+        let _ = (crd, index);
+        0
+    }
+}
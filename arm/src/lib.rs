@@ -1,13 +1,18 @@
 mod alu;
 mod arm_instructions;
+pub mod asm;
+mod block_cache;
+mod coprocessor;
 mod memory;
 mod registers;
 mod thumb_instructions;
 
-pub use memory::{AccessType, Memory, Waitstates};
+pub use coprocessor::Coprocessor;
+pub use memory::{AccessType, Memory, Waitstates, CODE_PAGE_SIZE};
 pub use registers::CpuMode;
 pub use registers::Registers;
 
+use block_cache::{BlockCache, CachedInstr};
 use util::bits::Bits as _;
 
 /// Function that executes and ARM instruction and returns the number of cycles
@@ -40,11 +45,17 @@ pub enum Isa {
 pub struct Cpu {
     pub registers: Registers,
     pub exception_handler: Option<ExceptionHandler>,
+    coprocessors: [Option<Box<dyn Coprocessor>>; 16],
 
     // pipeline:
     fetched: u32,
     decoded: u32,
     decoded_fn: InstrFunction,
+    /// Set when fetching the opcode in the matching pipeline stage aborted.
+    fetched_abort: bool,
+    decoded_abort: bool,
+
+    block_cache: BlockCache,
 }
 
 impl Cpu {
@@ -65,9 +76,13 @@ impl Cpu {
         Cpu {
             registers,
             exception_handler: None,
+            coprocessors: Default::default(),
             fetched: noop_opcode,
             decoded: noop_opcode,
             decoded_fn: noop,
+            fetched_abort: false,
+            decoded_abort: false,
+            block_cache: BlockCache::default(),
         }
     }
 
@@ -93,22 +108,111 @@ impl Cpu {
         }
     }
 
+    /// Runs a straight-line run of instructions, using cached decoded instructions where possible.
+    /// This always executes at least one instruction and stops before and after any instruction
+    /// that accesses memory, after the PC or state changes (branches, exceptions, ect.), or once
+    /// at least `budget` cycles have elapsed. This returns the number of cycles that were required
+    /// to run the instructions.
+    ///
+    /// Because instructions that access memory always run on their own, callers that keep track
+    /// of time between calls (e.g. for scheduled events) will see memory accesses happen at the
+    /// same time as they would with [`Cpu::step`], and can react to anything those accesses did
+    /// before any more instructions run.
+    pub fn run(&mut self, memory: &mut dyn Memory, budget: Cycles) -> Cycles {
+        let thumb = self.registers.getf_t();
+        let instr_size = if thumb { 2 } else { 4 };
+        let start = self.next_exec_pc();
+        let key = BlockCache::key(start, thumb);
+
+        let versions = BlockCache::versions(memory, start, thumb);
+        let mut block = self.block_cache.take(key, versions);
+        if block.first().map(|instr| instr.opcode) != Some(self.decoded) {
+            block.clear();
+            block.push(if thumb {
+                CachedInstr::thumb(self.decoded, self.decoded_fn)
+            } else {
+                CachedInstr::arm(self.decoded, self.decoded_fn)
+            });
+        }
+
+        let mut cycles = Cycles::ZERO;
+        let mut index = 0;
+        loop {
+            let exec_accesses_memory = block[index].accesses_memory;
+
+            // The fetched opcode is decoded during this step and is the next instruction in the
+            // block.
+            let next = index + 1;
+            // The first two instructions were already in the pipeline before this run and might
+            // have been fetched before the code was last written, so they are always checked.
+            // Later ones only need to be checked if writes to the code aren't tracked, because
+            // the block would have been thrown away otherwise.
+            let next_instr = match block.get(next) {
+                Some(instr) if (next > 1 && versions.is_some()) || instr.opcode == self.fetched => {
+                    *instr
+                }
+                _ => {
+                    block.truncate(next);
+                    let instr = if thumb {
+                        CachedInstr::thumb(self.fetched, Self::decode_thumb_opcode(self.fetched))
+                    } else {
+                        CachedInstr::arm(self.fetched, Self::decode_arm_opcode(self.fetched))
+                    };
+                    block.push(instr);
+                    instr
+                }
+            };
+
+            cycles += if thumb {
+                self.step_thumb_decoded(memory, next_instr.function)
+            } else {
+                self.step_arm_decoded(memory, next_instr.function)
+            };
+            index = next;
+
+            let expected_pc = start.wrapping_add(index as u32 * instr_size);
+            if cycles >= budget
+                || exec_accesses_memory
+                || next_instr.accesses_memory
+                || index + 1 >= block_cache::MAX_BLOCK_LEN
+                || self.registers.getf_t() != thumb
+                || self.next_exec_pc() != expected_pc
+            {
+                break;
+            }
+        }
+
+        self.block_cache.insert(key, versions, block);
+        cycles
+    }
+
     /// Returns the number of cycles required to step the CPU in the ARM state.
     fn step_arm(&mut self, memory: &mut dyn Memory) -> Cycles {
+        self.step_arm_decoded(memory, Self::decode_arm_opcode(self.fetched))
+    }
+
+    /// Steps the CPU in the ARM state with the already decoded function for the fetched opcode.
+    #[inline(always)]
+    fn step_arm_decoded(&mut self, memory: &mut dyn Memory, fetched_fn: InstrFunction) -> Cycles {
         let exec_opcode = self.decoded;
         let exec_fn = self.decoded_fn;
+        let exec_abort = self.decoded_abort;
 
         self.decoded = self.fetched;
-        self.decoded_fn = Self::decode_arm_opcode(self.decoded);
+        self.decoded_fn = fetched_fn;
+        self.decoded_abort = self.fetched_abort;
 
         let fetch_pc = (self.registers.read(15) & !0x3).wrapping_add(4);
         self.registers.write(15, fetch_pc);
         let (fetched, fetch_wait) = memory.fetch32(fetch_pc, AccessType::Seq);
         self.fetched = fetched;
+        self.fetched_abort = memory.take_abort();
 
         let cycles = Cycles::ONE + fetch_wait;
 
-        if check_condition(exec_opcode >> 28, &self.registers) {
+        if util::unlikely!(exec_abort) {
+            cycles + self.prefetch_abort(memory)
+        } else if check_condition(exec_opcode >> 28, &self.registers) {
             cycles + exec_fn(self, memory, exec_opcode)
         } else {
             cycles
@@ -117,19 +221,32 @@ impl Cpu {
 
     /// Returns the number of cycles required to step the CPU in the THUMB state.
     fn step_thumb(&mut self, memory: &mut dyn Memory) -> Cycles {
+        self.step_thumb_decoded(memory, Self::decode_thumb_opcode(self.fetched))
+    }
+
+    /// Steps the CPU in the THUMB state with the already decoded function for the fetched opcode.
+    #[inline(always)]
+    fn step_thumb_decoded(&mut self, memory: &mut dyn Memory, fetched_fn: InstrFunction) -> Cycles {
         let exec_opcode = self.decoded;
         let exec_fn = self.decoded_fn;
+        let exec_abort = self.decoded_abort;
 
         self.decoded = self.fetched;
-        self.decoded_fn = Self::decode_thumb_opcode(self.decoded);
+        self.decoded_fn = fetched_fn;
+        self.decoded_abort = self.fetched_abort;
 
         let fetch_pc = (self.registers.read(15) & !0x1).wrapping_add(2);
         self.registers.write(15, fetch_pc);
-        let (fetched, fetch_wait) = memory.load16(fetch_pc, AccessType::Seq);
+        let (fetched, fetch_wait) = memory.fetch16(fetch_pc, AccessType::Seq);
         self.fetched = fetched as u32;
+        self.fetched_abort = memory.take_abort();
 
         let cycles = Cycles::ONE + fetch_wait;
-        cycles + exec_fn(self, memory, exec_opcode)
+        if util::unlikely!(exec_abort) {
+            cycles + self.prefetch_abort(memory)
+        } else {
+            cycles + exec_fn(self, memory, exec_opcode)
+        }
     }
 
     /// The address of the instruction that will be executed next.
@@ -153,7 +270,9 @@ impl Cpu {
         let address = address & !0x3;
 
         let (decoded, wd) = memory.fetch32(address, AccessType::NonSeq);
+        self.decoded_abort = memory.take_abort();
         let (fetched, wf) = memory.fetch32(address.wrapping_add(4), AccessType::Seq);
+        self.fetched_abort = memory.take_abort();
 
         self.decoded = decoded;
         self.decoded_fn = Self::decode_arm_opcode(decoded);
@@ -168,7 +287,9 @@ impl Cpu {
         let address = address & !0x1;
 
         let (decoded, wd) = memory.fetch16(address, AccessType::NonSeq);
+        self.decoded_abort = memory.take_abort();
         let (fetched, wf) = memory.fetch16(address.wrapping_add(2), AccessType::Seq);
+        self.fetched_abort = memory.take_abort();
 
         self.decoded = decoded as u32;
         self.decoded_fn = Self::decode_thumb_opcode(decoded as u32);
@@ -208,6 +329,24 @@ impl Cpu {
         self.exception_handler.replace(Box::new(handler))
     }
 
+    /// Attaches a coprocessor as CP0-CP15, returning the one that was previously attached there.
+    /// Coprocessor instructions for empty slots raise the undefined instruction exception.
+    pub fn set_coprocessor<C>(
+        &mut self,
+        number: u32,
+        coprocessor: C,
+    ) -> Option<Box<dyn Coprocessor>>
+    where
+        C: 'static + Coprocessor,
+    {
+        self.coprocessors[number as usize].replace(Box::new(coprocessor))
+    }
+
+    /// Detaches the coprocessor attached as CP0-CP15.
+    pub fn remove_coprocessor(&mut self, number: u32) -> Option<Box<dyn Coprocessor>> {
+        self.coprocessors[number as usize].take()
+    }
+
     pub fn exception(&mut self, exception: CpuException, memory: &mut dyn Memory) -> Cycles {
         self.exception_with_ret(exception, self.next_exec_pc(), memory)
     }
@@ -222,6 +361,31 @@ impl Cpu {
         self.exception_with_ret(exception, return_addr, memory)
     }
 
+    /// Enters the data abort exception from the instruction that is executing. Instructions
+    /// restore any registers they modified before calling this so that the handler can retry
+    /// them (the "base restored" abort model).
+    ///
+    /// R14_abt is set to the address of the aborted instruction + 8 in both ARM and THUMB state.
+    fn data_abort(&mut self, memory: &mut dyn Memory) -> Cycles {
+        let return_addr = self
+            .registers
+            .read(15)
+            .wrapping_sub(if self.registers.getf_t() { 0 } else { 4 });
+        self.exception_with_ret(CpuException::DataAbort, return_addr, memory)
+    }
+
+    /// Enters the prefetch abort exception in place of executing an instruction whose fetch
+    /// aborted.
+    ///
+    /// R14_abt is set to the address of the aborted instruction + 4 in both ARM and THUMB state.
+    fn prefetch_abort(&mut self, memory: &mut dyn Memory) -> Cycles {
+        let return_addr = self
+            .registers
+            .read(15)
+            .wrapping_sub(if self.registers.getf_t() { 4 } else { 8 });
+        self.exception_with_ret(CpuException::PrefetchAbort, return_addr, memory)
+    }
+
     /// Actions performed by CPU when entering an exception
     ///   - R14_<new mode>=PC+nn   ;save old PC, ie. return address
     ///   - SPSR_<new mode>=CPSR   ;save old flags
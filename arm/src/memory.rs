@@ -21,8 +21,35 @@ pub trait Memory {
     fn stall(&mut self, _cycles: super::Cycles) {
         /* NOP */
     }
+
+    /// Returns true if a fetch, load, or store made since the last call aborted and clears the
+    /// abort. Implementations with some form of memory protection use this to signal faults.
+    ///
+    /// The value returned by an aborted load is discarded, and an aborted store should not
+    /// modify memory. Instructions with aborted loads or stores restore any registers that
+    /// they modified (including written back base registers) and then enter
+    /// [`CpuException::DataAbort`](crate::CpuException::DataAbort). Aborted fetches enter
+    /// [`CpuException::PrefetchAbort`](crate::CpuException::PrefetchAbort) if the instruction
+    /// reaches the execute stage of the pipeline.
+    fn take_abort(&mut self) -> bool {
+        false
+    }
+
+    /// Returns a number that changes whenever anything in the [`CODE_PAGE_SIZE`] aligned page
+    /// containing `address` is written. [`Cpu::run`](crate::Cpu::run) uses this to throw away
+    /// cached blocks of decoded instructions once the code they were decoded from is
+    /// overwritten.
+    ///
+    /// Returns `None` (the default) if writes to the page aren't tracked, in which case every
+    /// cached instruction is checked against the opcode that was actually fetched instead.
+    fn code_version(&self, _address: u32) -> Option<u32> {
+        None
+    }
 }
 
+/// Size of the pages that [`Memory::code_version`] tracks writes to.
+pub const CODE_PAGE_SIZE: u32 = 1024;
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
 pub enum AccessType {
@@ -6,6 +6,9 @@ use util::bits::Bits as _;
 #[inline(always)]
 fn sdt_ldr(cpu: &mut Cpu, memory: &mut dyn Memory, rd: u32, addr: u32) -> Cycles {
     let (value, wait) = memory.load32(addr & 0xFFFFFFFC, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
 
     // From the ARM7TDMI Documentation:
     //      A word load will normally use a word aligned address, however,
@@ -33,6 +36,9 @@ fn sdt_str(cpu: &mut Cpu, memory: &mut dyn Memory, rd: u32, addr: u32) -> Cycles
     // if rd == 15 { value = value.wrapping_add(4); }
 
     let wait = memory.store32(addr & 0xFFFFFFFC, value, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
 
     Cycles::ONE + wait
 }
@@ -402,6 +408,9 @@ fn thumb_ldr_pc(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32, rd: u32) ->
     // @ NOTE I just do a raw read here instead of an sdt_ldr because the address will always
     //        be word aligned.
     let (data, wait) = memory.load32(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, data);
 
     Cycles::ONE + wait
@@ -467,6 +476,9 @@ pub fn thumb_strb_reg(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cy
 
     let value = cpu.registers.read(rd);
     let wait = memory.store8(addr, (value & 0xFF) as u8, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
 
     Cycles::ONE + wait
 }
@@ -481,6 +493,9 @@ pub fn thumb_ldrb_reg(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cy
     let addr = base.wrapping_add(offset);
 
     let (value, wait) = memory.load8(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, value as u32);
 
     Cycles::ONE + wait
@@ -497,6 +512,9 @@ pub fn thumb_strh_reg(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cy
 
     let value = cpu.registers.read(rd) & 0xFFFF;
     let wait = memory.store16(addr, value as u16, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
 
     Cycles::ONE + wait
 }
@@ -511,6 +529,9 @@ pub fn thumb_ldrsb_reg(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> C
     let addr = base.wrapping_add(offset);
 
     let (value, wait) = memory.load8(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, value as i8 as i32 as u32);
 
     Cycles::ONE + wait
@@ -526,6 +547,9 @@ pub fn thumb_ldrh_reg(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cy
     let addr = base.wrapping_add(offset);
 
     let (value, wait) = memory.load16(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, value as u32);
 
     Cycles::ONE + wait
@@ -541,6 +565,9 @@ pub fn thumb_ldrsh_reg(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> C
     let addr = base.wrapping_add(offset);
 
     let (value, wait) = memory.load16(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, value as i16 as i32 as u32);
 
     Cycles::ONE + wait
@@ -579,6 +606,9 @@ pub fn thumb_strb_imm5(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> C
 
     let value = cpu.registers.read(rd);
     let wait = memory.store8(addr, (value & 0xFF) as u8, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
 
     Cycles::ONE + wait
 }
@@ -592,6 +622,9 @@ pub fn thumb_ldrb_imm5(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> C
     let addr = base.wrapping_add(offset);
 
     let (value, wait) = memory.load8(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, value as u32);
 
     Cycles::ONE + wait
@@ -607,6 +640,9 @@ pub fn thumb_strh_imm5(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> C
 
     let value = cpu.registers.read(rd) & 0xFFFF;
     let wait = memory.store16(addr, value as u16, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
 
     Cycles::ONE + wait
 }
@@ -620,6 +656,9 @@ pub fn thumb_ldrh_imm5(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> C
     let addr = base.wrapping_add(offset);
 
     let (value, wait) = memory.load16(addr, AccessType::NonSeq);
+    if util::unlikely!(memory.take_abort()) {
+        return Cycles::ONE + wait + cpu.data_abort(memory);
+    }
     cpu.registers.write(rd, value as u32);
 
     Cycles::ONE + wait
@@ -706,7 +745,7 @@ fn thumb_addpc(cpu: &mut Cpu, _memory: &mut dyn Memory, opcode: u32, rd: u32) ->
 
 #[inline(always)]
 fn thumb_addsp(cpu: &mut Cpu, _memory: &mut dyn Memory, opcode: u32, rd: u32) -> Cycles {
-    let offset = ((opcode & 0xFF) << 2).sign_extend(10);
+    let offset = (opcode & 0xFF) << 2;
     let sp = cpu.registers.read(13);
     cpu.registers.write(rd, sp.wrapping_add(offset));
 
@@ -797,6 +836,10 @@ pub fn thumb_push(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cycles
             let value = cpu.registers.read(reg);
             let wait = memory.store32(addr, value, access_type);
             cycles += Cycles::ONE + wait;
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(13, base);
+                return cycles + cpu.data_abort(memory);
+            }
 
             if access_type == AccessType::NonSeq {
                 access_type = AccessType::Seq;
@@ -830,6 +873,10 @@ pub fn thumb_push_lr(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cyc
             let value = cpu.registers.read(reg);
             let wait = memory.store32(addr, value, access_type);
             cycles += Cycles::ONE + wait;
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(13, base);
+                return cycles + cpu.data_abort(memory);
+            }
 
             if access_type == AccessType::NonSeq {
                 access_type = AccessType::Seq;
@@ -842,6 +889,10 @@ pub fn thumb_push_lr(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cyc
     let value = cpu.registers.read(14);
     let wait = memory.store32(addr, value, access_type);
     cycles += Cycles::ONE + wait;
+    if util::unlikely!(memory.take_abort()) {
+        cpu.registers.write(13, base);
+        return cycles + cpu.data_abort(memory);
+    }
 
     cycles
 }
@@ -867,9 +918,12 @@ pub fn thumb_pop(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cycles
             addr = addr.wrapping_add(4);
 
             let (value, wait) = memory.load32(addr, access_type);
-            cpu.registers.write(reg, value);
-
             cycles += Cycles::ONE + wait;
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(13, base);
+                return cycles + cpu.data_abort(memory);
+            }
+            cpu.registers.write(reg, value);
 
             if access_type == AccessType::NonSeq {
                 access_type = AccessType::Seq;
@@ -910,9 +964,12 @@ pub fn thumb_pop_pc(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cycl
             addr = addr.wrapping_add(4);
 
             let (value, wait) = memory.load32(addr, access_type);
-            cpu.registers.write(reg, value);
-
             cycles += Cycles::ONE + wait;
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(13, base);
+                return cycles + cpu.data_abort(memory);
+            }
+            cpu.registers.write(reg, value);
 
             if access_type == AccessType::NonSeq {
                 access_type = AccessType::Seq;
@@ -932,8 +989,13 @@ pub fn thumb_pop_pc(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32) -> Cycl
     // transfer PC
     addr = addr.wrapping_add(4);
     let (value, wait) = memory.load32(addr, access_type);
+    cycles += wait;
+    if util::unlikely!(memory.take_abort()) {
+        cpu.registers.write(13, base);
+        return cycles + cpu.data_abort(memory);
+    }
     let dest = value & 0xFFFFFFFE;
-    cycles += cpu.branch_thumb(dest, memory) + wait;
+    cycles += cpu.branch_thumb(dest, memory);
 
     cycles
 }
@@ -945,6 +1007,18 @@ fn thumb_stmia(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32, rb: u32) ->
     let reg_count = register_list.count_ones();
     let base = cpu.registers.read(rb);
 
+    // With an empty register list R15 is stored instead (as PC+2, so $+6) and the base
+    // register is incremented by 0x40 as if all 16 registers had been transferred.
+    if register_list == 0 {
+        let value = cpu.registers.read(15).wrapping_add(2);
+        cycles += memory.store32(base, value, AccessType::NonSeq);
+        if util::unlikely!(memory.take_abort()) {
+            return cycles + cpu.data_abort(memory);
+        }
+        cpu.registers.write(rb, base.wrapping_add(0x40));
+        return cycles;
+    }
+
     // the lowest register always goes into the lowest address so we precalculate the lowest
     let mut addr = base.wrapping_sub(4);
 
@@ -957,6 +1031,10 @@ fn thumb_stmia(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32, rb: u32) ->
             let value = cpu.registers.read(reg);
             let wait = memory.store32(addr, value, access_type);
             cycles += wait;
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(rb, base);
+                return cycles + cpu.data_abort(memory);
+            }
 
             if access_type == AccessType::NonSeq {
                 access_type = AccessType::Seq;
@@ -979,6 +1057,20 @@ fn thumb_ldmia(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32, rb: u32) ->
     let reg_count = register_list.count_ones();
     let base = cpu.registers.read(rb);
 
+    // With an empty register list R15 is loaded instead and the base register is incremented
+    // by 0x40 as if all 16 registers had been transferred.
+    if register_list == 0 {
+        let (value, wait) = memory.load32(base, AccessType::NonSeq);
+        cycles += Cycles::ONE + wait;
+        if util::unlikely!(memory.take_abort()) {
+            return cycles + cpu.data_abort(memory);
+        }
+        cpu.registers.write(rb, base.wrapping_add(0x40));
+        memory.stall(Cycles::ONE);
+        cycles += cpu.branch_thumb(value & 0xFFFFFFFE, memory);
+        return cycles;
+    }
+
     // the lowest register always goes into the lowest address so we precalculate the lowest
     let mut addr = base.wrapping_sub(4);
 
@@ -993,6 +1085,10 @@ fn thumb_ldmia(cpu: &mut Cpu, memory: &mut dyn Memory, opcode: u32, rb: u32) ->
 
             let (value, wait) = memory.load32(addr, access_type);
             cycles += wait;
+            if util::unlikely!(memory.take_abort()) {
+                cpu.registers.write(rb, base);
+                return cycles + cpu.data_abort(memory);
+            }
             cpu.registers.write(reg, value);
 
             if access_type == AccessType::NonSeq {
@@ -0,0 +1,197 @@
+mod common;
+
+use arm::{CpuMode, Isa};
+use common::Executor;
+
+/// Loads, stores, and fetches from this address abort.
+const ABORT_ADDRESS: u32 = 0x10000000;
+
+fn execute_with_abort(name: &str, isa: Isa, source: &str) -> Executor {
+    let mut exec = Executor::new(name, isa);
+    exec.mem
+        .set_abort_range(ABORT_ADDRESS..ABORT_ADDRESS + 0x1000);
+    exec.push(source);
+    exec
+}
+
+#[test]
+pub fn test_ldr_data_abort() {
+    let exec = execute_with_abort(
+        "ldr-data-abort",
+        Isa::Arm,
+        "
+        b       main
+        b       _exit           @ undefined vector
+        b       _exit           @ swi vector
+        b       _exit           @ prefetch abort vector
+        b       data_abort_handler
+    main:
+        ldr     r0, =0x10000000
+        mov     r2, #7
+        ldr     r3, =aborted
+    aborted:
+        ldr     r2, [r0, #4]!
+        mov     r5, #1
+        b       _exit
+    data_abort_handler:
+        mov     r4, r14
+        subs    r15, r14, #4    @ skip the aborted instruction
+        ",
+    );
+    let registers = &exec.cpu.registers;
+    assert_eq!(registers.read(0), ABORT_ADDRESS, "base was not restored");
+    assert_eq!(registers.read(2), 7);
+    assert_eq!(registers.read(4), registers.read(3) + 8);
+    assert_eq!(registers.read(5), 1);
+    assert_eq!(registers.read_mode(), CpuMode::System);
+}
+
+#[test]
+pub fn test_str_data_abort() {
+    let exec = execute_with_abort(
+        "str-data-abort",
+        Isa::Arm,
+        "
+        b       main
+        b       _exit           @ undefined vector
+        b       _exit           @ swi vector
+        b       _exit           @ prefetch abort vector
+        b       data_abort_handler
+    main:
+        ldr     r0, =0x10000000
+        mov     r2, #7
+        str     r2, [r0], #4
+        strh    r2, [r0, #2]!
+        b       _exit
+    data_abort_handler:
+        add     r5, r5, #1
+        subs    r15, r14, #4
+        ",
+    );
+    let registers = &exec.cpu.registers;
+    assert_eq!(registers.read(0), ABORT_ADDRESS, "base was not restored");
+    assert_eq!(registers.read(5), 2);
+}
+
+#[test]
+pub fn test_block_transfer_data_abort() {
+    let exec = execute_with_abort(
+        "block-transfer-data-abort",
+        Isa::Arm,
+        "
+        b       main
+        b       _exit           @ undefined vector
+        b       _exit           @ swi vector
+        b       _exit           @ prefetch abort vector
+        b       data_abort_handler
+    main:
+        ldr     r0, =0x10000000
+        mov     r1, #1
+        mov     r2, #2
+        ldmia   r0!, {r1, r2}
+        stmib   r0!, {r1, r2}
+        ldmia   r0, {r0, r1}
+        b       _exit
+    data_abort_handler:
+        add     r5, r5, #1
+        subs    r15, r14, #4
+        ",
+    );
+    let registers = &exec.cpu.registers;
+    assert_eq!(registers.read(0), ABORT_ADDRESS, "base was not restored");
+    assert_eq!(registers.read(1), 1);
+    assert_eq!(registers.read(2), 2);
+    assert_eq!(registers.read(5), 3);
+}
+
+#[test]
+pub fn test_swp_data_abort() {
+    let exec = execute_with_abort(
+        "swp-data-abort",
+        Isa::Arm,
+        "
+        b       main
+        b       _exit           @ undefined vector
+        b       _exit           @ swi vector
+        b       _exit           @ prefetch abort vector
+        b       data_abort_handler
+    main:
+        ldr     r0, =0x10000000
+        mov     r1, #1
+        mov     r2, #2
+        swp     r1, r2, [r0]
+        swpb    r1, r2, [r0]
+        b       _exit
+    data_abort_handler:
+        add     r5, r5, #1
+        subs    r15, r14, #4
+        ",
+    );
+    let registers = &exec.cpu.registers;
+    assert_eq!(registers.read(1), 1);
+    assert_eq!(registers.read(5), 2);
+}
+
+#[test]
+pub fn test_prefetch_abort() {
+    let exec = execute_with_abort(
+        "prefetch-abort",
+        Isa::Arm,
+        "
+        b       main
+        b       _exit           @ undefined vector
+        b       _exit           @ swi vector
+        b       prefetch_abort_handler
+        b       _exit           @ data abort vector
+    main:
+        ldr     r0, =0x10000000
+        mov     r15, r0
+    prefetch_abort_handler:
+        mov     r4, r14
+        b       _exit
+        ",
+    );
+    let registers = &exec.cpu.registers;
+    assert_eq!(registers.read(4), ABORT_ADDRESS + 4);
+    assert_eq!(registers.read_mode(), CpuMode::Abort);
+}
+
+#[test]
+pub fn test_thumb_data_abort() {
+    // Exception vectors are always executed in ARM state.
+    let exec = execute_with_abort(
+        "thumb-data-abort",
+        Isa::Thumb,
+        "
+        b       main
+        nop
+
+        .arm
+        b       _exit           @ undefined vector
+        b       _exit           @ swi vector
+        b       _exit           @ prefetch abort vector
+        b       data_abort_handler
+    data_abort_handler:
+        add     r5, r5, #1
+        mov     r4, r14
+        sub     r14, r14, #6    @ skip the aborted instruction
+        movs    r15, r14
+
+        .thumb
+    main:
+        ldr     r0, =0x10000000
+        mov     r2, #7
+        mov     sp, r0
+        pop     {r1, r2}
+        ldr     r3, =aborted
+    aborted:
+        ldr     r2, [r0, #4]
+        ",
+    );
+    let registers = &exec.cpu.registers;
+    assert_eq!(registers.read(2), 7);
+    assert_eq!(registers.read(4), registers.read(3) + 8);
+    assert_eq!(registers.read(13), ABORT_ADDRESS, "base was not restored");
+    assert_eq!(registers.read(5), 2);
+    assert!(registers.getf_t());
+}
@@ -1,7 +1,6 @@
 mod common;
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_branch() {
     let (cpu, _mem) = common::execute_arm(
         "b",
@@ -15,7 +14,6 @@ pub fn test_branch() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_branch_and_link() {
     let (cpu, _mem) = common::execute_arm(
         "bl",
@@ -32,7 +30,6 @@ pub fn test_branch_and_link() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_branch_and_exchange() {
     let (cpu, _mem) = common::execute_arm(
         "bx-to-arm",
@@ -0,0 +1,148 @@
+mod common;
+
+use arm::{Coprocessor, Isa};
+use common::Executor;
+
+/// A coprocessor with 16 plain registers. CDP adds CRn, CRm, and opcode 1 into CRd.
+#[derive(Default)]
+struct TestCoprocessor {
+    registers: [u32; 16],
+}
+
+impl Coprocessor for TestCoprocessor {
+    fn cdp(&mut self, opcode1: u32, crd: u32, crn: u32, crm: u32, _opcode2: u32) -> bool {
+        self.registers[crd as usize] =
+            self.registers[crn as usize] + self.registers[crm as usize] + opcode1;
+        true
+    }
+
+    fn mcr(&mut self, _opcode1: u32, crn: u32, _crm: u32, _opcode2: u32, value: u32) -> bool {
+        self.registers[crn as usize] = value;
+        true
+    }
+
+    fn mrc(&mut self, _opcode1: u32, crn: u32, _crm: u32, _opcode2: u32) -> Option<u32> {
+        Some(self.registers[crn as usize])
+    }
+
+    fn ldc(&mut self, long: bool, _crd: u32) -> Option<u32> {
+        Some(if long { 2 } else { 1 })
+    }
+
+    fn ldc_word(&mut self, crd: u32, index: u32, value: u32) {
+        self.registers[(crd + index) as usize] = value;
+    }
+
+    fn stc(&mut self, long: bool, _crd: u32) -> Option<u32> {
+        Some(if long { 2 } else { 1 })
+    }
+
+    fn stc_word(&mut self, crd: u32, index: u32) -> u32 {
+        self.registers[(crd + index) as usize]
+    }
+}
+
+/// A coprocessor that doesn't respond to anything.
+struct AbsentCoprocessor;
+
+impl Coprocessor for AbsentCoprocessor {}
+
+#[test]
+pub fn test_register_transfer() {
+    let mut exec = Executor::new("mcr-mrc", Isa::Arm);
+    exec.cpu.set_coprocessor(15, TestCoprocessor::default());
+    exec.push(
+        "
+        ldr     r0, =0x12345678
+        .word   0xEE010F10      @ mcr p15, 0, r0, c1, c0, 0
+        .word   0xEE112F10      @ mrc p15, 0, r2, c1, c0, 0
+        ldr     r0, =0x60000000
+        .word   0xEE070F10      @ mcr p15, 0, r0, c7, c0, 0
+        .word   0xEE17FF10      @ mrc p15, 0, r15, c7, c0, 0
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(2), 0x12345678);
+    assert!(exec.cpu.registers.getf_z());
+    assert!(exec.cpu.registers.getf_c());
+    assert!(!exec.cpu.registers.getf_n());
+    assert!(!exec.cpu.registers.getf_v());
+}
+
+#[test]
+pub fn test_data_operation() {
+    let mut exec = Executor::new("cdp", Isa::Arm);
+    exec.cpu.set_coprocessor(15, TestCoprocessor::default());
+    exec.push(
+        "
+        mov     r0, #3
+        .word   0xEE030F10      @ mcr p15, 0, r0, c3, c0, 0
+        mov     r0, #4
+        .word   0xEE040F10      @ mcr p15, 0, r0, c4, c0, 0
+        .word   0xEE132FA4      @ cdp p15, 1, c2, c3, c4, 5
+        .word   0xEE123F10      @ mrc p15, 0, r3, c2, c0, 0
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(3), 8);
+}
+
+#[test]
+pub fn test_data_transfer() {
+    let mut exec = Executor::new("ldc-stc", Isa::Arm);
+    exec.cpu.set_coprocessor(15, TestCoprocessor::default());
+    exec.data(
+        "
+    buffer:
+        .space 8
+    values:
+        .word 0xAAAA
+        .word 0xBBBB
+        ",
+    );
+    exec.push(
+        "
+        mov     r0, #8
+        .word   0xEE020F10      @ mcr p15, 0, r0, c2, c0, 0
+        ldr     r0, =buffer
+        mov     r6, r0
+        .word   0xEDA02F01      @ stc p15, c2, [r0, #4]!
+        ldr     r1, =values
+        mov     r7, r1
+        .word   0xEC715F02      @ ldcl p15, c5, [r1], #-8
+        .word   0xEE154F10      @ mrc p15, 0, r4, c5, c0, 0
+        .word   0xEE165F10      @ mrc p15, 0, r5, c6, c0, 0
+        ",
+    );
+    let buffer = exec.cpu.registers.read(6);
+    let values = exec.cpu.registers.read(7);
+    assert_eq!(exec.mem.view32(buffer + 4), 8);
+    assert_eq!(exec.cpu.registers.read(0), buffer + 4);
+    assert_eq!(exec.cpu.registers.read(1), values - 8);
+    assert_eq!(exec.cpu.registers.read(4), 0xAAAA);
+    assert_eq!(exec.cpu.registers.read(5), 0xBBBB);
+}
+
+#[test]
+pub fn test_no_coprocessor_is_undefined() {
+    const SOURCE: &str = "
+        b       main
+        b       undefined_handler
+    main:
+        .word   0xEE010F10      @ mcr p15, 0, r0, c1, c0, 0
+        mov     r2, #6
+        b       _exit
+    undefined_handler:
+        mov     r1, #5
+        movs    r15, r14
+        ";
+
+    let mut exec = Executor::new("cp-missing", Isa::Arm);
+    exec.push(SOURCE);
+    assert_eq!(exec.cpu.registers.read(1), 5);
+    assert_eq!(exec.cpu.registers.read(2), 6);
+
+    let mut exec = Executor::new("cp-not-responding", Isa::Arm);
+    exec.cpu.set_coprocessor(15, AbsentCoprocessor);
+    exec.push(SOURCE);
+    assert_eq!(exec.cpu.registers.read(1), 5);
+    assert_eq!(exec.cpu.registers.read(2), 6);
+}
@@ -1,7 +1,6 @@
 mod common;
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_mov() {
     let (cpu, _mem) = common::execute_arm("mov-imm", "mov r0, #5");
     assert_eq!(cpu.registers.read(0), 5);
@@ -29,7 +28,6 @@ pub fn test_mov() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_asr() {
     // ASR by register with a value of 0.
     let (cpu, _mem) = common::execute_arm(
@@ -58,7 +56,6 @@ pub fn test_asr() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_mvn() {
     let (cpu, _mem) = common::execute_arm(
         "mvn",
@@ -75,7 +72,6 @@ pub fn test_mvn() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_orr() {
     let (cpu, _mem) = common::execute_arm(
         "orr",
@@ -93,7 +89,6 @@ pub fn test_orr() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_rsc() {
     let (cpu, _mem) = common::execute_arm(
         "rsc",
@@ -111,7 +106,6 @@ pub fn test_rsc() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_sbc() {
     let mut exec = common::Executor::new("sbc", arm::Isa::Arm);
 
@@ -138,7 +132,6 @@ pub fn test_sbc() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_adc() {
     let mut exec = common::Executor::new("adc", arm::Isa::Arm);
     exec.push(
@@ -182,7 +175,6 @@ pub fn test_adc() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_add() {
     let mut exec = common::Executor::new("add", arm::Isa::Arm);
 
@@ -209,7 +201,6 @@ pub fn test_add() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_and() {
     let mut exec = common::Executor::new("and", arm::Isa::Arm);
 
@@ -239,7 +230,6 @@ pub fn test_and() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_bic() {
     let (cpu, _mem) = common::execute_arm(
         "bic",
@@ -257,7 +247,6 @@ pub fn test_bic() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_eor() {
     let (cpu, _mem) = common::execute_arm(
         "eor",
@@ -275,7 +264,6 @@ pub fn test_eor() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_cmn() {
     let (cpu, _mem) = common::execute_arm(
         "cmn",
@@ -1,7 +1,6 @@
 mod common;
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_mla() {
     let (cpu, _mem) = common::execute_arm(
         "mla",
@@ -18,7 +17,6 @@ pub fn test_mla() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_mul() {
     let (cpu, _mem) = common::execute_arm(
         "mul",
@@ -35,7 +33,6 @@ pub fn test_mul() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_umull() {
     let (cpu, _mem) = common::execute_arm(
         "umull",
@@ -51,7 +48,6 @@ pub fn test_umull() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_smull() {
     let (cpu, _mem) = common::execute_arm(
         "smull",
@@ -1,7 +1,6 @@
 mod common;
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_mrs() {
     let (cpu, _mem) = common::execute_arm(
         "mrs",
@@ -20,7 +19,6 @@ pub fn test_mrs() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_msr() {
     let mut exec = common::Executor::new("msr", arm::Isa::Arm);
 
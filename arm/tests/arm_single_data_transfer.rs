@@ -1,7 +1,6 @@
 mod common;
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_imm() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-preinc-imm",
@@ -22,7 +21,6 @@ pub fn test_ldr_preinc_imm() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_imm_unaligned() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-preinc-imm-unaligned",
@@ -42,7 +40,6 @@ pub fn test_ldr_preinc_imm_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_imm() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-predec-imm",
@@ -63,7 +60,6 @@ pub fn test_ldr_predec_imm() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_imm_unaligned() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-predec-imm-unaligned",
@@ -79,7 +75,6 @@ pub fn test_ldr_predec_imm_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_imm_writeback() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-preinc-imm-writeback",
@@ -100,7 +95,6 @@ pub fn test_ldr_preinc_imm_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_imm_unaligned_writeback() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-preinc-imm-unaligned-writeback",
@@ -116,7 +110,6 @@ pub fn test_ldr_preinc_imm_unaligned_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_imm_writeback() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-predec-imm-writeback",
@@ -138,7 +131,6 @@ pub fn test_ldr_predec_imm_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_imm_unaligned_writeback() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-predec-imm-unaligned-writeback",
@@ -155,7 +147,6 @@ pub fn test_ldr_predec_imm_unaligned_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_reg() {
     let mut exec = common::Executor::new("ldr-preinc-reg", arm::Isa::Arm);
     exec.data(
@@ -231,7 +222,6 @@ pub fn test_ldr_preinc_reg() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_reg_unaligned() {
     let (cpu, _mem) = common::execute_arm(
         "ldr-preinc-reg-unaligned",
@@ -247,7 +237,6 @@ pub fn test_ldr_preinc_reg_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_reg() {
     let mut exec = common::Executor::new("ldr-predec-reg", arm::Isa::Arm);
     exec.data(
@@ -312,7 +301,6 @@ pub fn test_ldr_predec_reg() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_reg_unaligned() {
     let mut exec = common::Executor::new("ldr-predec-reg-unaligned", arm::Isa::Arm);
     exec.data(
@@ -332,7 +320,6 @@ pub fn test_ldr_predec_reg_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_reg_writeback() {
     let mut exec = common::Executor::new("ldr-preinc-reg-writeback", arm::Isa::Arm);
     exec.data(
@@ -404,7 +391,6 @@ pub fn test_ldr_preinc_reg_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_preinc_reg_unaligned_writeback() {
     let mut exec = common::Executor::new("ldr-preinc-reg-unaligned-writeback", arm::Isa::Arm);
     exec.data(
@@ -424,7 +410,6 @@ pub fn test_ldr_preinc_reg_unaligned_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_reg_writeback() {
     let mut exec = common::Executor::new("ldr-predec-reg-writeback", arm::Isa::Arm);
     exec.data(
@@ -482,7 +467,6 @@ pub fn test_ldr_predec_reg_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_predec_reg_unaligned_writeback() {
     let mut exec = common::Executor::new("ldr-predec-reg-unaligned-writeback", arm::Isa::Arm);
     exec.data(
@@ -502,7 +486,6 @@ pub fn test_ldr_predec_reg_unaligned_writeback() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postinc_imm() {
     let mut exec = common::Executor::new("ldr-postinc-imm", arm::Isa::Arm);
     exec.data(
@@ -531,7 +514,6 @@ pub fn test_ldr_postinc_imm() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postinc_imm_unaligned() {
     let mut exec = common::Executor::new("ldr-postinc-imm-unaligned", arm::Isa::Arm);
     exec.data(
@@ -550,7 +532,6 @@ pub fn test_ldr_postinc_imm_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postdec_imm() {
     let mut exec = common::Executor::new("ldr-postdec-imm", arm::Isa::Arm);
     exec.data(
@@ -579,7 +560,6 @@ pub fn test_ldr_postdec_imm() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postdec_imm_unaligned() {
     let mut exec = common::Executor::new("ldr-postdec-imm-unaligned", arm::Isa::Arm);
     exec.data(
@@ -598,7 +578,6 @@ pub fn test_ldr_postdec_imm_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postinc_reg() {
     let mut exec = common::Executor::new("ldr-postinc-reg", arm::Isa::Arm);
     exec.data(
@@ -667,7 +646,6 @@ pub fn test_ldr_postinc_reg() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postinc_reg_unaligned() {
     let mut exec = common::Executor::new("ldr-postinc-reg-unaligned", arm::Isa::Arm);
     exec.data(
@@ -687,7 +665,6 @@ pub fn test_ldr_postinc_reg_unaligned() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postdec_reg() {
     let mut exec = common::Executor::new("ldr-postdec-reg", arm::Isa::Arm);
     exec.data(
@@ -756,7 +733,6 @@ pub fn test_ldr_postdec_reg() {
 }
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_ldr_postdec_reg_unaligned() {
     let mut exec = common::Executor::new("ldr-postdec-reg-unaligned", arm::Isa::Arm);
     exec.data(
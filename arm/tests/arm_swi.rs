@@ -1,7 +1,6 @@
 mod common;
 
 #[test]
-#[cfg(feature = "devkit-arm-tests")]
 pub fn test_swi() {
     let (cpu, _mem) = common::execute_arm(
         "swi",
@@ -0,0 +1,70 @@
+mod common;
+
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc,
+};
+
+use arm::{CpuException, ExceptionHandlerResult, Isa};
+use common::Executor;
+
+#[test]
+pub fn test_undefined() {
+    let (cpu, _mem) = common::execute_arm(
+        "undefined",
+        "
+        b       main
+        b       undefined_handler
+        b       _exit           @ swi vector
+    main:
+        mov     r0, #4
+        ldr     r3, =return_point
+        .word   0xE7F000F0      @ permanently undefined
+    return_point:
+        mov     r2, #6
+        b       _exit
+    undefined_handler:
+        mov     r1, #5
+        mov     r4, r14
+        movs    r15, r14
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 4);
+    assert_eq!(cpu.registers.read(1), 5);
+    assert_eq!(cpu.registers.read(2), 6);
+    assert_eq!(cpu.registers.read(3), cpu.registers.read(4));
+    assert_eq!(cpu.registers.read_mode(), arm::CpuMode::System);
+}
+
+#[test]
+pub fn test_undefined_handled() {
+    let undefined_count = Arc::new(AtomicU32::new(0));
+
+    let mut exec = Executor::new("undefined-handled", Isa::Arm);
+    let count = Arc::clone(&undefined_count);
+    exec.cpu
+        .set_exception_handler(move |_cpu, _memory, exception| {
+            if exception == CpuException::Undefined {
+                count.fetch_add(1, Ordering::Relaxed);
+                ExceptionHandlerResult::Handled
+            } else {
+                ExceptionHandlerResult::Ignored
+            }
+        });
+    exec.push(
+        "
+        b       main
+        b       undefined_handler
+    main:
+        .word   0xE7F000F0      @ permanently undefined
+        mov     r2, #6
+        b       _exit
+    undefined_handler:
+        mov     r1, #5
+        movs    r15, r14
+        ",
+    );
+    assert_eq!(undefined_count.load(Ordering::Relaxed), 1);
+    assert_eq!(exec.cpu.registers.read(1), 0);
+    assert_eq!(exec.cpu.registers.read(2), 6);
+}
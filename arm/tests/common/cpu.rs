@@ -1,6 +1,5 @@
 #![allow(dead_code)]
 
-use super::devkit;
 use super::memory::TestMemory;
 use arm::{Cpu, CpuMode, Isa};
 
@@ -18,6 +17,12 @@ pub fn execute_arm(name: &str, source: &str) -> (Cpu, TestMemory) {
     (exec.cpu, exec.mem)
 }
 
+pub fn execute_thumb(name: &str, source: &str) -> (Cpu, TestMemory) {
+    let mut exec = Executor::new(name, arm::Isa::Thumb);
+    exec.push(source);
+    (exec.cpu, exec.mem)
+}
+
 pub struct Executor {
     pub cpu: Cpu,
     pub mem: TestMemory,
@@ -78,7 +83,16 @@ impl Executor {
         source.push_str(".text\n");
         source.push_str("_exit:\n");
         source.push_str(".word 0xF777F777\n");
-        let bin = devkit::assemble(self.base_isa, &name, &source).unwrap();
+        let bin = arm::asm::assemble(self.base_isa, &source)
+            .unwrap_or_else(|err| panic!("failed to assemble {name}: {err}"));
+
+        // Check the built-in assembler against GNU as when devkitARM is available.
+        #[cfg(feature = "devkit-arm-tests")]
+        assert_eq!(
+            bin,
+            super::devkit::assemble(self.base_isa, &name, &source).unwrap(),
+            "{name} was assembled differently by devkitARM"
+        );
 
         let min_len = bin.len() + 8;
         self.mem.set_memory_with_padding(bin, min_len);
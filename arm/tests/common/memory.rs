@@ -1,9 +1,17 @@
-use arm::Memory;
+#![allow(dead_code)]
+
+use std::ops::Range;
+
+use arm::{Memory, CODE_PAGE_SIZE};
 use util::mem::{read_u16, read_u32, write_u16, write_u32};
 
 pub struct TestMemory {
     data: Vec<u8>,
     len_no_padding: usize,
+    abort_range: Range<u32>,
+    aborted: bool,
+    /// Code version of each page, or `None` if writes aren't tracked.
+    code_versions: Option<Vec<u32>>,
 }
 
 impl TestMemory {
@@ -20,52 +28,120 @@ impl TestMemory {
         TestMemory {
             data,
             len_no_padding,
+            abort_range: 0..0,
+            aborted: false,
+            code_versions: Some(vec![0; min_len / CODE_PAGE_SIZE as usize + 1]),
+        }
+    }
+
+    /// Stops tracking writes so that [`Memory::code_version`] always returns `None`.
+    pub fn untrack_code_writes(&mut self) {
+        self.code_versions = None;
+    }
+
+    fn code_written(&mut self, address: u32) {
+        if let Some(versions) = &mut self.code_versions {
+            let page = (address / CODE_PAGE_SIZE) as usize;
+            versions[page] = versions[page].wrapping_add(1);
         }
     }
 
+    /// Accesses to addresses in this range abort instead of touching memory. Loads from the
+    /// range return 0.
+    pub fn set_abort_range(&mut self, range: Range<u32>) {
+        self.abort_range = range;
+    }
+
+    fn check_abort(&mut self, address: u32) -> bool {
+        let abort = self.abort_range.contains(&address);
+        self.aborted |= abort;
+        abort
+    }
+
     pub fn set_memory_with_padding(&mut self, mut data: Vec<u8>, min_len: usize) {
         let len_no_padding = data.len();
         data.resize(min_len, 0xc0);
         self.data = data;
         self.len_no_padding = len_no_padding;
+        if self.code_versions.is_some() {
+            self.code_versions = Some(vec![0; min_len / CODE_PAGE_SIZE as usize + 1]);
+        }
     }
 
     pub fn view32(&mut self, address: u32) -> u32 {
+        if self.abort_range.contains(&address) {
+            return 0;
+        }
         read_u32(&self.data, address as usize)
     }
 
     pub fn view16(&mut self, address: u32) -> u16 {
+        if self.abort_range.contains(&address) {
+            return 0;
+        }
         read_u16(&self.data, address as usize)
     }
 }
 
 impl Memory for TestMemory {
     fn load32(&mut self, address: u32, _access: arm::AccessType) -> (u32, arm::Waitstates) {
+        if self.check_abort(address) {
+            return (0, 0u8.into());
+        }
         let data = read_u32(&self.data, address as usize);
         (data, 0u8.into())
     }
 
     fn load16(&mut self, address: u32, _access: arm::AccessType) -> (u16, arm::Waitstates) {
+        if self.check_abort(address) {
+            return (0, 0u8.into());
+        }
         let data = read_u16(&self.data, address as usize);
         (data, 0u8.into())
     }
 
     fn load8(&mut self, address: u32, _access: arm::AccessType) -> (u8, arm::Waitstates) {
+        if self.check_abort(address) {
+            return (0, 0u8.into());
+        }
         (self.data[address as usize], 0u8.into())
     }
 
     fn store32(&mut self, address: u32, value: u32, _access: arm::AccessType) -> arm::Waitstates {
+        if self.check_abort(address) {
+            return 0u8.into();
+        }
         write_u32(&mut self.data, address as usize, value);
+        self.code_written(address);
         0u8.into()
     }
 
     fn store16(&mut self, address: u32, value: u16, _access: arm::AccessType) -> arm::Waitstates {
+        if self.check_abort(address) {
+            return 0u8.into();
+        }
         write_u16(&mut self.data, address as usize, value);
+        self.code_written(address);
         0u8.into()
     }
 
     fn store8(&mut self, address: u32, value: u8, _access: arm::AccessType) -> arm::Waitstates {
+        if self.check_abort(address) {
+            return 0u8.into();
+        }
         self.data[address as usize] = value;
+        self.code_written(address);
         0u8.into()
     }
+
+    fn take_abort(&mut self) -> bool {
+        std::mem::take(&mut self.aborted)
+    }
+
+    fn code_version(&self, address: u32) -> Option<u32> {
+        self.code_versions
+            .as_ref()?
+            .get((address / CODE_PAGE_SIZE) as usize)
+            .copied()
+    }
 }
@@ -1,6 +1,7 @@
 pub mod cpu;
+#[cfg(feature = "devkit-arm-tests")]
 pub mod devkit;
 pub mod memory;
 
+#[allow(unused_imports)]
 pub use cpu::*;
-pub use memory::*;
@@ -0,0 +1,125 @@
+mod common;
+
+use arm::{Cpu, CpuMode, Cycles, Isa, Memory as _};
+use common::memory::TestMemory;
+
+/// Enough cycles to run every instruction in the tests below.
+const BUDGET: Cycles = Cycles::new(1000);
+
+fn setup(isa: Isa, source: &str) -> (Cpu, TestMemory) {
+    let bin = arm::asm::assemble(isa, source).expect("failed to assemble");
+    let min_len = bin.len() + 64;
+    let mut mem = TestMemory::with_padding(bin, min_len);
+    let cpu = Cpu::new(isa, CpuMode::System, &mut mem);
+    (cpu, mem)
+}
+
+#[test]
+pub fn test_run_stops_before_memory_access() {
+    let (mut cpu, mut mem) = setup(
+        Isa::Arm,
+        "
+        mov     r0, #1
+        add     r0, r0, #1
+        ldr     r1, [r0]
+        add     r0, r0, #1
+    end:
+        b       end
+        ",
+    );
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(0), 2);
+    assert_eq!(cpu.next_exec_pc(), 8);
+
+    // The load runs on its own so that anything it does is seen before the next instruction.
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(0), 2);
+    assert_eq!(cpu.next_exec_pc(), 12);
+
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(0), 3);
+}
+
+#[test]
+pub fn test_run_stops_after_branch() {
+    let (mut cpu, mut mem) = setup(
+        Isa::Arm,
+        "
+        mov     r0, #1
+        b       target
+        mov     r0, #5
+    target:
+        mov     r1, #2
+        ",
+    );
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(0), 1);
+    assert_eq!(cpu.registers.read(1), 0);
+    assert_eq!(cpu.next_exec_pc(), 12);
+}
+
+#[test]
+pub fn test_run_budget() {
+    let (mut cpu, mut mem) = setup(
+        Isa::Arm,
+        "
+        mov     r0, #1
+        mov     r1, #2
+        ",
+    );
+    cpu.run(&mut mem, Cycles::ZERO);
+    assert_eq!(cpu.registers.read(0), 1);
+    assert_eq!(cpu.registers.read(1), 0);
+}
+
+#[test]
+pub fn test_run_modified_code() {
+    run_modified_code(true);
+}
+
+#[test]
+pub fn test_run_modified_code_untracked() {
+    run_modified_code(false);
+}
+
+fn run_modified_code(track_code_writes: bool) {
+    let (mut cpu, mut mem) = setup(
+        Isa::Arm,
+        "
+        mov     r0, #1
+        mov     r1, #2
+        mov     r2, #3
+    end:
+        b       end
+        ",
+    );
+    if !track_code_writes {
+        mem.untrack_code_writes();
+    }
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(2), 3);
+
+    // Replace the third instruction, which isn't in the pipeline when the block starts, with
+    // `add r2, r0, r0, lsl #3` and run the same block again.
+    mem.store32(8, 0xE0802180, arm::AccessType::NonSeq);
+    cpu.branch(0, &mut mem);
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(1), 2);
+    assert_eq!(cpu.registers.read(2), 9);
+}
+
+#[test]
+pub fn test_run_thumb() {
+    let (mut cpu, mut mem) = setup(
+        Isa::Thumb,
+        "
+        mov     r0, #1
+        add     r0, #1
+        push    {r0}
+        add     r0, #1
+        ",
+    );
+    cpu.run(&mut mem, BUDGET);
+    assert_eq!(cpu.registers.read(0), 2);
+    assert_eq!(cpu.next_exec_pc(), 4);
+}
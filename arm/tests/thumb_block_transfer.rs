@@ -0,0 +1,209 @@
+mod common;
+
+use arm::Isa;
+use common::Executor;
+
+const STACK: &str = "
+buffer:
+    .space 0x80
+stack:
+";
+
+#[test]
+pub fn test_push_pop() {
+    let mut exec = Executor::new("push-pop-lr", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r0, =stack
+        mov     sp, r0
+        mov     r1, #1
+        mov     r2, #2
+        mov     r3, #3
+        ldr     r4, =0xAAAA
+        mov     lr, r4
+        push    {r1-r3, lr}
+        mov     r0, sp
+        pop     {r4-r7}
+        ",
+    );
+    let sp = exec.cpu.registers.read(0);
+    assert_eq!(exec.cpu.registers.read(13), sp + 16);
+    assert_eq!(exec.mem.view32(sp), 1);
+    assert_eq!(exec.mem.view32(sp + 12), 0xAAAA);
+    assert_eq!(exec.cpu.registers.read(4), 1);
+    assert_eq!(exec.cpu.registers.read(5), 2);
+    assert_eq!(exec.cpu.registers.read(6), 3);
+    assert_eq!(exec.cpu.registers.read(7), 0xAAAA);
+
+    let mut exec = Executor::new("pop-pc", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r0, =stack
+        mov     sp, r0
+        ldr     r1, =location
+        push    {r1}
+        mov     r2, #1
+        pop     {pc}
+        mov     r2, #2  @ should not be executed
+    location:
+        mov     r3, #3
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(2), 1);
+    assert_eq!(exec.cpu.registers.read(3), 3);
+    assert_eq!(exec.cpu.registers.read(13), exec.cpu.registers.read(0));
+    assert!(exec.cpu.registers.getf_t());
+}
+
+#[test]
+pub fn test_push_lr_pop_pc_call() {
+    let mut exec = Executor::new("push-lr-pop-pc", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r0, =stack
+        mov     sp, r0
+        mov     r0, #1
+        bl      function
+    return_point:
+        mov     r1, #2
+        ldr     r5, =return_point
+        b       _exit
+    function:
+        push    {r4, lr}
+        mov     r4, #9
+        add     r0, r4
+        pop     {r4, pc}
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(0), 10);
+    assert_eq!(exec.cpu.registers.read(1), 2);
+    assert_eq!(exec.cpu.registers.read(4), 0);
+    assert_eq!(exec.cpu.registers.read(14), exec.cpu.registers.read(5) | 1);
+}
+
+#[test]
+pub fn test_ldmia_stmia() {
+    let mut exec = Executor::new("ldmia-stmia", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r0, =buffer
+        mov     r6, r0
+        mov     r1, #1
+        mov     r2, #2
+        mov     r3, #3
+        stmia   r0!, {r1-r3}
+        mov     r7, r0
+        mov     r0, r6
+        ldmia   r0!, {r3-r5}
+        ",
+    );
+    let buffer = exec.cpu.registers.read(6);
+    assert_eq!(exec.cpu.registers.read(7), buffer + 12);
+    assert_eq!(exec.cpu.registers.read(0), buffer + 12);
+    assert_eq!(exec.mem.view32(buffer + 8), 3);
+    assert_eq!(exec.cpu.registers.read(3), 1);
+    assert_eq!(exec.cpu.registers.read(4), 2);
+    assert_eq!(exec.cpu.registers.read(5), 3);
+}
+
+#[test]
+pub fn test_block_transfer_base_in_list() {
+    // If the base is the first register in the list its original value is stored.
+    let mut exec = Executor::new("stmia-base-first", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r0, =buffer
+        mov     r6, r0
+        mov     r1, #1
+        .hword  0xC003      @ stmia r0!, {r0, r1}
+        ",
+    );
+    let buffer = exec.cpu.registers.read(6);
+    assert_eq!(exec.mem.view32(buffer), buffer);
+    assert_eq!(exec.mem.view32(buffer + 4), 1);
+    assert_eq!(exec.cpu.registers.read(0), buffer + 8);
+
+    // Otherwise the written back value is stored.
+    let mut exec = Executor::new("stmia-base-not-first", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r1, =buffer
+        mov     r6, r1
+        mov     r0, #7
+        .hword  0xC103      @ stmia r1!, {r0, r1}
+        ",
+    );
+    let buffer = exec.cpu.registers.read(6);
+    assert_eq!(exec.mem.view32(buffer), 7);
+    assert_eq!(exec.mem.view32(buffer + 4), buffer + 8);
+    assert_eq!(exec.cpu.registers.read(1), buffer + 8);
+
+    // A loaded base overrides the written back value.
+    let mut exec = Executor::new("ldmia-base-in-list", Isa::Thumb);
+    exec.data(
+        "
+    values:
+        .word 0x11111111
+        .word 0x22222222
+        ",
+    );
+    exec.push(
+        "
+        ldr     r1, =values
+        .hword  0xC903      @ ldmia r1!, {r0, r1}
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(0), 0x11111111);
+    assert_eq!(exec.cpu.registers.read(1), 0x22222222);
+}
+
+#[test]
+pub fn test_block_transfer_empty_list() {
+    // An empty register list stores PC+2 and adds 0x40 to the base register.
+    let mut exec = Executor::new("stmia-empty", Isa::Thumb);
+    exec.data(STACK);
+    exec.push(
+        "
+        ldr     r1, =buffer
+        mov     r6, r1
+        ldr     r3, =location
+    location:
+        .hword  0xC100      @ stmia r1!, {}
+        ",
+    );
+    let buffer = exec.cpu.registers.read(6);
+    assert_eq!(exec.cpu.registers.read(1), buffer + 0x40);
+    assert_eq!(exec.mem.view32(buffer), exec.cpu.registers.read(3) + 6);
+
+    // An empty register list loads the PC and adds 0x40 to the base register.
+    let mut exec = Executor::new("ldmia-empty", Isa::Thumb);
+    exec.data(
+        "
+    pointer:
+        .word location
+        ",
+    );
+    exec.push(
+        "
+        ldr     r0, =pointer
+        mov     r6, r0
+        .hword  0xC800      @ ldmia r0!, {}
+        mov     r1, #1      @ should not be executed
+    location:
+        mov     r2, #2
+        ",
+    );
+    assert_eq!(
+        exec.cpu.registers.read(0),
+        exec.cpu.registers.read(6) + 0x40
+    );
+    assert_eq!(exec.cpu.registers.read(1), 0);
+    assert_eq!(exec.cpu.registers.read(2), 2);
+    assert!(exec.cpu.registers.getf_t());
+}
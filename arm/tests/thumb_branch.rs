@@ -0,0 +1,137 @@
+mod common;
+
+#[test]
+pub fn test_conditional_branch() {
+    let (cpu, _mem) = common::execute_thumb(
+        "bne-loop",
+        "
+        mov     r0, #0
+        mov     r1, #5
+    loop:
+        add     r0, #2
+        sub     r1, #1
+        bne     loop
+        cmp     r0, #10
+        beq     equal
+        mov     r2, #1  @ should not be executed
+    equal:
+        mov     r3, #1
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 10);
+    assert_eq!(cpu.registers.read(1), 0);
+    assert_eq!(cpu.registers.read(2), 0);
+    assert_eq!(cpu.registers.read(3), 1);
+
+    let (cpu, _mem) = common::execute_thumb(
+        "signed-unsigned-conditions",
+        "
+        mov     r0, #1
+        neg     r1, r0          @ r1 = -1
+        cmp     r1, r0
+        blt     less_than
+        mov     r2, #1          @ should not be executed
+    less_than:
+        bhi     higher
+        mov     r3, #1          @ should not be executed
+    higher:
+        cmp     r0, r1
+        bgt     greater_than
+        mov     r4, #1          @ should not be executed
+    greater_than:
+        bls     lower_or_same
+        mov     r5, #1
+    lower_or_same:
+        ",
+    );
+    assert_eq!(cpu.registers.read(2), 0);
+    assert_eq!(cpu.registers.read(3), 0);
+    assert_eq!(cpu.registers.read(4), 0);
+    assert_eq!(cpu.registers.read(5), 0);
+}
+
+#[test]
+pub fn test_unconditional_branch() {
+    let (cpu, _mem) = common::execute_thumb(
+        "b",
+        "
+        mov     r0, #5
+        b       _exit
+        mov     r0, #8  @ should not be executed
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 5);
+}
+
+#[test]
+pub fn test_long_branch_with_link() {
+    let (cpu, _mem) = common::execute_thumb(
+        "bl",
+        "
+        ldr     r1, =skipped
+        mov     r0, #5
+        bl      _exit
+    skipped:
+        mov     r0, #8  @ should not be executed
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 5);
+    assert_eq!(cpu.registers.read(14), cpu.registers.read(1) | 1);
+
+    // Offsets that don't fit in the lower half of the pair on their own.
+    let (cpu, _mem) = common::execute_thumb(
+        "bl-far",
+        "
+        bl      start
+    backwards:
+        mov     r1, #8
+        bx      lr
+        .space  0x1000
+    start:
+        bl      backwards
+        mov     r0, #7
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 7);
+    assert_eq!(cpu.registers.read(1), 8);
+}
+
+#[test]
+pub fn test_branch_and_exchange() {
+    let (cpu, _mem) = common::execute_thumb(
+        "bx-to-arm-and-back",
+        "
+        ldr     r0, =arm_code
+        bx      r0
+        mov     r1, #3  @ should not be executed
+        nop             @ keeps the ARM code word aligned
+
+        .arm
+    arm_code:
+        mov     r1, #1
+        ldr     r2, =thumb_code + 1
+        bx      r2
+
+        .thumb
+    thumb_code:
+        mov     r3, #3
+        ",
+    );
+    assert_eq!(cpu.registers.read(1), 1);
+    assert_eq!(cpu.registers.read(3), 3);
+    assert!(cpu.registers.getf_t());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "bx-hi-register",
+        "
+        ldr     r0, =location + 1
+        mov     r8, r0
+        bx      r8
+        mov     r1, #1  @ should not be executed
+    location:
+        mov     r2, #2
+        ",
+    );
+    assert_eq!(cpu.registers.read(1), 0);
+    assert_eq!(cpu.registers.read(2), 2);
+}
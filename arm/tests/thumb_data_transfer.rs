@@ -0,0 +1,137 @@
+mod common;
+
+use arm::Isa;
+use common::Executor;
+
+const DATA: &str = "
+var:
+    .word 0x8899AABB
+    .word 0x11223344
+    .space 24
+";
+
+#[test]
+pub fn test_pc_relative_load() {
+    let (cpu, _mem) = common::execute_thumb(
+        "ldr-pc",
+        "
+        ldr     r0, =0x12345678
+        ldr     r1, =0x12345678
+        ldr     r2, =0xCAFEBABE
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0x12345678);
+    assert_eq!(cpu.registers.read(1), 0x12345678);
+    assert_eq!(cpu.registers.read(2), 0xCAFEBABE);
+}
+
+#[test]
+pub fn test_register_offset() {
+    let mut exec = Executor::new("ldr-str-reg", Isa::Thumb);
+    exec.data(DATA);
+    exec.push(
+        "
+        ldr     r0, =var
+        mov     r1, #8
+        ldr     r2, =0xDEADBEEF
+        str     r2, [r0, r1]
+        ldr     r3, [r0, r1]
+        mov     r1, #1
+        ldr     r4, [r0, r1]    @ unaligned loads are rotated
+        ldrb    r5, [r0, r1]
+        mov     r1, #12
+        strb    r2, [r0, r1]
+        ldr     r6, [r0, r1]
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(3), 0xDEADBEEF);
+    assert_eq!(exec.cpu.registers.read(4), 0xBB8899AA);
+    assert_eq!(exec.cpu.registers.read(5), 0xAA);
+    assert_eq!(exec.cpu.registers.read(6), 0xEF);
+}
+
+#[test]
+pub fn test_sign_extended_register_offset() {
+    let mut exec = Executor::new("ldrh-ldsb-ldsh-reg", Isa::Thumb);
+    exec.data(DATA);
+    exec.push(
+        "
+        ldr     r0, =var
+        mov     r1, #2
+        ldrh    r2, [r0, r1]
+        ldsh    r3, [r0, r1]
+        mov     r1, #1
+        ldsb    r4, [r0, r1]
+        mov     r1, #4
+        ldsb    r5, [r0, r1]
+        mov     r1, #8
+        strh    r2, [r0, r1]
+        ldr     r6, [r0, r1]
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(2), 0x8899);
+    assert_eq!(exec.cpu.registers.read(3), 0xFFFF8899);
+    assert_eq!(exec.cpu.registers.read(4), 0xFFFFFFAA);
+    assert_eq!(exec.cpu.registers.read(5), 0x44);
+    assert_eq!(exec.cpu.registers.read(6), 0x8899);
+}
+
+#[test]
+pub fn test_immediate_offset() {
+    let mut exec = Executor::new("ldr-str-imm", Isa::Thumb);
+    exec.data(DATA);
+    exec.push(
+        "
+        ldr     r0, =var
+        ldr     r1, [r0, #4]
+        ldrb    r2, [r0, #5]
+        mov     r3, #0x7F
+        strb    r3, [r0, #8]
+        str     r1, [r0, #12]
+        ldr     r4, [r0, #8]
+        ldr     r5, [r0, #12]
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(1), 0x11223344);
+    assert_eq!(exec.cpu.registers.read(2), 0x33);
+    assert_eq!(exec.cpu.registers.read(4), 0x7F);
+    assert_eq!(exec.cpu.registers.read(5), 0x11223344);
+}
+
+#[test]
+pub fn test_halfword_immediate_offset() {
+    let mut exec = Executor::new("ldrh-strh-imm", Isa::Thumb);
+    exec.data(DATA);
+    exec.push(
+        "
+        ldr     r0, =var
+        ldrh    r1, [r0, #2]
+        ldrh    r2, [r0, #4]
+        strh    r1, [r0, #10]
+        ldr     r3, [r0, #8]
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(1), 0x8899);
+    assert_eq!(exec.cpu.registers.read(2), 0x3344);
+    assert_eq!(exec.cpu.registers.read(3), 0x88990000);
+}
+
+#[test]
+pub fn test_sp_relative() {
+    let mut exec = Executor::new("ldr-str-sp", Isa::Thumb);
+    exec.data(DATA);
+    exec.push(
+        "
+        ldr     r0, =var
+        mov     sp, r0
+        ldr     r1, =0xDEADBEEF
+        str     r1, [sp, #16]
+        ldr     r2, [sp, #16]
+        ldr     r3, [r0, #16]
+        ldr     r4, [sp, #4]
+        ",
+    );
+    assert_eq!(exec.cpu.registers.read(2), 0xDEADBEEF);
+    assert_eq!(exec.cpu.registers.read(3), 0xDEADBEEF);
+    assert_eq!(exec.cpu.registers.read(4), 0x11223344);
+}
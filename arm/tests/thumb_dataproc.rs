@@ -0,0 +1,357 @@
+mod common;
+
+#[test]
+pub fn test_move_shifted_register() {
+    let (cpu, _mem) = common::execute_thumb(
+        "lsl-imm",
+        "
+        ldr     r1, =0xC0000001
+        lsl     r0, r1, #1
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0x80000002);
+    assert!(cpu.registers.getf_c());
+    assert!(cpu.registers.getf_n());
+
+    // A shift amount of 0 encodes LSR #32 and ASR #32.
+    let (cpu, _mem) = common::execute_thumb(
+        "lsr-imm-32",
+        "
+        ldr     r1, =0x80000001
+        lsr     r0, r1, #32
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0);
+    assert!(cpu.registers.getf_c());
+    assert!(cpu.registers.getf_z());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "asr-imm-32",
+        "
+        ldr     r1, =0x80000000
+        asr     r0, r1, #32
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0xFFFFFFFF);
+    assert!(cpu.registers.getf_c());
+    assert!(cpu.registers.getf_n());
+}
+
+#[test]
+pub fn test_add_subtract() {
+    let (cpu, _mem) = common::execute_thumb(
+        "add-sub-reg-imm3",
+        "
+        mov     r1, #10
+        mov     r2, #3
+        add     r0, r1, r2
+        sub     r3, r1, r2
+        add     r4, r1, #7
+        sub     r5, r2, #4
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 13);
+    assert_eq!(cpu.registers.read(3), 7);
+    assert_eq!(cpu.registers.read(4), 17);
+    assert_eq!(cpu.registers.read(5), 0xFFFFFFFF);
+    assert!(cpu.registers.getf_n());
+    assert!(!cpu.registers.getf_c());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "add-overflow",
+        "
+        ldr     r1, =0x7FFFFFFF
+        add     r0, r1, #1
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0x80000000);
+    assert!(cpu.registers.getf_v());
+    assert!(cpu.registers.getf_n());
+    assert!(!cpu.registers.getf_c());
+}
+
+#[test]
+pub fn test_immediate_operations() {
+    let (cpu, _mem) = common::execute_thumb(
+        "mov-cmp-add-sub-imm8",
+        "
+        mov     r0, #200
+        add     r0, #100
+        sub     r0, #50
+        mov     r1, #0
+        cmp     r0, #250
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 250);
+    assert!(cpu.registers.getf_z());
+    assert!(cpu.registers.getf_c());
+
+    // MOV with an immediate only changes N and Z.
+    let (cpu, _mem) = common::execute_thumb(
+        "mov-imm-keeps-carry",
+        "
+        mov     r0, #1
+        cmp     r0, #0      @ set carry
+        mov     r1, #0
+        ",
+    );
+    assert!(cpu.registers.getf_z());
+    assert!(cpu.registers.getf_c());
+}
+
+#[test]
+pub fn test_alu_operations() {
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-logical",
+        "
+        mov     r0, #0xF0
+        mov     r1, #0x3C
+        mov     r2, r0
+        and     r2, r1
+        mov     r3, r0
+        eor     r3, r1
+        mov     r4, r0
+        orr     r4, r1
+        mov     r5, r0
+        bic     r5, r1
+        mvn     r6, r0
+        ",
+    );
+    assert_eq!(cpu.registers.read(2), 0x30);
+    assert_eq!(cpu.registers.read(3), 0xCC);
+    assert_eq!(cpu.registers.read(4), 0xFC);
+    assert_eq!(cpu.registers.read(5), 0xC0);
+    assert_eq!(cpu.registers.read(6), 0xFFFFFF0F);
+    assert!(cpu.registers.getf_n());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-shift-by-register",
+        "
+        mov     r0, #1
+        mov     r1, #4
+        lsl     r0, r1
+        ldr     r2, =0x80000000
+        mov     r3, #33
+        asr     r2, r3
+        ldr     r4, =0x80000000
+        lsr     r4, r3
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 16);
+    assert_eq!(cpu.registers.read(2), 0xFFFFFFFF);
+    assert_eq!(cpu.registers.read(4), 0);
+    assert!(cpu.registers.getf_z());
+    assert!(!cpu.registers.getf_c());
+
+    // Rotating by a multiple of 32 leaves the value unchanged and sets carry to bit 31.
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-ror-64",
+        "
+        ldr     r0, =0x80000001
+        mov     r1, #64
+        ror     r0, r1
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0x80000001);
+    assert!(cpu.registers.getf_c());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-ror",
+        "
+        mov     r0, #0x81
+        mov     r1, #4
+        ror     r0, r1
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 0x10000008);
+    assert!(!cpu.registers.getf_c());
+}
+
+#[test]
+pub fn test_alu_arithmetic() {
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-adc-sbc",
+        "
+        mov     r0, #1
+        cmp     r0, #0      @ set carry
+        mov     r1, #5
+        mov     r2, #6
+        adc     r1, r2
+        mov     r3, #0
+        cmp     r3, #1      @ clear carry
+        mov     r4, #10
+        mov     r5, #3
+        sbc     r4, r5
+        ",
+    );
+    assert_eq!(cpu.registers.read(1), 12);
+    assert_eq!(cpu.registers.read(4), 6);
+    assert!(cpu.registers.getf_c());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-neg",
+        "
+        mov     r1, #5
+        neg     r0, r1
+        mov     r3, #0
+        neg     r2, r3
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), (-5i32) as u32);
+    assert_eq!(cpu.registers.read(2), 0);
+    assert!(cpu.registers.getf_z());
+    assert!(cpu.registers.getf_c());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-mul",
+        "
+        mov     r0, #6
+        mov     r1, #7
+        mul     r0, r1
+        ldr     r2, =-3
+        mul     r2, r1
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 42);
+    assert_eq!(cpu.registers.read(2), (-21i32) as u32);
+    assert!(cpu.registers.getf_n());
+}
+
+#[test]
+pub fn test_alu_compare() {
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-tst",
+        "
+        mov     r0, #0xF0
+        mov     r1, #0x0F
+        tst     r0, r1
+        ",
+    );
+    assert!(cpu.registers.getf_z());
+    assert_eq!(cpu.registers.read(0), 0xF0);
+
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-cmp",
+        "
+        mov     r0, #1
+        mov     r1, #2
+        cmp     r0, r1
+        ",
+    );
+    assert!(cpu.registers.getf_n());
+    assert!(!cpu.registers.getf_c());
+    assert!(!cpu.registers.getf_z());
+
+    let (cpu, _mem) = common::execute_thumb(
+        "alu-cmn",
+        "
+        ldr     r0, =0x7FFFFFFF
+        mov     r1, #1
+        cmn     r0, r1
+        ",
+    );
+    assert!(cpu.registers.getf_v());
+    assert!(cpu.registers.getf_n());
+    assert_eq!(cpu.registers.read(0), 0x7FFFFFFF);
+}
+
+#[test]
+pub fn test_hi_register_operations() {
+    let (cpu, _mem) = common::execute_thumb(
+        "hi-reg-add-cmp-mov",
+        "
+        mov     r0, #5
+        mov     r8, r0
+        mov     r1, #7
+        add     r8, r1
+        mov     r9, r8
+        add     r2, r9
+        cmp     r8, r9
+        ",
+    );
+    assert_eq!(cpu.registers.read(8), 12);
+    assert_eq!(cpu.registers.read(9), 12);
+    assert_eq!(cpu.registers.read(2), 12);
+    assert!(cpu.registers.getf_z());
+    assert!(cpu.registers.getf_c());
+
+    // Reading the PC gives the address of the instruction plus 4.
+    let (cpu, _mem) = common::execute_thumb(
+        "hi-reg-mov-from-pc",
+        "
+        ldr     r4, =location
+    location:
+        mov     r3, pc
+        ",
+    );
+    assert_eq!(cpu.registers.read(3), cpu.registers.read(4) + 4);
+
+    let (cpu, _mem) = common::execute_thumb(
+        "hi-reg-add-to-pc",
+        "
+        mov     r0, #2
+        add     pc, r0
+        mov     r1, #1  @ should not be executed
+        mov     r1, #2  @ should not be executed
+        mov     r2, #3
+        ",
+    );
+    assert_eq!(cpu.registers.read(1), 0);
+    assert_eq!(cpu.registers.read(2), 3);
+
+    let (cpu, _mem) = common::execute_thumb(
+        "hi-reg-mov-to-pc",
+        "
+        ldr     r0, =location
+        mov     pc, r0
+        mov     r1, #1  @ should not be executed
+    location:
+        mov     r2, #2
+        ",
+    );
+    assert_eq!(cpu.registers.read(1), 0);
+    assert_eq!(cpu.registers.read(2), 2);
+    assert!(cpu.registers.getf_t());
+}
+
+#[test]
+pub fn test_load_address() {
+    let (cpu, _mem) = common::execute_thumb(
+        "add-sp-imm",
+        "
+        ldr     r0, =0x1000
+        mov     sp, r0
+        add     r1, sp, #512
+        add     r2, sp, #1020
+        ",
+    );
+    assert_eq!(cpu.registers.read(1), 0x1200);
+    assert_eq!(cpu.registers.read(2), 0x13FC);
+
+    // Bit 1 of the PC is cleared before the offset is added.
+    let (cpu, _mem) = common::execute_thumb(
+        "add-pc-imm",
+        "
+        ldr     r4, =location
+    location:
+        add     r3, pc, #4
+        ",
+    );
+    assert_eq!(cpu.registers.read(4) % 4, 2);
+    assert_eq!(cpu.registers.read(3), cpu.registers.read(4) + 6);
+}
+
+#[test]
+pub fn test_add_offset_to_sp() {
+    let (cpu, _mem) = common::execute_thumb(
+        "add-sp",
+        "
+        ldr     r0, =0x1000
+        mov     sp, r0
+        add     sp, #508
+        sub     sp, #16
+        add     sp, #-8
+        ",
+    );
+    assert_eq!(cpu.registers.read(13), 0x1000 + 508 - 16 - 8);
+}
@@ -0,0 +1,34 @@
+mod common;
+
+#[test]
+pub fn test_swi() {
+    // Exception vectors are always executed in ARM state.
+    let (cpu, _mem) = common::execute_thumb(
+        "swi",
+        "
+        b       main
+        nop
+
+        .arm
+        b       _exit           @ undefined instruction vector
+        b       swi_handler
+    swi_handler:
+        mov     r1, #5
+        mov     r4, r14
+        movs    r15, r14
+
+        .thumb
+    main:
+        mov     r0, #4
+        ldr     r3, =return_point
+        swi     #6
+    return_point:
+        mov     r2, #6
+        ",
+    );
+    assert_eq!(cpu.registers.read(0), 4);
+    assert_eq!(cpu.registers.read(1), 5);
+    assert_eq!(cpu.registers.read(2), 6);
+    assert_eq!(cpu.registers.read(3), cpu.registers.read(4));
+    assert!(cpu.registers.getf_t());
+}
@@ -2,7 +2,8 @@ use egui::{
     plot::{Bar, BarChart, Line, Plot, Value, Values},
     Grid, Ui,
 };
-use gba::GbaAudioSampler;
+use gba::{AudioChannel, ChannelMask, GbaAudioSampler};
+use pyrite::GbaHandle;
 use util::circular::CircularBuffer;
 
 use crate::{rgb, GbaData};
@@ -12,6 +13,11 @@ pub struct AudioPane {
     samples_l: CircularBuffer<f32, { Self::BUFFER_SIZE }>,
     samples_r: CircularBuffer<f32, { Self::BUFFER_SIZE }>,
     commands_buffer_sizes: CircularBuffer<u32, { Self::FRAMES }>,
+
+    channel_mask: ChannelMask,
+    show_channels: bool,
+    /// Mono output of each channel on its own.
+    channel_samples: [CircularBuffer<f32, { Self::BUFFER_SIZE }>; 6],
 }
 
 impl AudioPane {
@@ -19,8 +25,9 @@ impl AudioPane {
     const FRAMES: usize = 16;
     const BUFFER_SIZE: usize = Self::FRAMES * Self::RENDER_SAMPLES as usize;
 
-    pub(crate) fn render(&mut self, ui: &mut Ui, data: &mut GbaData) {
+    pub(crate) fn render(&mut self, ui: &mut Ui, data: &mut GbaData, gba: &GbaHandle) {
         self.get_data(data);
+        self.render_channel_controls(ui, gba);
         Grid::new("GBA Frame Durations Grid")
             .num_columns(2)
             .striped(true)
@@ -29,12 +36,89 @@ impl AudioPane {
                 self.render_samples_plot(ui);
                 ui.end_row();
 
+                if self.show_channels {
+                    for channel in AudioChannel::ALL {
+                        ui.label(channel.name());
+                        self.render_channel_plot(ui, channel);
+                        ui.end_row();
+                    }
+                }
+
                 ui.label("Commands");
                 self.render_commands_buffer_plot(ui);
                 ui.end_row();
             });
     }
 
+    fn render_channel_controls(&mut self, ui: &mut Ui, gba: &GbaHandle) {
+        let mut changed = false;
+        Grid::new("Audio Channels Grid")
+            .num_columns(AudioChannel::ALL.len() + 1)
+            .show(ui, |ui| {
+                ui.label("");
+                for channel in AudioChannel::ALL {
+                    ui.label(channel.name());
+                }
+                ui.end_row();
+
+                ui.label("Mute");
+                for channel in AudioChannel::ALL {
+                    let mut muted = self.channel_mask.muted(channel);
+                    if ui.checkbox(&mut muted, "").changed() {
+                        self.channel_mask.set_muted(channel, muted);
+                        changed = true;
+                    }
+                }
+                ui.end_row();
+
+                ui.label("Solo");
+                for channel in AudioChannel::ALL {
+                    let mut soloed = self.channel_mask.soloed(channel);
+                    if ui.checkbox(&mut soloed, "").changed() {
+                        self.channel_mask.set_soloed(channel, soloed);
+                        changed = true;
+                    }
+                }
+                ui.end_row();
+            });
+
+        ui.horizontal(|ui| {
+            if ui.button("Reset").clicked() {
+                self.channel_mask = ChannelMask::default();
+                changed = true;
+            }
+
+            if ui
+                .checkbox(&mut self.show_channels, "Show Channels")
+                .changed()
+            {
+                self.sampler.set_channel_outputs_enabled(self.show_channels);
+            }
+        });
+
+        if changed {
+            let mask = self.channel_mask;
+            gba.after_frame(move |gba, _| gba.set_audio_channel_mask(mask));
+        }
+    }
+
+    fn render_channel_plot(&mut self, ui: &mut Ui, channel: AudioChannel) {
+        let samples = self.channel_samples[u8::from(channel) as usize]
+            .iter()
+            .enumerate()
+            .map(|(idx, &s)| Value::new(idx as f64, s));
+        let line = Line::new(Values::from_values_iter(samples)).color(rgb(0x37b24d));
+
+        Plot::new(("Channel Samples", u8::from(channel)))
+            .height(64.0)
+            .show_axes([false, false])
+            .allow_drag(true)
+            .allow_zoom(true)
+            .include_y(1.0)
+            .include_y(0.0)
+            .show(ui, |plot_ui| plot_ui.line(line));
+    }
+
     fn render_commands_buffer_plot(&mut self, ui: &mut Ui) {
         let mut bars = Vec::with_capacity(self.commands_buffer_sizes.len());
         for (idx, &size) in self.commands_buffer_sizes.iter().enumerate() {
@@ -93,6 +177,13 @@ impl AudioPane {
                 let (l, r) = self.sampler.frame();
                 self.samples_l.push(l);
                 self.samples_r.push(r);
+
+                if self.show_channels {
+                    for channel in AudioChannel::ALL {
+                        let (l, r) = self.sampler.channel_frame(channel);
+                        self.channel_samples[u8::from(channel) as usize].push((l + r) / 2.0);
+                    }
+                }
             }
         }
         data.requests.audio_data = true;
@@ -106,6 +197,10 @@ impl Default for AudioPane {
             samples_l: Default::default(),
             samples_r: Default::default(),
             commands_buffer_sizes: Default::default(),
+
+            channel_mask: ChannelMask::default(),
+            show_channels: false,
+            channel_samples: Default::default(),
         }
     }
 }
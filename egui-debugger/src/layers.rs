@@ -0,0 +1,142 @@
+use egui::{Color32, ColorImage, TextureHandle, Ui};
+use gba::{
+    video::{Layer, LayerBuffers},
+    Gba, SCREEN_HEIGHT, SCREEN_WIDTH,
+};
+use pyrite::GbaHandle;
+use util::array;
+
+use crate::{bgr555_to_color32, GbaData};
+
+/// The layer overrides currently applied to the GBA and the captured layers, if any.
+pub(crate) struct LayerData {
+    enabled: [bool; 7],
+    buffers: Option<Box<LayerBuffers>>,
+}
+
+impl LayerData {
+    pub(crate) fn pull(data: &mut Option<Box<LayerData>>, gba: &mut Gba) {
+        let data = data.get_or_insert_with(|| {
+            Box::new(LayerData {
+                enabled: [true; 7],
+                buffers: None,
+            })
+        });
+
+        for layer in Layer::ALL {
+            data.enabled[layer as usize] = gba.video().layer_enabled(layer);
+        }
+
+        match (gba.video().layer_buffers(), &mut data.buffers) {
+            (Some(source), Some(buffers)) => buffers.copy_from_slice(source),
+            (Some(source), buffers @ None) => {
+                let mut new_buffers = array::boxed_copied([0; gba::SCREEN_PIXEL_COUNT]);
+                new_buffers.copy_from_slice(source);
+                *buffers = Some(new_buffers);
+            }
+            (None, buffers) => *buffers = None,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LayersPane {
+    data: Option<Box<LayerData>>,
+    textures: Vec<TextureHandle>,
+    capture: bool,
+}
+
+impl LayersPane {
+    pub(crate) fn render(&mut self, ui: &mut Ui, data: &mut GbaData, gba: &GbaHandle) {
+        if let Some(layer_data) = data.layers.take() {
+            self.data = Some(layer_data);
+            self.update_textures(ui);
+        }
+        data.requests.layers = true;
+
+        let layer_data = if let Some(ref mut layer_data) = self.data {
+            layer_data
+        } else {
+            ui.label("Waiting for layers...");
+            return;
+        };
+
+        ui.horizontal(|ui| {
+            for layer in Layer::ALL {
+                let enabled = &mut layer_data.enabled[layer as usize];
+                if ui.checkbox(enabled, layer.name()).changed() {
+                    let enabled = *enabled;
+                    gba.after_frame(move |gba, _| {
+                        gba.video_mut().set_layer_enabled(layer, enabled)
+                    });
+                }
+            }
+
+            if ui.button("Enable All").clicked() {
+                layer_data.enabled = [true; 7];
+                gba.after_frame(|gba, _| {
+                    for layer in Layer::ALL {
+                        gba.video_mut().set_layer_enabled(layer, true);
+                    }
+                });
+            }
+        });
+
+        if ui.checkbox(&mut self.capture, "Capture Layers").changed() {
+            let capture = self.capture;
+            gba.after_frame(move |gba, _| gba.video_mut().set_layer_capture(capture));
+        }
+
+        if !self.capture {
+            return;
+        }
+
+        if self.textures.is_empty() {
+            ui.label("Waiting for captured layers...");
+            return;
+        }
+
+        ui.horizontal_wrapped(|ui| {
+            for (layer, texture) in self.textures.iter().enumerate() {
+                ui.vertical(|ui| {
+                    ui.strong(Layer::from(layer as u8).name());
+                    ui.image(texture, texture.size_vec2());
+                });
+            }
+        });
+    }
+
+    fn update_textures(&mut self, ui: &mut Ui) {
+        let buffers = match self.data.as_ref().and_then(|data| data.buffers.as_ref()) {
+            Some(buffers) => buffers,
+            None => {
+                self.textures.clear();
+                return;
+            }
+        };
+
+        for (layer, buffer) in buffers.iter().enumerate() {
+            let image = ColorImage {
+                size: [SCREEN_WIDTH, SCREEN_HEIGHT],
+                pixels: buffer
+                    .iter()
+                    .map(|&color| {
+                        if color & 0x8000 != 0 {
+                            bgr555_to_color32(color)
+                        } else {
+                            Color32::TRANSPARENT
+                        }
+                    })
+                    .collect(),
+            };
+
+            match self.textures.get_mut(layer) {
+                Some(texture) => texture.set(image),
+                None => {
+                    let name = format!("{} Layer", Layer::from(layer as u8).name());
+                    self.textures.push(ui.ctx().load_texture(name, image));
+                }
+            }
+        }
+    }
+}
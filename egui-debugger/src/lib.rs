@@ -1,9 +1,13 @@
 mod audio;
 mod ioregs;
+mod layers;
+mod memory;
+mod oam;
+mod palette;
 mod performance;
 
-use egui::{Color32, Context, Visuals};
-use gba::{Command, Gba};
+use egui::{Color32, Context, Ui, Visuals};
+use gba::{memory::palette::Palette, video::Layer, ChannelMask, Command, Gba};
 use parking_lot::Mutex;
 use pyrite::{CallbackId, GbaHandle, GbaThreadState};
 use std::{sync::Arc, time::Duration};
@@ -14,8 +18,13 @@ pub struct Debugger {
     performance_pane: performance::PerformancePane,
     audio_pane: audio::AudioPane,
     ioregs_pane: ioregs::IoRegistersPane,
+    oam_pane: oam::OamPane,
+    palette_pane: palette::PalettePane,
+    layers_pane: layers::LayersPane,
+    memory_pane: memory::MemoryPane,
 
     has_initialized: bool,
+    break_on_undefined: bool,
 
     gba_data: GbaData,
     gba_data_buffer: Arc<Mutex<GbaData>>,
@@ -40,16 +49,45 @@ impl Debugger {
                 ui.selectable_value(&mut self.current_pane, Pane::Performance, "Performance");
                 ui.selectable_value(&mut self.current_pane, Pane::IoRegisters, "IO Registers");
                 ui.selectable_value(&mut self.current_pane, Pane::Audio, "Audio");
+                ui.selectable_value(&mut self.current_pane, Pane::Oam, "OAM");
+                ui.selectable_value(&mut self.current_pane, Pane::Palette, "Palette");
+                ui.selectable_value(&mut self.current_pane, Pane::Layers, "Layers");
+                ui.selectable_value(&mut self.current_pane, Pane::Memory, "Memory");
+                ui.separator();
+                self.render_break_on_undefined(ui, gba);
             });
 
             match self.current_pane {
                 Pane::Performance => self.performance_pane.render(ui, &mut self.gba_data),
-                Pane::Audio => self.audio_pane.render(ui, &mut self.gba_data),
+                Pane::Audio => self.audio_pane.render(ui, &mut self.gba_data, gba),
                 Pane::IoRegisters => self.ioregs_pane.render(ui, &mut self.gba_data),
+                Pane::Oam => self.oam_pane.render(ui, &mut self.gba_data),
+                Pane::Palette => self.palette_pane.render(ui, &mut self.gba_data, gba),
+                Pane::Layers => self.layers_pane.render(ui, &mut self.gba_data, gba),
+                Pane::Memory => self.memory_pane.render(ui, &mut self.gba_data, gba),
             }
         });
     }
 
+    fn render_break_on_undefined(&mut self, ui: &mut Ui, gba: &GbaHandle) {
+        if ui
+            .checkbox(&mut self.break_on_undefined, "Break on Undefined")
+            .on_hover_text(
+                "Pause at the end of frames that execute an undefined instruction. The frame \
+                runs to completion, so the CPU keeps going after the instruction.",
+            )
+            .changed()
+        {
+            let enabled = self.break_on_undefined;
+            gba.after_frame(move |_, state| state.break_on_undefined = enabled);
+        }
+
+        if let Some(address) = self.gba_data.undefined_instruction {
+            ui.label(format!("Undefined: 0x{address:08X}"))
+                .on_hover_text("The first undefined instruction in the last frame that had one");
+        }
+    }
+
     fn fetch_updated_data(&mut self) {
         let mut locked = self.gba_data_buffer.lock();
         self.gba_data.copy_data(&mut *locked);
@@ -75,6 +113,16 @@ impl Debugger {
         if let Some(cb) = self.frame_callback.take() {
             gba.remove_on_frame(cb);
         }
+
+        // Don't leave layers hidden, channels muted, or keep capturing layers once the debugger
+        // is gone.
+        gba.after_frame(|gba, _| {
+            for layer in Layer::ALL {
+                gba.video_mut().set_layer_enabled(layer, true);
+            }
+            gba.video_mut().set_layer_capture(false);
+            gba.set_audio_channel_mask(ChannelMask::default());
+        });
     }
 }
 
@@ -83,6 +131,10 @@ enum Pane {
     Performance,
     Audio,
     IoRegisters,
+    Oam,
+    Palette,
+    Layers,
+    Memory,
 }
 
 impl Default for Pane {
@@ -96,12 +148,18 @@ struct GbaData {
     frame_duration: Option<Duration>,
     frame_processing_duration: Option<Duration>,
     frame_count: Option<u64>,
+    undefined_instruction: Option<u32>,
 
     audio_commands: Vec<Command>,
     has_audio_commands: bool,
 
     ioreg: Option<u32>,
 
+    obj_data: Option<Box<oam::ObjData>>,
+    palette: Option<Box<Palette>>,
+    layers: Option<Box<layers::LayerData>>,
+    memory: Option<memory::MemoryView>,
+
     updated: bool,
     requests: GbaDataRequests,
 }
@@ -118,6 +176,7 @@ impl GbaData {
         self.frame_duration = source.frame_duration.take();
         self.frame_processing_duration = source.frame_processing_duration.take();
         self.frame_count = source.frame_count.take();
+        self.undefined_instruction = source.undefined_instruction;
 
         if self.requests.audio_data {
             self.audio_commands.clear();
@@ -129,6 +188,13 @@ impl GbaData {
         }
 
         self.ioreg = source.ioreg.take();
+        self.obj_data = source.obj_data.take();
+        self.palette = source.palette.take();
+        // Layers aren't pulled for frames that weren't rendered, so keep showing the last ones.
+        if let Some(layers) = source.layers.take() {
+            self.layers = Some(layers);
+        }
+        self.memory = source.memory.take();
 
         source.requests = std::mem::take(&mut self.requests);
     }
@@ -139,9 +205,15 @@ struct GbaDataRequests {
     performance: bool,
     audio_data: bool,
     ioreg: Option<(/* addr */ u32, /* width */ u8)>,
+    obj_data: bool,
+    palette: bool,
+    layers: bool,
+    memory: Option<(/* addr */ u32, /* len */ u32)>,
 }
 
 fn pull_data_from_gba(data: &mut GbaData, gba: &mut Gba, state: &mut GbaThreadState) {
+    data.undefined_instruction = state.undefined_instruction();
+
     if data.requests.performance {
         data.frame_duration = Some(state.frame_duration());
         data.frame_processing_duration = Some(state.frame_processing_duration());
@@ -165,9 +237,37 @@ fn pull_data_from_gba(data: &mut GbaData, gba: &mut Gba, state: &mut GbaThreadSt
         }
     }
 
+    if data.requests.obj_data {
+        oam::ObjData::pull(&mut data.obj_data, gba);
+    }
+
+    if data.requests.palette {
+        match data.palette {
+            Some(ref mut palette) => (**palette).clone_from(gba.memory().palette()),
+            None => data.palette = Some(Box::new(gba.memory().palette().clone())),
+        }
+    }
+
+    if data.requests.layers && state.frame_rendered() {
+        layers::LayerData::pull(&mut data.layers, gba);
+    }
+
+    if let Some((address, len)) = data.requests.memory {
+        memory::MemoryView::pull(&mut data.memory, address, len, gba);
+    }
+
     data.updated = true;
 }
 
 const fn rgb(col: u32) -> Color32 {
     Color32::from_rgb((col >> 16) as u8, (col >> 8) as u8, col as u8)
 }
+
+/// Converts a GBA BGR555 color into a [`Color32`]. Bit 15 is ignored.
+const fn bgr555_to_color32(col: u16) -> Color32 {
+    // The lower bits are filled in with the upper bits so that 0x1F maps to 0xFF.
+    let r = ((col & 0x1F) << 3) as u8;
+    let g = (((col >> 5) & 0x1F) << 3) as u8;
+    let b = (((col >> 10) & 0x1F) << 3) as u8;
+    Color32::from_rgb(r | (r >> 5), g | (g >> 5), b | (b >> 5))
+}
@@ -0,0 +1,445 @@
+use egui::{Color32, RichText, ScrollArea, TextEdit, TextStyle, Ui};
+use gba::{
+    memory::{
+        BIOS_SIZE, EWRAM_SIZE, IOREGS_SIZE, IWRAM_SIZE, OAM_SIZE, PAL_SIZE, ROM_MAX_MASK, VRAM_SIZE,
+    },
+    Gba,
+};
+use pyrite::GbaHandle;
+use util::mem::{read_u16, read_u32};
+
+use crate::GbaData;
+
+const BYTES_PER_ROW: u32 = 16;
+const CHANGED_COLOR: Color32 = Color32::from_rgb(0xf0, 0x3e, 0x3e);
+
+/// A range of bytes read from the GBA's memory using the side-effect free `view8` function.
+pub(crate) struct MemoryView {
+    address: u32,
+    bytes: Vec<u8>,
+    rom_len: u32,
+}
+
+impl MemoryView {
+    pub(crate) fn pull(view: &mut Option<MemoryView>, address: u32, len: u32, gba: &mut Gba) {
+        let view = view.get_or_insert_with(|| MemoryView {
+            address,
+            bytes: Vec::with_capacity(len as usize),
+            rom_len: 0,
+        });
+
+        view.address = address;
+        view.bytes.clear();
+        view.bytes.extend(
+            (address..address.saturating_add(len)).map(|addr| gba.memory_mut().view8(addr)),
+        );
+        view.rom_len = gba.memory().rom().len() as u32;
+    }
+
+    fn get(&self, address: u32) -> Option<u8> {
+        let offset = address.checked_sub(self.address)?;
+        self.bytes.get(offset as usize).copied()
+    }
+}
+
+pub struct MemoryPane {
+    view: Option<MemoryView>,
+    changed: Vec<bool>,
+
+    region: Region,
+    data_type: DataType,
+    selected: Option<u32>,
+    jump_to: Option<u32>,
+    address_input: String,
+    value_input: String,
+    write_as_cpu: bool,
+    status: Option<String>,
+}
+
+impl Default for MemoryPane {
+    fn default() -> Self {
+        MemoryPane {
+            view: None,
+            changed: Vec::new(),
+            region: Region::Ewram,
+            data_type: DataType::U8,
+            selected: None,
+            jump_to: None,
+            address_input: String::new(),
+            value_input: String::new(),
+            write_as_cpu: false,
+            status: None,
+        }
+    }
+}
+
+impl MemoryPane {
+    pub(crate) fn render(&mut self, ui: &mut Ui, data: &mut GbaData, gba: &GbaHandle) {
+        if let Some(view) = data.memory.take() {
+            self.update_view(view);
+        }
+
+        ui.horizontal(|ui| {
+            for region in Region::ALL {
+                if ui
+                    .selectable_label(self.region == region, region.name())
+                    .clicked()
+                {
+                    self.region = region;
+                    self.jump_to = Some(region.base());
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let response = ui.add(
+                TextEdit::singleline(&mut self.address_input)
+                    .hint_text("address (hex)")
+                    .desired_width(96.0),
+            );
+            let submitted = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            if ui.button("Go").clicked() || submitted {
+                self.jump(parse_hex(&self.address_input));
+            }
+
+            ui.separator();
+            for data_type in DataType::ALL {
+                ui.selectable_value(&mut self.data_type, data_type, data_type.name());
+            }
+        });
+
+        let rom_len = self.view.as_ref().map(|view| view.rom_len).unwrap_or(0);
+        let size = self.region.size(rom_len);
+        if size == 0 {
+            ui.label("This region is empty.");
+            return;
+        }
+
+        ui.separator();
+        self.render_selected(ui, gba);
+        ui.separator();
+
+        let row_height = ui.text_style_height(&TextStyle::Monospace) + ui.spacing().item_spacing.y;
+        let total_rows = size.div_ceil(BYTES_PER_ROW) as usize;
+
+        let mut scroll_area = ScrollArea::vertical().auto_shrink([false, false]);
+        if let Some(address) = self.jump_to.take() {
+            let row = (address - self.region.base()) / BYTES_PER_ROW;
+            scroll_area = scroll_area.vertical_scroll_offset(row as f32 * row_height);
+        }
+
+        scroll_area.show_rows(ui, row_height, total_rows, |ui, rows| {
+            let start = self.region.base() + rows.start as u32 * BYTES_PER_ROW;
+            let len = (rows.len() as u32 * BYTES_PER_ROW).min(self.region.base() + size - start);
+            data.requests.memory = Some((start, len));
+
+            for row in rows {
+                let address = self.region.base() + row as u32 * BYTES_PER_ROW;
+                self.render_row(ui, address, row_height);
+            }
+        });
+    }
+
+    fn render_row(&mut self, ui: &mut Ui, address: u32, row_height: f32) {
+        ui.horizontal(|ui| {
+            ui.set_height(row_height);
+            ui.label(RichText::new(format!("{address:08X}")).monospace().weak());
+
+            let width = self.data_type.width();
+            for cell in (address..address + BYTES_PER_ROW).step_by(width as usize) {
+                let view = self.view.as_ref();
+                let value = (0..width)
+                    .map(|offset| view.and_then(|view| view.get(cell + offset)))
+                    .collect::<Option<Vec<u8>>>();
+
+                let mut text = match value {
+                    Some(ref bytes) => {
+                        let value = bytes
+                            .iter()
+                            .rev()
+                            .fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+                        RichText::new(format!("{value:0digits$X}", digits = width as usize * 2))
+                    }
+                    None => RichText::new("??".repeat(width as usize)),
+                }
+                .monospace();
+
+                if (0..width).any(|offset| self.is_changed(cell + offset)) {
+                    text = text.color(CHANGED_COLOR);
+                }
+
+                let selected = self.selected == Some(cell);
+                if ui.selectable_label(selected, text).clicked() {
+                    self.select(cell);
+                }
+            }
+
+            ui.separator();
+
+            let ascii: String = (address..address + BYTES_PER_ROW)
+                .map(
+                    |addr| match self.view.as_ref().and_then(|view| view.get(addr)) {
+                        Some(byte @ 0x20..=0x7E) => byte as char,
+                        _ => '.',
+                    },
+                )
+                .collect();
+            ui.label(RichText::new(ascii).monospace());
+        });
+    }
+
+    fn render_selected(&mut self, ui: &mut Ui, gba: &GbaHandle) {
+        let address = if let Some(address) = self.selected {
+            address
+        } else {
+            ui.label("Select a value to inspect or edit it.");
+            return;
+        };
+
+        let view = self.view.as_ref();
+        let bytes: Vec<u8> = (0..4)
+            .map_while(|offset| view.and_then(|view| view.get(address + offset)))
+            .collect();
+
+        ui.horizontal_wrapped(|ui| {
+            ui.strong(format!("0x{address:08X}"));
+            if let Some(&byte) = bytes.first() {
+                ui.label(format!("u8: {byte} i8: {}", byte as i8));
+            }
+            if bytes.len() >= 2 {
+                let value = read_u16(&bytes, 0);
+                ui.label(format!("u16: {value} i16: {}", value as i16));
+            }
+            if bytes.len() >= 4 {
+                let value = read_u32(&bytes, 0);
+                ui.label(format!("u32: {value} i32: {}", value as i32));
+            }
+            let ascii: String = bytes
+                .iter()
+                .map(|&byte| match byte {
+                    0x20..=0x7E => byte as char,
+                    _ => '.',
+                })
+                .collect();
+            ui.label(format!("ASCII: \"{ascii}\""));
+        });
+
+        ui.horizontal(|ui| {
+            let data_type = self.data_type;
+            let response = ui.add(
+                TextEdit::singleline(&mut self.value_input)
+                    .hint_text(format!("new {} (hex)", data_type.name()))
+                    .desired_width(96.0),
+            );
+            let submitted = response.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            if ui.button("Write").clicked() || submitted {
+                self.status = Some(match parse_hex(&self.value_input) {
+                    Some(value) if data_type.fits(value) => {
+                        let as_cpu = self.write_as_cpu;
+                        gba.after_frame(move |gba, _| {
+                            let memory = gba.memory_mut();
+                            match (data_type, as_cpu) {
+                                (DataType::U8, false) => memory.poke8(address, value as u8),
+                                (DataType::U16, false) => memory.poke16(address, value as u16),
+                                (DataType::U32, false) => memory.poke32(address, value),
+                                (DataType::U8, true) => memory.write8_as_cpu(address, value as u8),
+                                (DataType::U16, true) => {
+                                    memory.write16_as_cpu(address, value as u16)
+                                }
+                                (DataType::U32, true) => memory.write32_as_cpu(address, value),
+                            }
+                        });
+                        format!("wrote 0x{value:X} to 0x{address:08X}")
+                    }
+                    Some(value) => format!("0x{value:X} does not fit in a {}", data_type.name()),
+                    None => format!("invalid value `{}`", self.value_input),
+                });
+            }
+
+            ui.checkbox(&mut self.write_as_cpu, "Write as CPU")
+                .on_hover_text("Trigger IO side effects as if the write came from the CPU.");
+
+            if let Some(ref status) = self.status {
+                ui.label(status);
+            }
+        });
+    }
+
+    fn select(&mut self, address: u32) {
+        self.selected = Some(address);
+        self.status = None;
+
+        let width = self.data_type.width();
+        let view = self.view.as_ref();
+        let value = (0..width)
+            .rev()
+            .map(|offset| view.and_then(|view| view.get(address + offset)))
+            .try_fold(0u32, |acc, byte| Some((acc << 8) | byte? as u32));
+        self.value_input = value
+            .map(|value| format!("{value:0digits$X}", digits = width as usize * 2))
+            .unwrap_or_default();
+    }
+
+    fn jump(&mut self, address: Option<u32>) {
+        let address = match address {
+            Some(address) => address,
+            None => {
+                self.status = Some(format!("invalid address `{}`", self.address_input));
+                return;
+            }
+        };
+
+        let rom_len = self.view.as_ref().map(|view| view.rom_len).unwrap_or(0);
+        match Region::ALL
+            .into_iter()
+            .find(|region| region.contains(address, rom_len))
+        {
+            Some(region) => {
+                self.region = region;
+                self.jump_to = Some(address);
+                self.select(address & !(self.data_type.width() - 1));
+            }
+            None => self.status = Some(format!("0x{address:08X} is not in any memory region")),
+        }
+    }
+
+    fn is_changed(&self, address: u32) -> bool {
+        self.view
+            .as_ref()
+            .and_then(|view| address.checked_sub(view.address))
+            .and_then(|offset| self.changed.get(offset as usize).copied())
+            .unwrap_or(false)
+    }
+
+    fn update_view(&mut self, view: MemoryView) {
+        self.changed.clear();
+        if let Some(ref old) = self.view {
+            self.changed.extend(
+                (view.address..view.address + view.bytes.len() as u32)
+                    .zip(view.bytes.iter())
+                    .map(|(address, &byte)| old.get(address).is_some_and(|old| old != byte)),
+            );
+        }
+        self.view = Some(view);
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Region {
+    Bios,
+    Ewram,
+    Iwram,
+    Io,
+    Palette,
+    Vram,
+    Oam,
+    Rom,
+    Sram,
+}
+
+impl Region {
+    const ALL: [Region; 9] = [
+        Region::Bios,
+        Region::Ewram,
+        Region::Iwram,
+        Region::Io,
+        Region::Palette,
+        Region::Vram,
+        Region::Oam,
+        Region::Rom,
+        Region::Sram,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Region::Bios => "BIOS",
+            Region::Ewram => "EWRAM",
+            Region::Iwram => "IWRAM",
+            Region::Io => "IO",
+            Region::Palette => "Palette",
+            Region::Vram => "VRAM",
+            Region::Oam => "OAM",
+            Region::Rom => "ROM",
+            Region::Sram => "SRAM",
+        }
+    }
+
+    fn base(self) -> u32 {
+        match self {
+            Region::Bios => 0x00000000,
+            Region::Ewram => 0x02000000,
+            Region::Iwram => 0x03000000,
+            Region::Io => 0x04000000,
+            Region::Palette => 0x05000000,
+            Region::Vram => 0x06000000,
+            Region::Oam => 0x07000000,
+            Region::Rom => 0x08000000,
+            Region::Sram => 0x0E000000,
+        }
+    }
+
+    fn size(self, rom_len: u32) -> u32 {
+        match self {
+            Region::Bios => BIOS_SIZE,
+            Region::Ewram => EWRAM_SIZE,
+            Region::Iwram => IWRAM_SIZE,
+            // Rounded up so that the last row is complete.
+            Region::Io => (IOREGS_SIZE + BYTES_PER_ROW - 1) & !(BYTES_PER_ROW - 1),
+            Region::Palette => PAL_SIZE,
+            Region::Vram => VRAM_SIZE,
+            Region::Oam => OAM_SIZE,
+            Region::Rom => rom_len.min(ROM_MAX_MASK + 1),
+            // Backup media isn't emulated yet so this always reads as zeroes and pokes are
+            // ignored.
+            Region::Sram => 0x10000,
+        }
+    }
+
+    fn contains(self, address: u32, rom_len: u32) -> bool {
+        address >= self.base() && address - self.base() < self.size(rom_len)
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum DataType {
+    U8,
+    U16,
+    U32,
+}
+
+impl DataType {
+    const ALL: [DataType; 3] = [DataType::U8, DataType::U16, DataType::U32];
+
+    fn name(self) -> &'static str {
+        match self {
+            DataType::U8 => "u8",
+            DataType::U16 => "u16",
+            DataType::U32 => "u32",
+        }
+    }
+
+    fn width(self) -> u32 {
+        match self {
+            DataType::U8 => 1,
+            DataType::U16 => 2,
+            DataType::U32 => 4,
+        }
+    }
+
+    fn fits(self, value: u32) -> bool {
+        match self {
+            DataType::U8 => value <= u8::MAX as u32,
+            DataType::U16 => value <= u16::MAX as u32,
+            DataType::U32 => true,
+        }
+    }
+}
+
+fn parse_hex(text: &str) -> Option<u32> {
+    let text = text.trim();
+    let text = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u32::from_str_radix(text, 16).ok()
+}
@@ -0,0 +1,326 @@
+use egui::{
+    plot::{Bar, BarChart, HLine, Plot},
+    Color32, ColorImage, Grid, ScrollArea, TextureHandle, Ui,
+};
+use gba::{
+    memory::{io::LCDControl, palette::Palette, OAM_SIZE, VRAM_SIZE},
+    video::obj::{self, ObjAttr0, ObjAttr1, ObjAttr2, ObjLineStats, ObjMode, ObjShape},
+    Gba, SCREEN_HEIGHT,
+};
+use util::{array, mem::read_u16};
+
+use crate::{bgr555_to_color32, GbaData};
+
+/// A copy of everything that is required to decode and draw objects.
+pub(crate) struct ObjData {
+    oam: [u8; OAM_SIZE as usize],
+    vram: Box<[u8; VRAM_SIZE as usize]>,
+    palette: Palette,
+    dispcnt: LCDControl,
+    line_stats: [ObjLineStats; SCREEN_HEIGHT],
+}
+
+impl ObjData {
+    pub(crate) fn pull(data: &mut Option<Box<ObjData>>, gba: &mut Gba) {
+        let data = data.get_or_insert_with(|| {
+            Box::new(ObjData {
+                oam: [0; OAM_SIZE as usize],
+                vram: array::boxed_copied(0),
+                palette: Palette::default(),
+                dispcnt: LCDControl::default(),
+                line_stats: [ObjLineStats::default(); SCREEN_HEIGHT],
+            })
+        });
+
+        data.oam.copy_from_slice(gba.memory().oam());
+        data.vram.copy_from_slice(gba.memory().vram());
+        data.palette = gba.memory().palette().clone();
+        data.dispcnt = LCDControl::new(gba.memory_mut().view16(gba::memory::io::DISPCNT));
+        data.line_stats = *gba.video().obj_line_stats();
+    }
+
+    fn attrs(&self, obj: usize) -> (ObjAttr0, ObjAttr1, ObjAttr2) {
+        (
+            ObjAttr0::new(read_u16(&self.oam, obj * 8)),
+            ObjAttr1::new(read_u16(&self.oam, obj * 8 + 2)),
+            ObjAttr2::new(read_u16(&self.oam, obj * 8 + 4)),
+        )
+    }
+
+    /// Returns the affine parameters PA, PB, PC, and PD for the given parameter group.
+    fn affine_params(&self, group: usize) -> [f32; 4] {
+        let param = |offset: usize| read_u16(&self.oam, offset + group * 32) as i16 as f32 / 256.0;
+        [param(0x06), param(0x0E), param(0x16), param(0x1E)]
+    }
+}
+
+#[derive(Default)]
+pub struct OamPane {
+    data: Option<Box<ObjData>>,
+    previews: Vec<Option<TextureHandle>>,
+    preview_buffer: Vec<u16>,
+    hide_disabled: bool,
+    selected_line: Option<usize>,
+}
+
+impl OamPane {
+    const PREVIEW_SCALE: f32 = 2.0;
+
+    pub(crate) fn render(&mut self, ui: &mut Ui, data: &mut GbaData) {
+        if let Some(obj_data) = data.obj_data.take() {
+            self.data = Some(obj_data);
+            self.update_previews(ui);
+        }
+        data.requests.obj_data = true;
+
+        let obj_data = if let Some(ref obj_data) = self.data {
+            obj_data
+        } else {
+            ui.label("Waiting for OAM...");
+            return;
+        };
+
+        Self::render_cycles_plot(ui, obj_data, &mut self.selected_line);
+
+        ui.horizontal(|ui| {
+            ui.checkbox(&mut self.hide_disabled, "Hide Disabled");
+            if let Some(line) = self.selected_line {
+                ui.label(format!("Showing objects dropped on line {line}"));
+                if ui.button("Show All").clicked() {
+                    self.selected_line = None;
+                }
+            }
+        });
+
+        ScrollArea::vertical().show(ui, |ui| {
+            Grid::new("OAM Grid")
+                .num_columns(8)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.strong("OBJ");
+                    ui.strong("Preview");
+                    ui.strong("Position");
+                    ui.strong("Shape/Size");
+                    ui.strong("Priority");
+                    ui.strong("Mode");
+                    ui.strong("Affine");
+                    ui.strong("Dropped Lines");
+                    ui.end_row();
+
+                    for obj in 0..128 {
+                        if let Some(line) = self.selected_line {
+                            if !obj_data.line_stats[line].is_dropped(obj) {
+                                continue;
+                            }
+                        }
+
+                        let (attr0, ..) = obj_data.attrs(obj);
+                        if self.hide_disabled && attr0.disabled() && !attr0.rotscale() {
+                            continue;
+                        }
+
+                        Self::render_obj_row(ui, obj, obj_data, self.previews[obj].as_ref());
+                    }
+                });
+        });
+    }
+
+    fn render_obj_row(
+        ui: &mut Ui,
+        obj: usize,
+        obj_data: &ObjData,
+        preview: Option<&TextureHandle>,
+    ) {
+        let (attr0, attr1, attr2) = obj_data.attrs(obj);
+        let (width, height) = attr1.size(attr0.shape());
+        let disabled = attr0.disabled() && !attr0.rotscale();
+
+        ui.label(format!("{obj}"));
+
+        if let Some(preview) = preview {
+            ui.image(preview, preview.size_vec2() * Self::PREVIEW_SCALE);
+        } else {
+            ui.label("-");
+        }
+
+        // X is a signed 9-bit value and Y wraps at 256.
+        let x = ((attr1.x() as i16) << 7) >> 7;
+        let y = if attr0.y() >= 160 {
+            attr0.y() as i16 - 256
+        } else {
+            attr0.y() as i16
+        };
+        if disabled {
+            ui.weak(format!("({x}, {y})"));
+        } else {
+            ui.label(format!("({x}, {y})"));
+        }
+
+        let shape = match attr0.shape() {
+            ObjShape::Square => "Square",
+            ObjShape::Horizontal => "Horizontal",
+            ObjShape::Vertical => "Vertical",
+            ObjShape::Prohibited => "Prohibited",
+        };
+        let colors = if attr0.palette256() {
+            "256 colors".to_owned()
+        } else {
+            format!("16 colors (palette {})", attr2.palette())
+        };
+        ui.label(format!(
+            "{shape} {width}x{height}\n{colors}\ntile {}",
+            attr2.character_name()
+        ));
+
+        ui.label(format!("{}", attr2.priority()));
+
+        let mode = match attr0.mode() {
+            ObjMode::Normal => "Normal",
+            ObjMode::SemiTransparent => "Semi-Transparent",
+            ObjMode::ObjWindow => "OBJ Window",
+            ObjMode::Invalid => "Prohibited",
+        };
+        if disabled {
+            ui.weak("Disabled");
+        } else if attr0.mosaic() {
+            ui.label(format!("{mode}\nMosaic"));
+        } else {
+            ui.label(mode);
+        }
+
+        if attr0.rotscale() {
+            let group = attr1.rotscale_param() as usize;
+            let [pa, pb, pc, pd] = obj_data.affine_params(group);
+            let double_size = if attr0.double_size() {
+                " (double size)"
+            } else {
+                ""
+            };
+            ui.label(format!(
+                "group {group}{double_size}\nPA={pa:.3} PB={pb:.3}\nPC={pc:.3} PD={pd:.3}"
+            ));
+        } else {
+            let hflip = if attr1.horizontal_flip() { "H" } else { "-" };
+            let vflip = if attr1.vertical_flip() { "V" } else { "-" };
+            ui.label(format!("flip {hflip}{vflip}"));
+        }
+
+        let dropped_lines = obj_data
+            .line_stats
+            .iter()
+            .filter(|stats| stats.is_dropped(obj))
+            .count();
+        if dropped_lines > 0 {
+            ui.colored_label(
+                Color32::from_rgb(0xf0, 0x3e, 0x3e),
+                dropped_lines.to_string(),
+            );
+        } else {
+            ui.label("0");
+        }
+
+        ui.end_row();
+    }
+
+    fn render_cycles_plot(ui: &mut Ui, obj_data: &ObjData, selected_line: &mut Option<usize>) {
+        const USED_COLOR: Color32 = Color32::from_rgb(0x1c, 0x7e, 0xd6);
+        const DROPPED_COLOR: Color32 = Color32::from_rgb(0xf0, 0x3e, 0x3e);
+
+        let bars = obj_data
+            .line_stats
+            .iter()
+            .enumerate()
+            .map(|(line, stats)| {
+                let color = if stats.dropped != 0 {
+                    DROPPED_COLOR
+                } else {
+                    USED_COLOR
+                };
+                Bar::new(line as f64, stats.cycles_used as f64)
+                    .fill(color)
+                    .name(format!("line {line}"))
+            })
+            .collect();
+
+        let budget = obj::obj_cycle_budget(obj_data.dispcnt);
+        let lines_with_drops: Vec<usize> = obj_data
+            .line_stats
+            .iter()
+            .enumerate()
+            .filter(|(_, stats)| stats.dropped != 0)
+            .map(|(line, _)| line)
+            .collect();
+
+        ui.label(format!(
+            "OBJ cycles per line (budget: {budget}, lines with dropped objects: {})",
+            lines_with_drops.len()
+        ));
+
+        let chart = BarChart::new(bars).name("OBJ Cycles");
+        Plot::new("OBJ Cycles Per Line")
+            .height(96.0)
+            .show_axes([true, false])
+            .allow_drag(false)
+            .allow_zoom(false)
+            .include_y(budget as f64)
+            .show(ui, |plot_ui| {
+                plot_ui.hline(HLine::new(budget as f64).color(DROPPED_COLOR));
+                plot_ui.bar_chart(chart);
+            });
+
+        if !lines_with_drops.is_empty() {
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Lines with dropped objects:");
+                for line in lines_with_drops {
+                    ui.selectable_value(selected_line, Some(line), line.to_string());
+                }
+            });
+        }
+    }
+
+    fn update_previews(&mut self, ui: &mut Ui) {
+        let obj_data = if let Some(ref obj_data) = self.data {
+            obj_data
+        } else {
+            return;
+        };
+
+        self.previews.resize(128, None);
+        let mapping = obj_data.dispcnt.obj_char_vram_mapping();
+
+        for obj in 0..128 {
+            let attrs = obj_data.attrs(obj);
+            let (width, height) = attrs.1.size(attrs.0.shape());
+
+            obj::render_obj_preview(
+                attrs,
+                mapping,
+                &obj_data.vram,
+                &obj_data.palette,
+                &mut self.preview_buffer,
+            );
+            let image = ColorImage {
+                size: [width as usize, height as usize],
+                pixels: self
+                    .preview_buffer
+                    .iter()
+                    .map(|&color| {
+                        if color & 0x8000 != 0 {
+                            bgr555_to_color32(color)
+                        } else {
+                            Color32::TRANSPARENT
+                        }
+                    })
+                    .collect(),
+            };
+
+            match self.previews[obj] {
+                Some(ref mut texture) => texture.set(image),
+                None => {
+                    self.previews[obj] =
+                        Some(ui.ctx().load_texture(format!("OBJ {obj} Preview"), image));
+                }
+            }
+        }
+    }
+}
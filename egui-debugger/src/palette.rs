@@ -0,0 +1,325 @@
+use std::path::Path;
+
+use egui::{color_picker, Grid, Sense, Slider, TextEdit, Ui, Vec2};
+use gba::memory::palette::Palette;
+use pyrite::GbaHandle;
+
+use crate::{bgr555_to_color32, GbaData};
+
+#[derive(Default)]
+pub struct PalettePane {
+    palette: Option<Box<Palette>>,
+    selected: Option<(PaletteKind, u8)>,
+    file_kind: PaletteKind,
+    file_path: String,
+    file_status: Option<String>,
+}
+
+impl PalettePane {
+    const SWATCH_SIZE: f32 = 14.0;
+
+    pub(crate) fn render(&mut self, ui: &mut Ui, data: &mut GbaData, gba: &GbaHandle) {
+        if let Some(palette) = data.palette.take() {
+            self.palette = Some(palette);
+        }
+        data.requests.palette = true;
+
+        if self.palette.is_none() {
+            ui.label("Waiting for palette...");
+            return;
+        }
+
+        ui.horizontal_top(|ui| {
+            for kind in [PaletteKind::Background, PaletteKind::Object] {
+                ui.vertical(|ui| {
+                    ui.strong(kind.name());
+                    self.render_swatches(ui, kind);
+                });
+            }
+
+            ui.vertical(|ui| self.render_selected(ui, gba));
+        });
+
+        ui.separator();
+        self.render_file_controls(ui, gba);
+    }
+
+    fn render_swatches(&mut self, ui: &mut Ui, kind: PaletteKind) {
+        let palette = self.palette.as_ref().unwrap();
+
+        Grid::new(kind.name())
+            .spacing(Vec2::splat(1.0))
+            .show(ui, |ui| {
+                for row in 0..16u8 {
+                    for col in 0..16u8 {
+                        let entry = row * 16 + col;
+                        let color = kind.get(palette, entry);
+                        let selected = self.selected == Some((kind, entry));
+
+                        let (rect, response) =
+                            ui.allocate_exact_size(Vec2::splat(Self::SWATCH_SIZE), Sense::click());
+                        ui.painter()
+                            .rect_filled(rect, 0.0, bgr555_to_color32(color));
+                        if selected {
+                            ui.painter().rect_stroke(
+                                rect,
+                                0.0,
+                                (2.0, ui.visuals().strong_text_color()),
+                            );
+                        }
+
+                        let response = response.on_hover_text(format!(
+                            "{} #{entry} (0x{:08X})\n{}",
+                            kind.name(),
+                            kind.address(entry),
+                            describe_color(color)
+                        ));
+                        if response.clicked() {
+                            self.selected = Some((kind, entry));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+    }
+
+    fn render_selected(&mut self, ui: &mut Ui, gba: &GbaHandle) {
+        let (kind, entry) = if let Some(selected) = self.selected {
+            selected
+        } else {
+            ui.label("Select a color to edit it.");
+            return;
+        };
+
+        let palette = self.palette.as_mut().unwrap();
+        let color = kind.get(palette, entry);
+
+        ui.strong(format!("{} #{entry}", kind.name()));
+        ui.label(format!("Address: 0x{:08X}", kind.address(entry)));
+        ui.label(describe_color(color));
+
+        let (rect, _) = ui.allocate_exact_size(Vec2::new(64.0, 32.0), Sense::hover());
+        ui.painter()
+            .rect_filled(rect, 0.0, bgr555_to_color32(color));
+
+        let mut r = color & 0x1F;
+        let mut g = (color >> 5) & 0x1F;
+        let mut b = (color >> 10) & 0x1F;
+        let mut changed = false;
+        changed |= ui.add(Slider::new(&mut r, 0..=31).text("Red")).changed();
+        changed |= ui.add(Slider::new(&mut g, 0..=31).text("Green")).changed();
+        changed |= ui.add(Slider::new(&mut b, 0..=31).text("Blue")).changed();
+
+        let mut picked = bgr555_to_color32(color);
+        if color_picker::color_edit_button_srgba(ui, &mut picked, color_picker::Alpha::Opaque)
+            .changed()
+        {
+            r = (picked.r() >> 3) as u16;
+            g = (picked.g() >> 3) as u16;
+            b = (picked.b() >> 3) as u16;
+            changed = true;
+        }
+
+        if changed {
+            let new_color = r | (g << 5) | (b << 10) | (color & 0x8000);
+            let address = kind.address(entry);
+
+            // Update our local copy right away so that the editor doesn't flicker while waiting for
+            // the next frame from the GBA.
+            palette.store16(address, new_color);
+            gba.after_frame(move |gba, _| {
+                gba.memory_mut().palette_mut().store16(address, new_color)
+            });
+        }
+    }
+
+    fn render_file_controls(&mut self, ui: &mut Ui, gba: &GbaHandle) {
+        ui.horizontal(|ui| {
+            ui.selectable_value(&mut self.file_kind, PaletteKind::Background, "BG");
+            ui.selectable_value(&mut self.file_kind, PaletteKind::Object, "OBJ");
+            ui.add(
+                TextEdit::singleline(&mut self.file_path).hint_text("palette.pal or palette.gpl"),
+            );
+
+            if ui.button("Export").clicked() {
+                let palette = self.palette.as_ref().unwrap();
+                let colors: Vec<u16> = (0..=255u8)
+                    .map(|entry| self.file_kind.get(palette, entry))
+                    .collect();
+                self.file_status = Some(match export_palette(&self.file_path, &colors) {
+                    Ok(()) => format!(
+                        "exported {} palette to {}",
+                        self.file_kind.name(),
+                        self.file_path
+                    ),
+                    Err(err) => format!("error exporting palette: {err}"),
+                });
+            }
+
+            if ui.button("Import").clicked() {
+                self.file_status = Some(match import_palette(&self.file_path) {
+                    Ok(colors) => {
+                        let kind = self.file_kind;
+                        let palette = self.palette.as_mut().unwrap();
+                        for (entry, &color) in colors.iter().enumerate().take(256) {
+                            palette.store16(kind.address(entry as u8), color);
+                        }
+
+                        let count = colors.len().min(256);
+                        gba.after_frame(move |gba, _| {
+                            let palette = gba.memory_mut().palette_mut();
+                            for (entry, &color) in colors.iter().enumerate().take(256) {
+                                palette.store16(kind.address(entry as u8), color);
+                            }
+                        });
+                        format!("imported {count} colors into the {} palette", kind.name())
+                    }
+                    Err(err) => format!("error importing palette: {err}"),
+                });
+            }
+        });
+
+        if let Some(ref status) = self.file_status {
+            ui.label(status);
+        }
+    }
+}
+
+#[derive(Default, Copy, Clone, PartialEq, Eq)]
+enum PaletteKind {
+    #[default]
+    Background,
+    Object,
+}
+
+impl PaletteKind {
+    fn name(&self) -> &'static str {
+        match self {
+            PaletteKind::Background => "BG Palette",
+            PaletteKind::Object => "OBJ Palette",
+        }
+    }
+
+    fn get(&self, palette: &Palette, entry: u8) -> u16 {
+        match self {
+            PaletteKind::Background => palette.get_bg256(entry),
+            PaletteKind::Object => palette.get_obj256(entry),
+        }
+    }
+
+    fn address(&self, entry: u8) -> u32 {
+        let base = match self {
+            PaletteKind::Background => 0x05000000,
+            PaletteKind::Object => 0x05000200,
+        };
+        base + entry as u32 * 2
+    }
+}
+
+fn describe_color(color: u16) -> String {
+    let c = bgr555_to_color32(color);
+    format!(
+        "BGR555: 0x{:04X}\nRGB: ({}, {}, {}) #{:02X}{:02X}{:02X}",
+        color & 0x7FFF,
+        c.r(),
+        c.g(),
+        c.b(),
+        c.r(),
+        c.g(),
+        c.b()
+    )
+}
+
+fn bgr555_to_rgb(color: u16) -> [u8; 3] {
+    let c = bgr555_to_color32(color);
+    [c.r(), c.g(), c.b()]
+}
+
+fn rgb_to_bgr555([r, g, b]: [u8; 3]) -> u16 {
+    (r >> 3) as u16 | ((g >> 3) as u16) << 5 | ((b >> 3) as u16) << 10
+}
+
+/// Writes colors to a JASC-PAL (`.pal`) or GIMP (`.gpl`) palette file depending on the extension
+/// of the path.
+fn export_palette(path: &str, colors: &[u16]) -> Result<(), String> {
+    let source = match PaletteFormat::from_path(path)? {
+        PaletteFormat::Jasc => {
+            let mut source = format!("JASC-PAL\r\n0100\r\n{}\r\n", colors.len());
+            for &color in colors {
+                let [r, g, b] = bgr555_to_rgb(color);
+                source.push_str(&format!("{r} {g} {b}\r\n"));
+            }
+            source
+        }
+
+        PaletteFormat::Gimp => {
+            let mut source = String::from("GIMP Palette\nName: Pyrite\nColumns: 16\n#\n");
+            for (idx, &color) in colors.iter().enumerate() {
+                let [r, g, b] = bgr555_to_rgb(color);
+                source.push_str(&format!("{r:3} {g:3} {b:3}\tColor {idx}\n"));
+            }
+            source
+        }
+    };
+
+    std::fs::write(path, source).map_err(|err| err.to_string())
+}
+
+/// Reads the colors from a JASC-PAL (`.pal`) or GIMP (`.gpl`) palette file depending on the
+/// extension of the path and converts them into BGR555.
+fn import_palette(path: &str) -> Result<Vec<u16>, String> {
+    let format = PaletteFormat::from_path(path)?;
+    let source = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut lines = source.lines().map(str::trim);
+
+    let header = match format {
+        PaletteFormat::Jasc => "JASC-PAL",
+        PaletteFormat::Gimp => "GIMP Palette",
+    };
+    if lines.next() != Some(header) {
+        return Err(format!("missing `{header}` header"));
+    }
+
+    let color_lines: Box<dyn Iterator<Item = &str>> = match format {
+        // The version and color count come right after the header.
+        PaletteFormat::Jasc => Box::new(lines.skip(2)),
+
+        // Skip the name/columns attributes and comments.
+        PaletteFormat::Gimp => Box::new(lines.filter(|line| {
+            !line.starts_with('#') && !line.starts_with("Name:") && !line.starts_with("Columns:")
+        })),
+    };
+
+    let mut colors = Vec::with_capacity(256);
+    for (idx, line) in color_lines.filter(|line| !line.is_empty()).enumerate() {
+        let mut rgb = [0u8; 3];
+        let mut components = line.split_whitespace();
+        for component in rgb.iter_mut() {
+            *component = components
+                .next()
+                .and_then(|c| c.parse().ok())
+                .ok_or_else(|| format!("invalid color on line {}", idx + 1))?;
+        }
+        colors.push(rgb_to_bgr555(rgb));
+    }
+    Ok(colors)
+}
+
+enum PaletteFormat {
+    Jasc,
+    Gimp,
+}
+
+impl PaletteFormat {
+    fn from_path(path: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("pal") => Ok(PaletteFormat::Jasc),
+            Some("gpl") => Ok(PaletteFormat::Gimp),
+            _ => Err("palette files must end with .pal or .gpl".into()),
+        }
+    }
+}
@@ -1,11 +1,113 @@
+mod mixer;
 pub mod sampler;
+pub mod synth;
 
 use crate::{
     dma,
-    memory::io::{Dimension, Direction, FifoChannel, IoRegisters, PSGChannel, Resolution, Timing},
+    memory::io::{
+        ChannelLRVolumeEnable, DMASoundControlMixing, Dimension, Direction, FifoChannel,
+        IoRegisters, PSGChannel, Resolution, Timing,
+    },
     scheduler::{EventTag, Scheduler},
     Gba,
 };
+use util::primitive_enum;
+
+primitive_enum! {
+    /// One of the six sources that are mixed together into the GBA's audio output.
+    pub enum AudioChannel: u8 {
+        Sound1,
+        Sound2,
+        Sound3,
+        Sound4,
+        FifoA,
+        FifoB,
+    }
+}
+
+impl AudioChannel {
+    pub const ALL: [AudioChannel; 6] = [
+        AudioChannel::Sound1,
+        AudioChannel::Sound2,
+        AudioChannel::Sound3,
+        AudioChannel::Sound4,
+        AudioChannel::FifoA,
+        AudioChannel::FifoB,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AudioChannel::Sound1 => "PSG 1",
+            AudioChannel::Sound2 => "PSG 2",
+            AudioChannel::Sound3 => "PSG 3",
+            AudioChannel::Sound4 => "PSG 4",
+            AudioChannel::FifoA => "FIFO A",
+            AudioChannel::FifoB => "FIFO B",
+        }
+    }
+}
+
+/// Channels that have been muted or soloed for debugging. While any channel is soloed, only the
+/// soloed channels can be heard.
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
+pub struct ChannelMask {
+    muted: u8,
+    soloed: u8,
+}
+
+impl ChannelMask {
+    pub fn set_muted(&mut self, channel: AudioChannel, muted: bool) {
+        let bit = 1 << u8::from(channel);
+        self.muted = if muted {
+            self.muted | bit
+        } else {
+            self.muted & !bit
+        };
+    }
+
+    pub fn muted(&self, channel: AudioChannel) -> bool {
+        self.muted & (1 << u8::from(channel)) != 0
+    }
+
+    pub fn set_soloed(&mut self, channel: AudioChannel, soloed: bool) {
+        let bit = 1 << u8::from(channel);
+        self.soloed = if soloed {
+            self.soloed | bit
+        } else {
+            self.soloed & !bit
+        };
+    }
+
+    pub fn soloed(&self, channel: AudioChannel) -> bool {
+        self.soloed & (1 << u8::from(channel)) != 0
+    }
+
+    pub fn audible(&self, channel: AudioChannel) -> bool {
+        if self.soloed != 0 {
+            self.soloed(channel)
+        } else {
+            !self.muted(channel)
+        }
+    }
+
+    /// Silences the outputs of all channels that are not audible.
+    pub(crate) fn apply(&self, fifo_a: &mut i8, fifo_b: &mut i8, psg: &mut [i16; 4]) {
+        if !self.audible(AudioChannel::FifoA) {
+            *fifo_a = 0;
+        }
+        if !self.audible(AudioChannel::FifoB) {
+            *fifo_b = 0;
+        }
+        for (idx, output) in psg.iter_mut().enumerate() {
+            if !self.audible(AudioChannel::from(idx as u8)) {
+                *output = 0;
+            }
+        }
+    }
+}
+
+/// Limits the rate at which wave RAM is sampled to 32768Hz by default.
+const DEFAULT_MIN_WAVE_CYCLES: u32 = 512;
 
 #[derive(Default)]
 pub struct GbaAudio {
@@ -13,11 +115,16 @@ pub struct GbaAudio {
     commands: Vec<Command>,
     last_update_time: u64,
     psg_envelope_volumes: [u16; 4],
+    channel_mask: ChannelMask,
 
     /// Maximum frequency at which we will sample waveram.
     min_wave_cycles: u32,
     last_wave_sample_time: u64,
     last_wave_freq_rate: u16,
+    /// Number of samples that wave RAM has been rotated by since playback was started.
+    wave_rotation: u32,
+    /// The bank that `wave_rotation` applies to, or `None` if both banks were rotated together.
+    wave_rotation_bank: Option<u16>,
 }
 
 impl GbaAudio {
@@ -27,16 +134,57 @@ impl GbaAudio {
             commands: Vec::with_capacity(1024),
             last_update_time: 0,
             psg_envelope_volumes: [0; 4],
+            channel_mask: ChannelMask::default(),
 
-            min_wave_cycles: 512, // maximum sample rate of 32768 by default
+            min_wave_cycles: DEFAULT_MIN_WAVE_CYCLES,
             last_wave_sample_time: 0,
             last_wave_freq_rate: 0,
+            wave_rotation: 0,
+            wave_rotation_bank: None,
         }
     }
 
-    pub fn clear(&mut self, now: u64) {
+    /// Clears the commands from the previous frame. The current mixer settings are always pushed
+    /// first so that the commands for a frame can be played back without having seen any of the
+    /// commands from previous frames.
+    pub fn clear(&mut self, ioregs: &IoRegisters) {
         self.commands.clear();
-        self.last_update_time = now;
+        self.last_update_time = ioregs.time;
+
+        self.commands.push(Command::SetPSGMixing(ioregs.soundcnt_l));
+        self.commands
+            .push(Command::SetDMASoundMixing(ioregs.soundcnt_h));
+        self.commands
+            .push(Command::SetWaveVolume(ioregs.sound3cnt_h.volume()));
+        self.commands
+            .push(Command::SetChannelMask(self.channel_mask));
+    }
+
+    pub fn channel_mask(&self) -> ChannelMask {
+        self.channel_mask
+    }
+
+    /// Samples wave RAM at the rate that Sound 3 actually plays at (up to 2.1MHz) instead of
+    /// capping it at 32768Hz. This generates a lot more commands for high pitched tones, but is
+    /// required for [`NativeSynth`](synth::NativeSynth) to match hardware.
+    pub(crate) fn set_sample_exact(&mut self, sample_exact: bool) {
+        self.min_wave_cycles = if sample_exact {
+            0
+        } else {
+            DEFAULT_MIN_WAVE_CYCLES
+        };
+    }
+
+    pub(crate) fn set_channel_mask(&mut self, mask: ChannelMask, ioregs: &IoRegisters) {
+        self.channel_mask = mask;
+        self.wait(ioregs.time);
+        self.commands.push(Command::SetChannelMask(mask));
+    }
+
+    /// Waits until the end of the frame so that the commands for a frame always cover the
+    /// frame's full duration, even if no audio events happened near the end of it.
+    pub(crate) fn end_frame(&mut self, ioregs: &IoRegisters) {
+        self.wait(ioregs.time);
     }
 
     pub fn commands(&self) -> &[Command] {
@@ -48,14 +196,23 @@ impl GbaAudio {
         let samples =
             ((ioregs.time - self.last_wave_sample_time) / cycles_per_sample as u64) as u32;
 
-        let bank;
-        if ioregs.sound3cnt_l.dimension() == Dimension::Single {
-            bank = ioregs.sound3cnt_l.bank_number();
-            ioregs.waveram.shift(samples, bank);
+        // In two bank mode playback starts with the selected bank and continues into the other
+        // one, which is rotated into its place after 32 samples.
+        let bank = ioregs.sound3cnt_l.bank_number();
+        let rotation_bank = if ioregs.sound3cnt_l.dimension() == Dimension::Single {
+            Some(bank)
         } else {
-            bank = 0;
-            ioregs.waveram.wide_shift(samples);
+            None
+        };
+        if rotation_bank != self.wave_rotation_bank {
+            self.restart_waveram(ioregs);
+            self.wave_rotation_bank = rotation_bank;
+        }
+        match rotation_bank {
+            Some(bank) => ioregs.waveram.shift(samples, bank),
+            None => ioregs.waveram.wide_shift(samples),
         }
+        self.wave_rotation = (self.wave_rotation + samples) % 64;
 
         let sample = (ioregs.waveram.read_sample(bank) as i16) << 4;
         self.wait(ioregs.time);
@@ -67,6 +224,18 @@ impl GbaAudio {
             .schedule(wave_resample, next_cycles, EventTag::SamplePSG3);
     }
 
+    /// Rotates wave RAM back to its original position so that playback restarts from the first
+    /// sample of the selected bank.
+    fn restart_waveram(&mut self, ioregs: &mut IoRegisters) {
+        match self.wave_rotation_bank {
+            Some(bank) => ioregs
+                .waveram
+                .shift((32 - self.wave_rotation % 32) % 32, bank),
+            None => ioregs.waveram.wide_shift((64 - self.wave_rotation) % 64),
+        }
+        self.wave_rotation = 0;
+    }
+
     pub fn stop_psg(&mut self, chan: PSGChannel, ioregs: &mut IoRegisters) {
         if !ioregs.soundcnt_x.sound_on(chan) {
             return;
@@ -103,50 +272,89 @@ impl GbaAudio {
         };
         let volume_index = u16::from(chan) as usize;
 
-        let reschedule = if direction == Direction::Increasing {
-            self.psg_envelope_volumes[volume_index] += 1;
-            self.psg_envelope_volumes[volume_index] < 15
+        let volume = &mut self.psg_envelope_volumes[volume_index];
+        if direction == Direction::Increasing {
+            if *volume >= 15 {
+                return;
+            }
+            *volume += 1;
         } else {
-            self.psg_envelope_volumes[volume_index] -= 1;
-            self.psg_envelope_volumes[volume_index] > 0
-        };
+            if *volume == 0 {
+                return;
+            }
+            *volume -= 1;
+        }
+        let volume = *volume;
 
         self.wait(ioregs.time);
-        self.commands.push(Command::SetPSGEnvelopeVolume(
-            chan,
-            self.psg_envelope_volumes[volume_index],
-        ));
+        self.commands
+            .push(Command::SetPSGEnvelopeVolume(chan, volume));
 
-        if reschedule {
+        if Self::envelope_can_step(direction, volume) {
             self.schedule_psg_envelope_step(chan, ioregs);
         }
     }
 
-    fn psg_sweep_step(&mut self, ioregs: &mut IoRegisters) {
-        let delta = ioregs.sound1cnt_x.freq_setting() >> ioregs.sound1cnt_l.shifts();
-        let frate = if ioregs.sound1cnt_l.direction() == Direction::Increasing {
-            ioregs.sound1cnt_x.freq_setting().saturating_add(delta)
+    /// Returns true if an envelope moving in `direction` can still change the volume.
+    fn envelope_can_step(direction: Direction, volume: u16) -> bool {
+        if direction == Direction::Increasing {
+            volume < 15
         } else {
-            ioregs.sound1cnt_x.freq_setting().saturating_sub(delta)
-        };
+            volume > 0
+        }
+    }
 
+    fn psg_sweep_step(&mut self, ioregs: &mut IoRegisters) {
+        if ioregs.sound1cnt_l.sweep_time() == 0 || ioregs.sound1cnt_l.shifts() == 0 {
+            return;
+        }
+
+        let frate = Self::psg_sweep_calculate(ioregs);
         if frate >= 2048 {
             self.stop_psg(PSGChannel::Sound1, ioregs);
-        } else if (frate as i16) >= 0 && frate != ioregs.sound1cnt_x.freq_setting() {
-            ioregs.sound1cnt_x.set_freq_setting(frate);
-            self.wait(ioregs.time);
-            self.commands
-                .push(Command::SetSquareFrequencyRate(PSGChannel::Sound1, frate));
-            self.schedule_psg_sweep_step(ioregs);
+            return;
+        }
+
+        ioregs.sound1cnt_x.set_freq_setting(frate);
+        self.wait(ioregs.time);
+        self.commands
+            .push(Command::SetSquareFrequencyRate(PSGChannel::Sound1, frate));
+
+        // The new frequency is immediately run through the sweep calculation again and the
+        // channel is disabled if that would overflow, even though the result is discarded.
+        if Self::psg_sweep_calculate(ioregs) >= 2048 {
+            self.stop_psg(PSGChannel::Sound1, ioregs);
+            return;
+        }
+
+        self.schedule_psg_sweep_step(ioregs);
+    }
+
+    /// Calculates the next frequency setting of Sound 1 from the current sweep settings. Values
+    /// of 2048 or more mean that the sweep has overflowed.
+    fn psg_sweep_calculate(ioregs: &IoRegisters) -> u16 {
+        let freq = ioregs.sound1cnt_x.freq_setting();
+        let delta = freq >> ioregs.sound1cnt_l.shifts();
+        if ioregs.sound1cnt_l.direction() == Direction::Increasing {
+            freq + delta
+        } else {
+            freq - delta
         }
     }
 
     fn set_psg_sweep_control(&mut self, ioregs: &IoRegisters) {
-        if !ioregs.soundcnt_x.master_enable() {
+        if !ioregs.soundcnt_x.master_enable() || !ioregs.soundcnt_x.sound_on(PSGChannel::Sound1) {
             return;
         }
-        self.wait(ioregs.time);
-        // FIXME reimplement this
+
+        if ioregs.sound1cnt_l.sweep_time() == 0 {
+            self.scheduler
+                .unschedule_matching(|event| event.tag == EventTag::SweepTickPSG1);
+        } else if ioregs.sound1cnt_l.shifts() > 0
+            && !self.scheduler.contains_tag(EventTag::SweepTickPSG1)
+        {
+            self.schedule_psg_sweep_step(ioregs);
+        }
     }
 
     fn set_psg_square_duty_len_env(&mut self, chan: PSGChannel, ioregs: &IoRegisters) {
@@ -194,6 +402,26 @@ impl GbaAudio {
         });
     }
 
+    /// Starts or stops the length counter of a PSG that is already playing when its length
+    /// flag is written without restarting the sound.
+    fn update_psg_length_flag(
+        &mut self,
+        chan: PSGChannel,
+        length_flag: bool,
+        ioregs: &IoRegisters,
+    ) {
+        if !ioregs.soundcnt_x.sound_on(chan) {
+            return;
+        }
+
+        let tag = EventTag::psg_length_end(chan);
+        if !length_flag {
+            self.scheduler.unschedule_matching(|event| event.tag == tag);
+        } else if !self.scheduler.contains_tag(tag) {
+            self.schedule_psg_length_end(chan, ioregs);
+        }
+    }
+
     fn schedule_psg_length_end(&mut self, chan: PSGChannel, ioregs: &IoRegisters) {
         use PSGChannel::*;
 
@@ -282,10 +510,16 @@ impl GbaAudio {
             }
 
             if ioregs.sound4cnt_l.envelope_step_time() > 0
-                && ioregs.sound4cnt_l.initial_envelope_volume() > 0
+                && Self::envelope_can_step(
+                    ioregs.sound4cnt_l.envelope_direction(),
+                    ioregs.sound4cnt_l.initial_envelope_volume(),
+                )
             {
                 self.schedule_psg_envelope_step(Sound4, ioregs);
             }
+        } else {
+            let length_flag = ioregs.sound4cnt_h.length_flag();
+            self.update_psg_length_flag(Sound4, length_flag, ioregs);
         }
     }
 
@@ -304,6 +538,7 @@ impl GbaAudio {
             self.unschedule_psg_events(Sound3);
             self.last_wave_sample_time = ioregs.time;
             self.last_wave_freq_rate = ioregs.sound3cnt_x.freq_setting();
+            self.restart_waveram(ioregs);
             self.scheduler
                 .schedule(wave_resample, 0, EventTag::SamplePSG3);
 
@@ -314,6 +549,9 @@ impl GbaAudio {
             return;
         }
 
+        let length_flag = ioregs.sound3cnt_x.length_flag();
+        self.update_psg_length_flag(Sound3, length_flag, ioregs);
+
         if ioregs.soundcnt_x.sound_on(Sound3)
             && ioregs.sound3cnt_x.freq_setting() != self.last_wave_freq_rate
         {
@@ -355,16 +593,23 @@ impl GbaAudio {
                 self.schedule_psg_length_end(chan, ioregs);
             }
 
-            if dle.envelope_step_time() > 0 && dle.initial_envelope_volume() > 0 {
+            if dle.envelope_step_time() > 0
+                && Self::envelope_can_step(dle.envelope_direction(), dle.initial_envelope_volume())
+            {
                 self.schedule_psg_envelope_step(chan, ioregs);
             }
 
-            if chan == Sound1
-                && ioregs.sound1cnt_l.sweep_time() > 0
-                && ioregs.sound1cnt_l.shifts() > 0
-            {
-                self.schedule_psg_sweep_step(ioregs)
+            if chan == Sound1 && ioregs.sound1cnt_l.shifts() > 0 {
+                // An overflow check is performed immediately when the sound is restarted.
+                if Self::psg_sweep_calculate(ioregs) >= 2048 {
+                    self.stop_psg(Sound1, ioregs);
+                } else if ioregs.sound1cnt_l.sweep_time() > 0 {
+                    self.schedule_psg_sweep_step(ioregs)
+                }
             }
+        } else {
+            let length_flag = ctl.length_flag();
+            self.update_psg_length_flag(chan, length_flag, ioregs);
         }
     }
 
@@ -373,6 +618,23 @@ impl GbaAudio {
         self.commands.push(Command::SetResolution(resolution));
     }
 
+    fn set_psg_mixing(&mut self, ioregs: &IoRegisters) {
+        self.wait(ioregs.time);
+        self.commands.push(Command::SetPSGMixing(ioregs.soundcnt_l));
+    }
+
+    fn set_dma_sound_mixing(&mut self, ioregs: &IoRegisters) {
+        self.wait(ioregs.time);
+        self.commands
+            .push(Command::SetDMASoundMixing(ioregs.soundcnt_h));
+    }
+
+    fn set_wave_volume(&mut self, ioregs: &IoRegisters) {
+        self.wait(ioregs.time);
+        self.commands
+            .push(Command::SetWaveVolume(ioregs.sound3cnt_h.volume()));
+    }
+
     fn set_bias(&mut self, bias: u16, ioregs: &IoRegisters) {
         self.wait(ioregs.time);
         self.commands.push(Command::SetBias(bias));
@@ -466,6 +728,18 @@ pub fn bias_changed(gba: &mut Gba) {
         .set_bias(gba.mem.ioregs.soundbias.bias(), &gba.mem.ioregs);
 }
 
+pub fn psg_mixing_changed(gba: &mut Gba) {
+    gba.audio.set_psg_mixing(&gba.mem.ioregs);
+}
+
+pub fn dma_sound_mixing_changed(gba: &mut Gba) {
+    gba.audio.set_dma_sound_mixing(&gba.mem.ioregs);
+}
+
+pub fn wave_volume_changed(gba: &mut Gba) {
+    gba.audio.set_wave_volume(&gba.mem.ioregs);
+}
+
 pub fn check_fifo_timer_overflow(timer: usize, gba: &mut Gba) {
     if gba.mem.ioregs.soundcnt_h.dma_enable(FifoChannel::A)
         && gba.mem.ioregs.soundcnt_h.dma_timer_select(FifoChannel::A) == timer
@@ -497,10 +771,349 @@ pub enum Command {
     SetResolution(Resolution),
     SetBias(u16),
 
-    SetNoiseFrequencyParams { r: u8, s: u8 },
+    SetNoiseFrequencyParams {
+        r: u8,
+        s: u8,
+    },
     SetNoiseCounterWidth(u16),
     SetPSGEnabled(PSGChannel, bool),
     SetSquareFrequencyRate(PSGChannel, u16),
     SetSquareDuty(PSGChannel, u16),
     SetPSGEnvelopeVolume(PSGChannel, u16),
+
+    /// SOUNDCNT_L: PSG master volumes and left/right enables.
+    SetPSGMixing(ChannelLRVolumeEnable),
+    /// SOUNDCNT_H: PSG and FIFO volumes and FIFO left/right enables.
+    SetDMASoundMixing(DMASoundControlMixing),
+    /// Sound 3 output volume as a percentage (0, 25, 50, 75, or 100).
+    SetWaveVolume(u32),
+    /// Channels muted or soloed by a debugger.
+    SetChannelMask(ChannelMask),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::io::*;
+    use synth::NativeSynth;
+
+    const SWEEP_STEP: u64 = Gba::CYCLES_PER_SECOND as u64 / 128;
+    const ENVELOPE_STEP: u64 = Gba::CYCLES_PER_SECOND as u64 / 64;
+    const LENGTH_STEP: u64 = Gba::CYCLES_PER_SECOND as u64 / 256;
+
+    /// Creates a GBA with the sound master enable set so that the APU can be driven entirely
+    /// through IO register writes.
+    fn setup() -> Gba {
+        let mut gba = Gba::new();
+        gba.reset(false);
+        write(&mut gba, SOUNDCNT_X, 0x0080);
+        gba
+    }
+
+    fn write(gba: &mut Gba, address: u32, value: u16) {
+        gba.mem.write16_as_cpu(address, value);
+        run(gba, 0);
+    }
+
+    /// Processes all events for the next `cycles` cycles without running the CPU.
+    fn run(gba: &mut Gba, cycles: u64) {
+        let target = gba.mem.ioregs.time + cycles;
+        while let Some((event, when)) = gba.scheduler.next(target) {
+            gba.mem.ioregs.time = when;
+            (event)(gba);
+        }
+        gba.mem.ioregs.time = target;
+    }
+
+    fn sound_on(gba: &Gba, chan: PSGChannel) -> bool {
+        gba.mem.ioregs.soundcnt_x.sound_on(chan)
+    }
+
+    fn square_frequencies(gba: &Gba) -> Vec<u16> {
+        gba.audio
+            .commands()
+            .iter()
+            .filter_map(|command| match *command {
+                Command::SetSquareFrequencyRate(PSGChannel::Sound1, rate) => Some(rate),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn envelope_volumes(gba: &Gba, chan: PSGChannel) -> Vec<u16> {
+        gba.audio
+            .commands()
+            .iter()
+            .filter_map(|command| match *command {
+                Command::SetPSGEnvelopeVolume(c, volume) if c == chan => Some(volume),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn wave_samples(gba: &Gba) -> Vec<i16> {
+        gba.audio
+            .commands()
+            .iter()
+            .filter_map(|command| match *command {
+                Command::PlaySampleWave(sample) => Some(sample >> 4),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn channel_mask_solo_overrides_mute() {
+        let mut mask = ChannelMask::default();
+        assert!(AudioChannel::ALL.iter().all(|&c| mask.audible(c)));
+
+        mask.set_muted(AudioChannel::Sound4, true);
+        assert!(!mask.audible(AudioChannel::Sound4));
+        assert!(mask.audible(AudioChannel::FifoA));
+
+        mask.set_soloed(AudioChannel::FifoA, true);
+        mask.set_soloed(AudioChannel::Sound4, true);
+        assert!(mask.audible(AudioChannel::FifoA));
+        assert!(mask.audible(AudioChannel::Sound4));
+        assert!(!mask.audible(AudioChannel::Sound1));
+
+        let (mut fifo_a, mut fifo_b, mut psg) = (10, 20, [1, 2, 3, 4]);
+        mask.apply(&mut fifo_a, &mut fifo_b, &mut psg);
+        assert_eq!((fifo_a, fifo_b, psg), (10, 0, [0, 0, 0, 4]));
+    }
+
+    #[test]
+    fn sweep_overflow_disables_sound1() {
+        let mut gba = setup();
+        write(&mut gba, SOUND1CNT_L, 0x0011); // increase, 1 shift, 1 step
+        write(&mut gba, SOUND1CNT_H, 0xF080);
+        write(&mut gba, SOUND1CNT_X, 0x8000 | 1200);
+        assert!(sound_on(&gba, PSGChannel::Sound1));
+
+        run(&mut gba, SWEEP_STEP - 16);
+        assert_eq!(square_frequencies(&gba), [1200]);
+        assert!(sound_on(&gba, PSGChannel::Sound1));
+
+        // 1200 + 600 = 1800 is written back but 1800 + 900 would overflow.
+        run(&mut gba, 32);
+        assert_eq!(square_frequencies(&gba), [1200, 1800]);
+        assert!(!sound_on(&gba, PSGChannel::Sound1));
+        assert_eq!(gba.mem.ioregs.sound1cnt_x.freq_setting(), 1800);
+    }
+
+    #[test]
+    fn sweep_overflow_check_on_restart() {
+        let mut gba = setup();
+        write(&mut gba, SOUND1CNT_L, 0x0071); // increase, 1 shift, 7 steps
+        write(&mut gba, SOUND1CNT_H, 0xF080);
+        write(&mut gba, SOUND1CNT_X, 0x8000 | 1400);
+        assert!(!sound_on(&gba, PSGChannel::Sound1));
+    }
+
+    #[test]
+    fn sweep_decrease() {
+        let mut gba = setup();
+        write(&mut gba, SOUND1CNT_L, 0x001A); // decrease, 2 shifts, 1 step
+        write(&mut gba, SOUND1CNT_H, 0xF080);
+        write(&mut gba, SOUND1CNT_X, 0x8000 | 1024);
+        run(&mut gba, SWEEP_STEP * 2 + 16);
+        assert_eq!(square_frequencies(&gba), [1024, 768, 576]);
+        assert!(sound_on(&gba, PSGChannel::Sound1));
+
+        // Sweeps stop when the sweep time is set to 0.
+        write(&mut gba, SOUND1CNT_L, 0x000A);
+        run(&mut gba, SWEEP_STEP * 2);
+        assert_eq!(square_frequencies(&gba), [1024, 768, 576]);
+
+        // ...and start again without restarting the sound.
+        write(&mut gba, SOUND1CNT_L, 0x001A);
+        run(&mut gba, SWEEP_STEP + 16);
+        assert_eq!(square_frequencies(&gba), [1024, 768, 576, 432]);
+    }
+
+    #[test]
+    fn envelope_limits() {
+        let mut gba = setup();
+
+        // Increasing from the maximum volume does nothing.
+        write(&mut gba, SOUND2CNT_L, 0xF900);
+        write(&mut gba, SOUND2CNT_H, 0x8000);
+        run(&mut gba, ENVELOPE_STEP * 3);
+        assert_eq!(envelope_volumes(&gba, PSGChannel::Sound2), [15]);
+
+        // Decreasing stops at 0.
+        write(&mut gba, SOUND2CNT_L, 0x2100);
+        write(&mut gba, SOUND2CNT_H, 0x8000);
+        run(&mut gba, ENVELOPE_STEP * 4);
+        assert_eq!(envelope_volumes(&gba, PSGChannel::Sound2), [15, 2, 1, 0]);
+
+        // Increasing from 0 is allowed.
+        write(&mut gba, SOUND2CNT_L, 0x0900);
+        write(&mut gba, SOUND2CNT_H, 0x8000);
+        run(&mut gba, ENVELOPE_STEP + 16);
+        assert_eq!(
+            envelope_volumes(&gba, PSGChannel::Sound2),
+            [15, 2, 1, 0, 0, 1]
+        );
+    }
+
+    #[test]
+    fn length_counter() {
+        let mut gba = setup();
+        write(&mut gba, SOUND2CNT_L, 0xF03F); // length = 64 - 63 = 1 step
+        write(&mut gba, SOUND2CNT_H, 0xC000);
+        run(&mut gba, LENGTH_STEP - 16);
+        assert!(sound_on(&gba, PSGChannel::Sound2));
+        run(&mut gba, 32);
+        assert!(!sound_on(&gba, PSGChannel::Sound2));
+
+        // The length flag can be enabled after the sound has been started.
+        write(&mut gba, SOUND2CNT_H, 0x8000);
+        run(&mut gba, LENGTH_STEP * 2);
+        assert!(sound_on(&gba, PSGChannel::Sound2));
+        write(&mut gba, SOUND2CNT_H, 0x4000);
+        run(&mut gba, LENGTH_STEP + 16);
+        assert!(!sound_on(&gba, PSGChannel::Sound2));
+    }
+
+    #[test]
+    fn wave_volume() {
+        let mut gba = setup();
+        for (value, volume) in [
+            (0x8000, 75),
+            (0x2000, 100),
+            (0x4000, 50),
+            (0x6000, 25),
+            (0, 0),
+        ] {
+            write(&mut gba, SOUND3CNT_H, value);
+            assert!(matches!(
+                gba.audio.commands().last(),
+                Some(&Command::SetWaveVolume(v)) if v == volume
+            ));
+        }
+    }
+
+    /// Fills bank 0 with 0..=15 twice and bank 1 with 8s.
+    fn fill_waveram(gba: &mut Gba) {
+        // The CPU accesses the bank that is not selected for playback.
+        write(gba, SOUND3CNT_L, 0x0040);
+        for (idx, address) in (WAVE_RAM0_L..=WAVE_RAM3_H).step_by(2).enumerate() {
+            let lo = (idx as u16 % 4) * 4;
+            let value = (lo << 4) | (lo + 1) | ((lo + 2) << 12) | ((lo + 3) << 8);
+            write(gba, address, value);
+        }
+        write(gba, SOUND3CNT_L, 0x0000);
+        for address in (WAVE_RAM0_L..=WAVE_RAM3_H).step_by(2) {
+            write(gba, address, 0x8888);
+        }
+    }
+
+    const WAVE_SAMPLE_CYCLES: u64 = 8 * (2048 - 1984);
+
+    #[test]
+    fn wave_single_bank() {
+        let mut gba = setup();
+        fill_waveram(&mut gba);
+        write(&mut gba, SOUND3CNT_L, 0x0080);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 1984);
+        run(&mut gba, WAVE_SAMPLE_CYCLES * 40 - 16);
+        let expected: Vec<i16> = (0..40).map(|idx| idx % 16).collect();
+        assert_eq!(wave_samples(&gba), expected);
+
+        // Playing the other bank:
+        write(&mut gba, SOUND3CNT_L, 0x00C0);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 1984);
+        run(&mut gba, WAVE_SAMPLE_CYCLES * 8 - 16);
+        assert_eq!(wave_samples(&gba)[40..], [8; 8]);
+    }
+
+    #[test]
+    fn wave_dual_bank() {
+        let mut gba = setup();
+        fill_waveram(&mut gba);
+        write(&mut gba, SOUND3CNT_L, 0x00A0);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 1984);
+        run(&mut gba, WAVE_SAMPLE_CYCLES * 70 - 16);
+        let expected: Vec<i16> = (0..70)
+            .map(|idx| if idx % 64 < 32 { idx % 16 } else { 8 })
+            .collect();
+        assert_eq!(wave_samples(&gba), expected);
+
+        // Starting with bank 1 plays bank 1 and then bank 0.
+        write(&mut gba, SOUND3CNT_L, 0x00E0);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 1984);
+        run(&mut gba, WAVE_SAMPLE_CYCLES * 40 - 16);
+        let expected: Vec<i16> = (0..40)
+            .map(|idx| if idx < 32 { 8 } else { idx % 16 })
+            .collect();
+        assert_eq!(wave_samples(&gba)[70..], expected);
+    }
+
+    #[test]
+    fn wave_sample_exact() {
+        // 64 cycles per sample, which is above the default 32768Hz limit.
+        const CYCLES: u64 = 8 * (2048 - 2040);
+
+        let mut gba = setup();
+        fill_waveram(&mut gba);
+        write(&mut gba, SOUND3CNT_L, 0x0080);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 2040);
+        run(&mut gba, CYCLES * 40 - 16);
+        assert_eq!(wave_samples(&gba).len(), 5);
+
+        let mut gba = setup();
+        gba.set_sample_exact_audio(true);
+        fill_waveram(&mut gba);
+        write(&mut gba, SOUND3CNT_L, 0x0080);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 2040);
+        run(&mut gba, CYCLES * 40 - 16);
+        let expected: Vec<i16> = (0..40).map(|idx| idx % 16).collect();
+        assert_eq!(wave_samples(&gba), expected);
+    }
+
+    #[test]
+    fn wave_restart_from_first_sample() {
+        let mut gba = setup();
+        fill_waveram(&mut gba);
+        write(&mut gba, SOUND3CNT_L, 0x0080);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 1984);
+        run(&mut gba, WAVE_SAMPLE_CYCLES * 5 - 16);
+        write(&mut gba, SOUND3CNT_X, 0x8000 | 1984);
+        run(&mut gba, WAVE_SAMPLE_CYCLES * 3 - 16);
+        assert_eq!(wave_samples(&gba), [0, 1, 2, 3, 4, 0, 1, 2]);
+    }
+
+    /// Runs the noise channel with one LFSR shift per output sample and returns the output.
+    fn noise_output(width_7bit: bool) -> Vec<u16> {
+        let mut gba = setup();
+        write(&mut gba, SOUNDCNT_L, 0x8877); // Sound 4 left/right at full volume
+        write(&mut gba, SOUNDCNT_H, 0x0002);
+        write(&mut gba, SOUNDBIAS, 0xC200); // 262.144KHz, 64 cycles per sample
+        write(&mut gba, SOUND4CNT_L, 0xF000);
+        let width = if width_7bit { 0x0008 } else { 0x0000 };
+        write(&mut gba, SOUND4CNT_H, 0x8001 | width); // r = 1, s = 0: 64 cycles per shift
+        run(&mut gba, 64 * 1024);
+        gba.audio.wait(gba.mem.ioregs.time);
+
+        let mut synth = NativeSynth::new();
+        gba.audio
+            .commands()
+            .iter()
+            .for_each(|&command| synth.command(command));
+        synth.frames().map(|frame| frame.left).collect()
+    }
+
+    #[test]
+    fn noise_lfsr_widths() {
+        let output = noise_output(true);
+        assert!(output.len() > 512);
+        let output = &output[64..];
+        assert!(output.iter().any(|&s| s != output[0]));
+        assert!((0..256).all(|idx| output[idx] == output[idx + 127]));
+
+        let output = noise_output(false);
+        let output = &output[64..];
+        assert!((0..256).any(|idx| output[idx] != output[idx + 127]));
+    }
 }
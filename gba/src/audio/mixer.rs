@@ -0,0 +1,105 @@
+use crate::memory::io::{ChannelLRVolumeEnable, DMASoundControlMixing, FifoChannel, PSGChannel};
+
+/// The final stage of the GBA's audio output which combines the FIFOs and PSGs into a single
+/// 10bit stereo output level.
+pub(crate) struct Mixer {
+    /// SOUNDCNT_L: PSG master volume and left/right enables.
+    psg_mixing: ChannelLRVolumeEnable,
+    /// SOUNDCNT_H: PSG/FIFO volumes and FIFO left/right enables.
+    dma_mixing: DMASoundControlMixing,
+    bias: i16,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer {
+            psg_mixing: ChannelLRVolumeEnable::default(),
+            dma_mixing: DMASoundControlMixing::default(),
+            bias: 0x100,
+        }
+    }
+}
+
+impl Mixer {
+    pub(crate) fn set_psg_mixing(&mut self, mixing: ChannelLRVolumeEnable) {
+        self.psg_mixing = mixing;
+    }
+
+    pub(crate) fn set_dma_mixing(&mut self, mixing: DMASoundControlMixing) {
+        self.dma_mixing = mixing;
+    }
+
+    pub(crate) fn set_bias(&mut self, bias: u16) {
+        self.bias = bias as i16;
+    }
+
+    /// Mixes the current FIFO samples and PSG outputs into the unsigned 10bit left and right
+    /// output levels.
+    pub(crate) fn mix(&self, fifo_a: i8, fifo_b: i8, psg: [i16; 4]) -> (u16, u16) {
+        // Each of the two FIFOs can span the FULL output range (+/-200h) at 100% volume.
+        let (fifo_a_l, fifo_a_r) = self.mix_fifo(FifoChannel::A, fifo_a);
+        let (fifo_b_l, fifo_b_r) = self.mix_fifo(FifoChannel::B, fifo_b);
+
+        // Each of the four PSGs can span one QUARTER of the output range (+/-80h).
+        let (psg_l, psg_r) = self.mix_psg(psg);
+
+        // The current output levels of all six channels are added together by hardware.
+        // So together, the FIFOs and PSGs, could reach THRICE the range (+/-600h).
+        //
+        // The BIAS value is added to that signed value. With default BIAS (200h),
+        // the possible range becomes -400h..+800h.
+        //
+        // Values that exceed the unsigned 10bit output range of 0..3FFh are clipped to MinMax(0,3FFh).
+        let out_l = (fifo_a_l + fifo_b_l + psg_l + self.bias).clamp(0, 0x3FF);
+        let out_r = (fifo_a_r + fifo_b_r + psg_r + self.bias).clamp(0, 0x3FF);
+        (out_l as u16, out_r as u16)
+    }
+
+    /// Applies the FIFO volume (50% or 100%) and left/right enables from SOUNDCNT_H.
+    fn mix_fifo(&self, channel: FifoChannel, sample: i8) -> (i16, i16) {
+        let mut output = (sample as i16) << 2;
+        if self.dma_mixing.dma_volume(channel) == 0 {
+            output >>= 1;
+        }
+
+        let l = if self.dma_mixing.dma_enable_left(channel) {
+            output
+        } else {
+            0
+        };
+        let r = if self.dma_mixing.dma_enable_right(channel) {
+            output
+        } else {
+            0
+        };
+        (l, r)
+    }
+
+    /// Routes each PSG to the left and right outputs using the enable flags in SOUNDCNT_L, then
+    /// applies the left/right master volumes (SOUNDCNT_L) and the PSG volume (SOUNDCNT_H).
+    fn mix_psg(&self, psg: [i16; 4]) -> (i16, i16) {
+        let mut psg_l = 0;
+        let mut psg_r = 0;
+        for (idx, &output) in psg.iter().enumerate() {
+            let channel = PSGChannel::from(idx as u16);
+            if self.psg_mixing.enable_left(channel) {
+                psg_l += output;
+            }
+            if self.psg_mixing.enable_right(channel) {
+                psg_r += output;
+            }
+        }
+
+        // Master volumes are 0-7 where 7 is full volume.
+        psg_l = psg_l * (self.psg_mixing.master_volme_left() as i16 + 1) / 8;
+        psg_r = psg_r * (self.psg_mixing.master_volume_right() as i16 + 1) / 8;
+
+        // 0=25%, 1=50%, 2=100%, 3=Prohibited (treated as 100%)
+        let shift = match self.dma_mixing.analogue_volume() {
+            0 => 2,
+            1 => 1,
+            _ => 0,
+        };
+        (psg_l >> shift, psg_r >> shift)
+    }
+}
@@ -1,6 +1,6 @@
 use crate::memory::io::PSGChannel;
 
-use super::Command;
+use super::{mixer::Mixer, AudioChannel, ChannelMask, Command};
 
 pub struct GbaAudioSampler {
     native_frequency: u32,
@@ -10,12 +10,16 @@ pub struct GbaAudioSampler {
     fifo_a: i8,
     fifo_b: i8,
 
-    // PSG outputs BEFORE volumes for left and right channels are
-    // applied. This DOES take into the account the master volume
-    // that is set in the DMA Sound Control/Mixing register.
-    bias: i16,
+    mixer: Mixer,
     wait_frames: f64,
 
+    /// Channels muted or soloed through the sampler.
+    channel_mask: ChannelMask,
+    /// Channels muted or soloed in the emulator, received through [`Command::SetChannelMask`].
+    command_channel_mask: ChannelMask,
+    /// The output of each channel mixed on its own during the last frame, if enabled.
+    channel_outputs: Option<[(f32, f32); 6]>,
+
     sound1: SquareWave,
     sound2: SquareWave,
     sound3: WaveSample,
@@ -29,9 +33,13 @@ impl GbaAudioSampler {
             native_frequency_f: native_frequency as f64,
             fifo_a: 0,
             fifo_b: 0,
-            bias: 0x100,
+            mixer: Mixer::default(),
             wait_frames: 0.0,
 
+            channel_mask: ChannelMask::default(),
+            command_channel_mask: ChannelMask::default(),
+            channel_outputs: None,
+
             sound1: SquareWave::default(),
             sound2: SquareWave::default(),
             sound3: WaveSample::default(),
@@ -39,46 +47,62 @@ impl GbaAudioSampler {
         }
     }
 
-    fn generate_output_frame(&mut self) -> (f32, f32) {
-        const GBA_RANGE_RECIP: f32 = 1.0 / 1024.0;
+    pub fn channel_mask(&self) -> ChannelMask {
+        self.channel_mask
+    }
 
-        // Each of the two FIFOs can span the FULL output range (+/-200h).
-        let fifo_a = (self.fifo_a as i16) << 2;
-        let fifo_b = (self.fifo_b as i16) << 2;
+    /// Mutes or solos channels in this sampler's output. This is applied on top of any mask
+    /// that was set in the emulator, so a channel is only heard if both allow it.
+    pub fn set_channel_mask(&mut self, mask: ChannelMask) {
+        self.channel_mask = mask;
+    }
 
-        // FIXME implement volume for FIFO
-        let fifo_a_l = fifo_a;
-        let fifo_a_r = fifo_a;
-        let fifo_b_l = fifo_b;
-        let fifo_b_r = fifo_b;
+    /// Enables rendering each channel on its own, which can then be retrieved after each frame
+    /// with [`GbaAudioSampler::channel_frame`].
+    pub fn set_channel_outputs_enabled(&mut self, enabled: bool) {
+        if enabled != self.channel_outputs.is_some() {
+            self.channel_outputs = enabled.then_some([(0.0, 0.0); 6]);
+        }
+    }
+
+    /// The output of a single channel during the last frame, as if it were the only channel
+    /// being played. Muting and soloing do not affect this. Returns silence if channel outputs
+    /// are not enabled.
+    pub fn channel_frame(&self, channel: AudioChannel) -> (f32, f32) {
+        self.channel_outputs
+            .map(|outputs| outputs[u8::from(channel) as usize])
+            .unwrap_or((0.0, 0.0))
+    }
 
-        // Each of the four PSGs can span one QUARTER of the output range (+/-80h).
-        // FIXME implement PSG output
-        let psg = [
+    fn generate_output_frame(&mut self) -> (f32, f32) {
+        const GBA_RANGE_RECIP: f32 = 1.0 / 1024.0;
+
+        let mut psg = [
             self.sound1.frame(),
             self.sound2.frame(),
             self.sound3.frame(),
             self.sound4.frame(),
         ];
-        let mut psg_l = [0i16; 4];
-        let mut psg_r = [0i16; 4];
-
-        // FIXME temporary, eventually this will apply volume to each element first.
-        psg_l[..4].clone_from_slice(&psg[..4]);
-        psg_r[..4].clone_from_slice(&psg[..4]);
-
-        let psg_l: i16 = psg_l.iter().sum();
-        let psg_r: i16 = psg_r.iter().sum();
-
-        // The current output levels of all six channels are added together by hardware.
-        // So together, the FIFOs and PSGs, could reach THRICE the range (+/-600h).
-        //
-        // The BIAS value is added to that signed value. With default BIAS (200h),
-        // the possible range becomes -400h..+800h.
-        //
-        // Values that exceed the unsigned 10bit output range of 0..3FFh are clipped to MinMax(0,3FFh).
-        let gba_out_l = (fifo_a_l + fifo_b_l + psg_l + self.bias).clamp(0, 0x3FF);
-        let gba_out_r = (fifo_a_r + fifo_b_r + psg_r + self.bias).clamp(0, 0x3FF);
+        let (mut fifo_a, mut fifo_b) = (self.fifo_a, self.fifo_b);
+
+        if let Some(ref mut outputs) = self.channel_outputs {
+            for (idx, output) in outputs.iter_mut().enumerate() {
+                let mut channel_psg = [0; 4];
+                let (mut channel_fifo_a, mut channel_fifo_b) = (0, 0);
+                match AudioChannel::from(idx as u8) {
+                    AudioChannel::FifoA => channel_fifo_a = fifo_a,
+                    AudioChannel::FifoB => channel_fifo_b = fifo_b,
+                    _ => channel_psg[idx] = psg[idx],
+                }
+                let (l, r) = self.mixer.mix(channel_fifo_a, channel_fifo_b, channel_psg);
+                *output = (l as f32 * GBA_RANGE_RECIP, r as f32 * GBA_RANGE_RECIP);
+            }
+        }
+
+        self.command_channel_mask
+            .apply(&mut fifo_a, &mut fifo_b, &mut psg);
+        self.channel_mask.apply(&mut fifo_a, &mut fifo_b, &mut psg);
+        let (gba_out_l, gba_out_r) = self.mixer.mix(fifo_a, fifo_b, psg);
 
         let out_l = gba_out_l as f32 * GBA_RANGE_RECIP;
         let out_r = gba_out_r as f32 * GBA_RANGE_RECIP;
@@ -104,7 +128,7 @@ impl GbaAudioSampler {
             Command::PlaySampleFifoA(sample) => self.fifo_a = sample,
             Command::PlaySampleFifoB(sample) => self.fifo_b = sample,
             Command::PlaySampleWave(sample) => self.sound3.sample = sample,
-            Command::SetBias(bias) => self.bias = bias as i16,
+            Command::SetBias(bias) => self.mixer.set_bias(bias),
             Command::SetResolution(resolution) => {
                 // FIXME: Frequency is currently ignored. Should it continue to be this way?
                 let frequency = resolution.frequency();
@@ -148,9 +172,15 @@ impl GbaAudioSampler {
             Command::SetPSGEnvelopeVolume(chan, volume) => match chan {
                 PSGChannel::Sound1 => self.sound1.set_volume(volume as i16),
                 PSGChannel::Sound2 => self.sound2.set_volume(volume as i16),
-                PSGChannel::Sound3 => log::debug!("SetPSGVolume(3)"),
+                // Sound 3 has no envelope, its volume is set with SetWaveVolume.
+                PSGChannel::Sound3 => {}
                 PSGChannel::Sound4 => self.sound4.set_volume(volume as i16),
             },
+
+            Command::SetPSGMixing(mixing) => self.mixer.set_psg_mixing(mixing),
+            Command::SetDMASoundMixing(mixing) => self.mixer.set_dma_mixing(mixing),
+            Command::SetWaveVolume(volume) => self.sound3.volume = volume as i16,
+            Command::SetChannelMask(mask) => self.command_channel_mask = mask,
         }
     }
 
@@ -211,12 +241,14 @@ impl SquareWave {
 pub struct WaveSample {
     enabled: bool,
     sample: i16,
+    /// Output volume as a percentage.
+    volume: i16,
 }
 
 impl WaveSample {
     fn frame(&self) -> i16 {
         if self.enabled {
-            self.sample
+            self.sample * self.volume / 100
         } else {
             0
         }
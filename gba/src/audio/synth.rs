@@ -0,0 +1,561 @@
+//! Sample-exact audio synthesis.
+//!
+//! Unlike [`GbaAudioSampler`](super::sampler::GbaAudioSampler), which approximates each channel
+//! at the host's sample rate, [`NativeSynth`] steps every channel using integer cycle counters and
+//! samples the mixer at the rate selected by the `Resolution` bits of SOUNDBIAS, quantizing the
+//! output to the matching bit depth the same way hardware does. [`GbaAudioSynth`] then converts
+//! that output to the host's sample rate using a band-limited resampler.
+
+use std::collections::VecDeque;
+
+use crate::memory::io::{PSGChannel, Resolution};
+
+use super::{mixer::Mixer, ChannelMask, Command};
+
+/// The highest sample rate that the GBA can output at (the 6bit/262.144kHz resolution).
+const MAX_NATIVE_FREQUENCY: u32 = 256 * 1024;
+
+/// A single output sample at the GBA's native sample rate. Both levels are unsigned 10bit values
+/// with the bits below the current resolution's bit depth cleared.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct NativeFrame {
+    pub left: u16,
+    pub right: u16,
+}
+
+/// Synthesizes audio at the GBA's native sample rate from the commands generated by
+/// [`GbaAudio`](super::GbaAudio).
+///
+/// Audio regression tests should enable
+/// [`Gba::set_sample_exact_audio`](crate::Gba::set_sample_exact_audio) before running, pass the
+/// commands from [`Gba::audio`](crate::Gba::audio) to [`NativeSynth::command`] after every
+/// frame, and compare the frames returned by [`NativeSynth::frames`] against a recording of the
+/// GBA's output. The frames aren't resampled, so they can be compared exactly.
+pub struct NativeSynth {
+    resolution: Resolution,
+    cycles_until_sample: u32,
+    mixer: Mixer,
+    channel_mask: ChannelMask,
+
+    fifo_a: i8,
+    fifo_b: i8,
+    sound1: SquareWave,
+    sound2: SquareWave,
+    sound3: WaveSample,
+    sound4: Noise,
+
+    output: VecDeque<NativeFrame>,
+}
+
+impl Default for NativeSynth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NativeSynth {
+    pub fn new() -> Self {
+        let resolution = Resolution::Res9bit32khz;
+        NativeSynth {
+            resolution,
+            cycles_until_sample: Self::cycles_per_sample(resolution),
+            mixer: Mixer::default(),
+            channel_mask: ChannelMask::default(),
+
+            fifo_a: 0,
+            fifo_b: 0,
+            sound1: SquareWave::default(),
+            sound2: SquareWave::default(),
+            sound3: WaveSample::default(),
+            sound4: Noise::default(),
+
+            output: VecDeque::new(),
+        }
+    }
+
+    /// The rate at which frames are currently being output.
+    pub fn sample_rate(&self) -> u32 {
+        self.resolution.frequency()
+    }
+
+    /// Removes and returns all of the frames that have been generated so far.
+    pub fn frames(&mut self) -> std::collections::vec_deque::Drain<'_, NativeFrame> {
+        self.output.drain(..)
+    }
+
+    pub fn command(&mut self, command: Command) {
+        match command {
+            Command::Wait(cycles) => self.run(cycles),
+            Command::PlaySampleFifoA(sample) => self.fifo_a = sample,
+            Command::PlaySampleFifoB(sample) => self.fifo_b = sample,
+            Command::PlaySampleWave(sample) => self.sound3.sample = sample,
+            Command::SetBias(bias) => self.mixer.set_bias(bias),
+            Command::SetResolution(resolution) => {
+                self.resolution = resolution;
+                self.cycles_until_sample = self
+                    .cycles_until_sample
+                    .min(Self::cycles_per_sample(resolution));
+            }
+
+            Command::SetPSGEnabled(chan, enabled) => match chan {
+                PSGChannel::Sound1 => self.sound1.set_enabled(enabled),
+                PSGChannel::Sound2 => self.sound2.set_enabled(enabled),
+                PSGChannel::Sound3 => self.sound3.enabled = enabled,
+                PSGChannel::Sound4 => self.sound4.set_enabled(enabled),
+            },
+
+            Command::SetSquareFrequencyRate(chan, rate) => match chan {
+                PSGChannel::Sound1 => self.sound1.set_frequency_rate(rate),
+                PSGChannel::Sound2 => self.sound2.set_frequency_rate(rate),
+                // Only the square channels have these, anything else is ignored.
+                PSGChannel::Sound3 | PSGChannel::Sound4 => {}
+            },
+            Command::SetSquareDuty(chan, duty) => match chan {
+                PSGChannel::Sound1 => self.sound1.set_duty(duty),
+                PSGChannel::Sound2 => self.sound2.set_duty(duty),
+                PSGChannel::Sound3 | PSGChannel::Sound4 => {}
+            },
+
+            Command::SetNoiseCounterWidth(width) => self.sound4.width = width,
+            Command::SetNoiseFrequencyParams { r, s } => {
+                self.sound4.set_frequency_params(r as u32, s as u32)
+            }
+
+            Command::SetPSGEnvelopeVolume(chan, volume) => match chan {
+                PSGChannel::Sound1 => self.sound1.volume = volume as i16,
+                PSGChannel::Sound2 => self.sound2.volume = volume as i16,
+                // Sound 3 has no envelope, its volume is set with SetWaveVolume.
+                PSGChannel::Sound3 => {}
+                PSGChannel::Sound4 => self.sound4.volume = volume as i16,
+            },
+
+            Command::SetPSGMixing(mixing) => self.mixer.set_psg_mixing(mixing),
+            Command::SetDMASoundMixing(mixing) => self.mixer.set_dma_mixing(mixing),
+            Command::SetWaveVolume(volume) => self.sound3.volume = volume as i16,
+            Command::SetChannelMask(mask) => self.channel_mask = mask,
+        }
+    }
+
+    fn run(&mut self, mut cycles: u32) {
+        while cycles >= self.cycles_until_sample {
+            cycles -= self.cycles_until_sample;
+            self.step_channels(self.cycles_until_sample);
+            self.sample();
+            self.cycles_until_sample = Self::cycles_per_sample(self.resolution);
+        }
+        self.step_channels(cycles);
+        self.cycles_until_sample -= cycles;
+    }
+
+    fn step_channels(&mut self, cycles: u32) {
+        self.sound1.step(cycles);
+        self.sound2.step(cycles);
+        self.sound4.step(cycles);
+    }
+
+    fn sample(&mut self) {
+        let mut psg = [
+            self.sound1.output(),
+            self.sound2.output(),
+            self.sound3.output(),
+            self.sound4.output(),
+        ];
+        let (mut fifo_a, mut fifo_b) = (self.fifo_a, self.fifo_b);
+        self.channel_mask.apply(&mut fifo_a, &mut fifo_b, &mut psg);
+        let (left, right) = self.mixer.mix(fifo_a, fifo_b, psg);
+
+        // The lower bits of the 10bit output are dropped depending on the resolution.
+        let mask = !((1u16 << (10 - self.resolution.bit_depth())) - 1);
+        self.output.push_back(NativeFrame {
+            left: left & mask,
+            right: right & mask,
+        });
+    }
+
+    fn cycles_per_sample(resolution: Resolution) -> u32 {
+        crate::Gba::CYCLES_PER_SECOND / resolution.frequency()
+    }
+}
+
+/// Generates audio at the host's sample rate by running a [`NativeSynth`] and resampling its
+/// output with a band-limited filter. This can be used in place of a
+/// [`GbaAudioSampler`](super::sampler::GbaAudioSampler), along with
+/// [`Gba::set_sample_exact_audio`](crate::Gba::set_sample_exact_audio) so that Sound 3 isn't
+/// limited to 32768Hz.
+pub struct GbaAudioSynth {
+    synth: NativeSynth,
+    resampler: Resampler,
+}
+
+impl GbaAudioSynth {
+    pub fn new(host_frequency: u32) -> Self {
+        GbaAudioSynth {
+            synth: NativeSynth::new(),
+            resampler: Resampler::new(MAX_NATIVE_FREQUENCY, host_frequency),
+        }
+    }
+
+    pub fn command(&mut self, command: Command) {
+        const GBA_RANGE_RECIP: f32 = 1.0 / 1024.0;
+
+        self.synth.command(command);
+
+        // Every sample is held until the next one, so repeating each at the highest native rate
+        // allows the resampler to work with a single input rate even if the resolution changes.
+        let hold = MAX_NATIVE_FREQUENCY / self.synth.sample_rate();
+        for frame in self.synth.frames() {
+            let sample = [
+                frame.left as f32 * GBA_RANGE_RECIP,
+                frame.right as f32 * GBA_RANGE_RECIP,
+            ];
+            for _ in 0..hold {
+                self.resampler.push(sample);
+            }
+        }
+    }
+
+    pub fn needs_commands(&self) -> bool {
+        !self.resampler.has_output()
+    }
+
+    pub fn frame(&mut self) -> (f32, f32) {
+        let [l, r] = self.resampler.next();
+        (l, r)
+    }
+}
+
+#[derive(Default)]
+struct SquareWave {
+    enabled: bool,
+    /// Number of cycles per duty step.
+    period: u32,
+    timer: u32,
+    step: u8,
+    /// Number of the 8 duty steps that are high.
+    duty_steps: u8,
+    volume: i16,
+}
+
+impl SquareWave {
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if enabled {
+            self.timer = self.period;
+        }
+    }
+
+    fn set_frequency_rate(&mut self, rate: u16) {
+        debug_assert!(rate < 2048);
+        // Frequency = 131072/(2048-n) Hz with 8 duty steps per period.
+        self.period = 16 * (2048 - rate as u32);
+        if self.timer == 0 || self.timer > self.period {
+            self.timer = self.period;
+        }
+    }
+
+    fn set_duty(&mut self, duty: u16) {
+        self.duty_steps = match duty {
+            0 => 1,
+            1 => 2,
+            2 => 4,
+            3 => 6,
+            _ => unreachable!("invalid wave duty"),
+        };
+    }
+
+    fn step(&mut self, cycles: u32) {
+        if self.period == 0 {
+            return;
+        }
+
+        if cycles < self.timer {
+            self.timer -= cycles;
+        } else {
+            let cycles = cycles - self.timer;
+            let steps = 1 + cycles / self.period;
+            self.step = ((self.step as u32 + steps) % 8) as u8;
+            self.timer = self.period - cycles % self.period;
+        }
+    }
+
+    fn output(&self) -> i16 {
+        if self.enabled && self.step < self.duty_steps {
+            (0x80 * self.volume) / 15
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Default)]
+struct WaveSample {
+    enabled: bool,
+    sample: i16,
+    /// Output volume as a percentage.
+    volume: i16,
+}
+
+impl WaveSample {
+    fn output(&self) -> i16 {
+        if self.enabled {
+            self.sample * self.volume / 100
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Default)]
+struct Noise {
+    enabled: bool,
+    lfsr: u16,
+    /// 0 = 15 bits, 1 = 7 bits
+    width: u16,
+    /// Number of cycles per shift of the LFSR.
+    period: u32,
+    timer: u32,
+    volume: i16,
+}
+
+impl Noise {
+    fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if enabled {
+            // The initial value when (re-)starting the sound is X=40h (7bit) or X=4000h (15bit).
+            self.lfsr = if self.width == 0 { 0x4000 } else { 0x40 };
+            self.timer = self.period;
+        }
+    }
+
+    fn set_frequency_params(&mut self, r: u32, s: u32) {
+        // Frequency = 524288 Hz / r / 2^(s+1) ;For r=0 assume r=0.5 instead
+        self.period = if r != 0 {
+            (32 * r) << (s + 1)
+        } else {
+            16 << (s + 1)
+        };
+        if self.timer == 0 || self.timer > self.period {
+            self.timer = self.period;
+        }
+    }
+
+    fn step(&mut self, mut cycles: u32) {
+        if self.period == 0 || !self.enabled {
+            return;
+        }
+
+        while cycles >= self.timer {
+            cycles -= self.timer;
+            self.timer = self.period;
+            self.shift();
+        }
+        self.timer -= cycles;
+    }
+
+    fn shift(&mut self) {
+        //   7bit:  X=X SHR 1, IF carry THEN Out=HIGH, X=X XOR 60h ELSE Out=LOW
+        //   15bit: X=X SHR 1, IF carry THEN Out=HIGH, X=X XOR 6000h ELSE Out=LOW
+        let carry = self.lfsr & 1 != 0;
+        self.lfsr >>= 1;
+        if carry {
+            self.lfsr ^= if self.width == 0 { 0x6000 } else { 0x60 };
+        }
+    }
+
+    fn output(&self) -> i16 {
+        if self.enabled && self.lfsr & 1 != 0 {
+            (0x80 * self.volume) / 15
+        } else {
+            0
+        }
+    }
+}
+
+/// A polyphase windowed-sinc resampler.
+struct Resampler {
+    input: VecDeque<[f32; 2]>,
+    /// Position of the next output sample relative to the start of `input`.
+    position: f64,
+    /// Number of input samples per output sample.
+    ratio: f64,
+    /// Filter coefficients for each fractional position between two input samples.
+    phases: Vec<Vec<f32>>,
+    half_taps: usize,
+    last_output: [f32; 2],
+}
+
+impl Resampler {
+    /// The number of zero crossings of the sinc function on each side of the filter.
+    const ZERO_CROSSINGS: f64 = 8.0;
+    const PHASES: usize = 64;
+
+    fn new(input_frequency: u32, output_frequency: u32) -> Self {
+        let ratio = input_frequency as f64 / output_frequency as f64;
+
+        // Cut off a little below the lower of the two nyquist frequencies to leave room for the
+        // transition band.
+        let cutoff = 0.45 / ratio.max(1.0);
+        let half_taps = (Self::ZERO_CROSSINGS / (2.0 * cutoff)).ceil() as usize;
+
+        let phases = (0..=Self::PHASES)
+            .map(|phase| {
+                let fraction = phase as f64 / Self::PHASES as f64;
+                let mut taps: Vec<f64> = (0..half_taps * 2)
+                    .map(|tap| {
+                        let t = tap as f64 - (half_taps - 1) as f64 - fraction;
+                        let window = blackman(t / half_taps as f64);
+                        2.0 * cutoff * sinc(2.0 * cutoff * t) * window
+                    })
+                    .collect();
+
+                // Normalize so that there is no change in volume.
+                let sum: f64 = taps.iter().sum();
+                taps.iter_mut().for_each(|tap| *tap /= sum);
+                taps.into_iter().map(|tap| tap as f32).collect()
+            })
+            .collect();
+
+        let mut input = VecDeque::new();
+        input.extend(std::iter::repeat_n([0.0; 2], half_taps));
+
+        Resampler {
+            input,
+            position: (half_taps - 1) as f64,
+            ratio,
+            phases,
+            half_taps,
+            last_output: [0.0; 2],
+        }
+    }
+
+    fn push(&mut self, sample: [f32; 2]) {
+        self.input.push_back(sample);
+    }
+
+    fn has_output(&self) -> bool {
+        self.input.len() > self.position as usize + self.half_taps
+    }
+
+    fn next(&mut self) -> [f32; 2] {
+        if !self.has_output() {
+            return self.last_output;
+        }
+
+        let index = self.position as usize;
+        let phase = ((self.position - index as f64) * Self::PHASES as f64).round() as usize;
+        let taps = &self.phases[phase];
+        let start = index + 1 - self.half_taps;
+
+        let mut output = [0.0; 2];
+        for (tap, sample) in taps.iter().zip(self.input.range(start..)) {
+            output[0] += tap * sample[0];
+            output[1] += tap * sample[1];
+        }
+        self.last_output = output;
+
+        self.position += self.ratio;
+        let consumed = (self.position as usize + 1).saturating_sub(self.half_taps);
+        self.input.drain(..consumed);
+        self.position -= consumed as f64;
+
+        output
+    }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x == 0.0 {
+        1.0
+    } else {
+        let x = std::f64::consts::PI * x;
+        x.sin() / x
+    }
+}
+
+/// Blackman window for `x` in the range [-1, 1].
+fn blackman(x: f64) -> f64 {
+    use std::f64::consts::PI;
+    if x.abs() > 1.0 {
+        0.0
+    } else {
+        0.42 + 0.5 * (PI * x).cos() + 0.08 * (2.0 * PI * x).cos()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::memory::io::{ChannelLRVolumeEnable, DMASoundControlMixing};
+
+    fn levels(synth: &mut NativeSynth) -> Vec<u16> {
+        synth.frames().map(|frame| frame.left).collect()
+    }
+
+    #[test]
+    fn sample_rate_follows_resolution() {
+        let mut synth = NativeSynth::new();
+        synth.command(Command::Wait(512 * 10));
+        assert_eq!(synth.frames().count(), 10);
+
+        synth.command(Command::SetResolution(Resolution::Res6bit256khz));
+        synth.command(Command::Wait(64 * 10));
+        assert_eq!(synth.frames().count(), 10);
+        assert_eq!(synth.sample_rate(), 256 * 1024);
+    }
+
+    #[test]
+    fn output_is_quantized_to_bit_depth() {
+        let mut synth = NativeSynth::new();
+        synth.command(Command::SetBias(0x20F));
+        synth.command(Command::Wait(512));
+        assert_eq!(levels(&mut synth), [0x20E]);
+
+        synth.command(Command::SetResolution(Resolution::Res6bit256khz));
+        synth.command(Command::Wait(64));
+        assert_eq!(levels(&mut synth), [0x200]);
+    }
+
+    #[test]
+    fn square_wave_is_cycle_exact() {
+        let mut synth = NativeSynth::new();
+        synth.command(Command::SetBias(0x100));
+        // Sound 1 on the left and right at full master volume and 100% PSG volume.
+        synth.command(Command::SetPSGMixing(ChannelLRVolumeEnable::new(0x1177)));
+        synth.command(Command::SetDMASoundMixing(DMASoundControlMixing::new(
+            0x0002,
+        )));
+
+        // One duty step per sample at 32kHz (512 cycles).
+        synth.command(Command::SetSquareFrequencyRate(
+            PSGChannel::Sound1,
+            2048 - 32,
+        ));
+        synth.command(Command::SetSquareDuty(PSGChannel::Sound1, 2));
+        synth.command(Command::SetPSGEnvelopeVolume(PSGChannel::Sound1, 15));
+        synth.command(Command::SetPSGEnabled(PSGChannel::Sound1, true));
+        synth.command(Command::Wait(512 * 16));
+
+        const HI: u16 = 0x180;
+        const LO: u16 = 0x100;
+        assert_eq!(
+            levels(&mut synth),
+            [HI, HI, HI, LO, LO, LO, LO, HI, HI, HI, HI, LO, LO, LO, LO, HI]
+        );
+    }
+
+    #[test]
+    fn resampler_preserves_dc() {
+        let mut synth = GbaAudioSynth::new(48000);
+        synth.command(Command::SetBias(0x200));
+        while synth.needs_commands() {
+            synth.command(Command::Wait(512 * 1024));
+        }
+
+        // Skip past the filter's startup transient.
+        for _ in 0..64 {
+            synth.frame();
+        }
+        let (l, r) = synth.frame();
+        assert!((l - 0.5).abs() < 1e-4, "left = {l}");
+        assert!((r - 0.5).abs() < 1e-4, "right = {r}");
+    }
+}
@@ -1,7 +1,10 @@
 use arm::Memory;
 
 use crate::{
-    memory::io::{AddressControl, DMARegisters, Timing, TransferType},
+    memory::{
+        io::{AddressControl, DMARegisters, Timing, TransferType},
+        REGION_EWRAM, REGION_SRAM,
+    },
     Gba,
 };
 
@@ -181,12 +184,28 @@ pub fn step<const DMA: usize, const TRANSFER_TYPE: bool>(gba: &mut Gba) -> arm::
         processing_cycles = dma.processing_cycles();
     };
 
+    // DMA can't read from the BIOS or unmapped memory. Those reads still take as long as a
+    // normal read but return the last value that was transferred instead.
+    let source_region = dma.source >> 24;
+    let open_bus = !(REGION_EWRAM..=REGION_SRAM).contains(&source_region);
+
     let waitstates = if TRANSFER_TYPE == TRANSFER_32BIT {
-        let (value, src_wait) = gba.mem.load32(dma.source, access);
+        let (mut value, src_wait) = gba.mem.load32(dma.source, access);
+        if open_bus {
+            value = gba.mem.dma_latch;
+        } else {
+            gba.mem.dma_latch = value;
+        }
         let dst_wait = gba.mem.store32(dma.destination, value, access);
         src_wait + dst_wait
     } else {
-        let (value, src_wait) = gba.mem.load16(dma.source, access);
+        let (mut value, src_wait) = gba.mem.load16(dma.source, access);
+        if open_bus {
+            let shift = (dma.destination & 0x2) * 8;
+            value = (gba.mem.dma_latch >> shift) as u16;
+        } else {
+            gba.mem.dma_latch = value as u32 | ((value as u32) << 16);
+        }
         let dst_wait = gba.mem.store16(dma.destination, value, access);
         src_wait + dst_wait
     };
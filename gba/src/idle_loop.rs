@@ -0,0 +1,167 @@
+use arm::Cpu;
+
+use crate::memory::GbaMemory;
+
+/// Loops that branch back further than this many bytes aren't checked.
+const MAX_LOOP_SIZE: u32 = 64;
+
+/// Detects loops that busy-wait on memory (e.g. polling VCOUNT or an IRQ flag set by an interrupt
+/// handler) so that the CPU can skip ahead to the next event instead of running them.
+///
+/// When a short backwards branch is taken the CPU's registers are saved. If the CPU gets back to
+/// the same address with the same registers, without any stores or reads of values that change
+/// on their own (timer counters), and without any events running in between, then it will
+/// keep going around the same loop until an event changes something.
+#[derive(Default)]
+pub struct IdleLoopDetector {
+    /// Address of the first instruction of the loop that is being checked.
+    loop_start: Option<u32>,
+    /// Registers (R0-R15 and the CPSR) the last time the CPU was at the start of the loop, or
+    /// `None` if something changed since then.
+    snapshot: Option<[u32; 17]>,
+    last_pc: u32,
+}
+
+impl IdleLoopDetector {
+    /// Called before the CPU runs. Returns true if the CPU is idle until the next event.
+    pub fn check(&mut self, cpu: &Cpu, memory: &mut GbaMemory) -> bool {
+        let pc = cpu.next_exec_pc();
+        let last_pc = std::mem::replace(&mut self.last_pc, pc);
+
+        // Side effects are collected over the entire loop since the CPU can stop anywhere in it
+        // between checks. They're only cleared when a new snapshot is taken.
+        if self.loop_start == Some(pc) {
+            let registers = Self::registers(cpu);
+            if !memory.side_effects && self.snapshot == Some(registers) {
+                return true;
+            }
+            self.snapshot = Some(registers);
+            memory.side_effects = false;
+        } else if pc <= last_pc && last_pc - pc <= MAX_LOOP_SIZE {
+            self.loop_start = Some(pc);
+            self.snapshot = Some(Self::registers(cpu));
+            memory.side_effects = false;
+        }
+
+        false
+    }
+
+    /// Called when events run since they can change what the loop is waiting on without the
+    /// CPU touching memory.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.snapshot = None;
+    }
+
+    fn registers(cpu: &Cpu) -> [u32; 17] {
+        let mut registers = [0; 17];
+        for (idx, register) in registers.iter_mut().take(16).enumerate() {
+            *register = cpu.registers.read(idx as u32);
+        }
+        registers[16] = cpu.registers.read_cpsr();
+        registers
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::Gba;
+
+    /// Builds a ROM from ARM opcodes and runs the CPU until it goes idle or `max_runs` runs
+    /// have passed. Returns true if it went idle.
+    fn goes_idle(opcodes: &[u32], max_runs: usize) -> bool {
+        goes_idle_with(opcodes, max_runs, |_| {})
+    }
+
+    /// Like [`goes_idle`] but calls `between_runs` before every check.
+    fn goes_idle_with(
+        opcodes: &[u32],
+        max_runs: usize,
+        mut between_runs: impl FnMut(&mut Gba),
+    ) -> bool {
+        let mut gba = Gba::new();
+        gba.set_bios(None);
+        gba.set_gamepak(opcodes.iter().flat_map(|op| op.to_le_bytes()).collect());
+        gba.reset(false);
+
+        (0..max_runs).any(|_| {
+            between_runs(&mut gba);
+            let idle = gba.idle_loop.check(&gba.cpu, &mut gba.mem);
+            if !idle {
+                gba.cpu.run(&mut gba.mem, arm::Cycles::new(1024));
+            }
+            idle
+        })
+    }
+
+    #[test]
+    fn polling_loop_is_idle() {
+        assert!(goes_idle(
+            &[
+                0xE3A01403, // mov  r1, #0x03000000
+                0xE5910000, // ldr  r0, [r1]
+                0xE3500000, // cmp  r0, #0
+                0x0AFFFFFC, // beq  0x08000004
+            ],
+            16,
+        ));
+    }
+
+    #[test]
+    fn loop_with_store_is_not_idle() {
+        assert!(!goes_idle(
+            &[
+                0xE3A01403, // mov  r1, #0x03000000
+                0xE5910000, // ldr  r0, [r1]
+                0xE5810004, // str  r0, [r1, #4]
+                0xE3500000, // cmp  r0, #0
+                0x0AFFFFFB, // beq  0x08000004
+            ],
+            64,
+        ));
+    }
+
+    #[test]
+    fn loop_starting_with_store_is_not_idle() {
+        assert!(!goes_idle(
+            &[
+                0xE3A01403, // mov  r1, #0x03000000
+                0xE5810004, // str  r0, [r1, #4]
+                0xE5910000, // ldr  r0, [r1]
+                0xE3500000, // cmp  r0, #0
+                0x0AFFFFFB, // beq  0x08000004
+            ],
+            64,
+        ));
+    }
+
+    #[test]
+    fn viewing_timers_does_not_prevent_idle() {
+        assert!(goes_idle_with(
+            &[
+                0xE3A01403, // mov  r1, #0x03000000
+                0xE5910000, // ldr  r0, [r1]
+                0xE3500000, // cmp  r0, #0
+                0x0AFFFFFC, // beq  0x08000004
+            ],
+            16,
+            |gba| {
+                gba.mem.view16(0x04000100); // TM0CNT_L
+            },
+        ));
+    }
+
+    #[test]
+    fn timer_polling_loop_is_not_idle() {
+        assert!(!goes_idle(
+            &[
+                0xE3A01301, // mov  r1, #0x04000000
+                0xE2811C01, // add  r1, r1, #0x100
+                0xE1D100B0, // ldrh r0, [r1]
+                0xE3500000, // cmp  r0, #0
+                0x0AFFFFFC, // beq  0x08000008
+            ],
+            64,
+        ));
+    }
+}
@@ -1,16 +1,27 @@
 mod audio;
 mod dma;
+mod idle_loop;
 mod interrupts;
 pub mod memory;
 mod scheduler;
 mod timers;
-mod video;
+pub mod video;
 
 use dma::GbaDMA;
+use idle_loop::IdleLoopDetector;
 pub use memory::GbaMemory;
 
-use arm::{Cpu, Cycles, Memory};
-pub use audio::{sampler::GbaAudioSampler, Command, GbaAudio};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use arm::{Cpu, CpuException, Cycles, ExceptionHandlerResult, Memory};
+pub use audio::{
+    sampler::GbaAudioSampler,
+    synth::{GbaAudioSynth, NativeFrame, NativeSynth},
+    AudioChannel, ChannelMask, Command, GbaAudio,
+};
 use scheduler::Scheduler;
 use util::bits::Bits;
 pub use video::{GbaVideo, SCREEN_HEIGHT, SCREEN_PIXEL_COUNT, SCREEN_WIDTH};
@@ -25,16 +36,40 @@ pub struct Gba {
     scheduler: Scheduler,
     step_fn: fn(&mut Self) -> arm::Cycles,
     state: State,
+    idle_loop: IdleLoopDetector,
+    /// Address of the first undefined instruction the CPU ran into since it was last taken, set
+    /// from its exception handler.
+    undefined_instruction: UndefinedInstruction,
 }
 
 impl Gba {
     pub const CYCLES_PER_SECOND: u32 = 16 * 1024 * 1024;
+    /// 228 lines (160 visible + 68 VBLANK) of 1232 cycles each.
+    pub const CYCLES_PER_FRAME: u32 = 228 * 1232;
+    /// Roughly 59.73 frames per second.
+    pub const FRAMES_PER_SECOND: f64 =
+        Self::CYCLES_PER_SECOND as f64 / Self::CYCLES_PER_FRAME as f64;
+    /// Maximum number of cycles the CPU runs for at once when there are no scheduled events.
+    const MAX_RUN_CYCLES: u32 = 1024;
+
     pub fn new() -> Gba {
         let scheduler = Scheduler::default();
 
+        let undefined_instruction = UndefinedInstruction::default();
+        let mut cpu = Cpu::uninitialized(arm::Isa::Arm, arm::CpuMode::System);
+        let first_undefined = undefined_instruction.clone();
+        cpu.set_exception_handler(move |cpu, _memory, exception| {
+            // The exception is still taken, this only records where it happened.
+            if exception == CpuException::Undefined {
+                let pc = cpu.registers.read(15);
+                first_undefined.record(pc.wrapping_sub(if cpu.registers.getf_t() { 4 } else { 8 }));
+            }
+            ExceptionHandlerResult::Ignored
+        });
+
         Gba {
             mem: GbaMemory::new(scheduler.clone()),
-            cpu: Cpu::uninitialized(arm::Isa::Arm, arm::CpuMode::System),
+            cpu,
             dma: [
                 GbaDMA::default(),
                 GbaDMA::default(),
@@ -47,6 +82,8 @@ impl Gba {
             scheduler,
             state: State::Running,
             step_fn: Self::step_cpu,
+            idle_loop: IdleLoopDetector::default(),
+            undefined_instruction,
         }
     }
 
@@ -101,7 +138,7 @@ impl Gba {
     }
 
     pub fn frame(&mut self) {
-        self.audio.clear(self.mem.ioregs.time);
+        self.audio.clear(&self.mem.ioregs);
 
         // wait until we are out of VBLANK
         while self.mem.ioregs.dispstat.vblank() {
@@ -112,10 +149,24 @@ impl Gba {
         while !self.mem.ioregs.dispstat.vblank() {
             self.step();
         }
+
+        self.audio.end_frame(&self.mem.ioregs);
     }
 
     fn step_cpu(&mut self) -> arm::Cycles {
-        self.cpu.step(&mut self.mem)
+        let cycles_until_next_event = self.scheduler.cycles_until_next_event(self.mem.ioregs.time);
+
+        // A busy-wait loop can't make any progress until the next event, so skip ahead to it
+        // the same way that step_idle does for a real halt.
+        if self.idle_loop.check(&self.cpu, &mut self.mem) {
+            return cycles_until_next_event.unwrap_or(Cycles::new(32));
+        }
+
+        // Events can only be scheduled by memory accesses, which always run on their own, so
+        // anything they schedule is seen before any more instructions run and it's safe to keep
+        // running until the next event is due.
+        let budget = cycles_until_next_event.unwrap_or(Cycles::new(Self::MAX_RUN_CYCLES));
+        self.cpu.run(&mut self.mem, budget)
     }
 
     fn step_idle(&mut self) -> arm::Cycles {
@@ -140,11 +191,24 @@ impl Gba {
         };
     }
 
+    /// Runs the CPU (or DMA, or idles) until the next event is due and then runs all of the events
+    /// that are due. If there are no events scheduled this only runs a single step.
     fn step(&mut self) {
-        let now = self.mem.ioregs.time + u32::from((self.step_fn)(self)) as u64;
+        let mut now = self.mem.ioregs.time;
+        loop {
+            now += u32::from((self.step_fn)(self)) as u64;
+            // Anything scheduled by the next step must be relative to the current time.
+            self.mem.ioregs.time = now;
+            self.scheduler.set_time(now);
+            if self.scheduler.is_event_due(now) || !self.scheduler.has_events() {
+                break;
+            }
+        }
+
         while let Some((event, when)) = self.scheduler.next(now) {
             self.mem.ioregs.time = when;
             (event)(self);
+            self.idle_loop.invalidate();
         }
         self.mem.ioregs.time = now;
     }
@@ -202,9 +266,27 @@ impl Gba {
         &self.audio
     }
 
+    /// Generates the audio command stream for sample-exact playback with a [`NativeSynth`] or
+    /// [`GbaAudioSynth`] instead of limiting Sound 3 to 32768Hz. See [`NativeSynth`] for how to
+    /// use it to compare audio against hardware recordings.
+    pub fn set_sample_exact_audio(&mut self, sample_exact: bool) {
+        self.audio.set_sample_exact(sample_exact);
+    }
+
+    /// Mutes or solos audio channels in the audio command stream, for debugging.
+    pub fn set_audio_channel_mask(&mut self, mask: ChannelMask) {
+        self.audio.set_channel_mask(mask, &self.mem.ioregs);
+    }
+
     pub fn cpu(&self) -> &Cpu {
         &self.cpu
     }
+
+    /// Returns the address of the first undefined instruction that was executed since this was
+    /// last called. The CPU still takes the undefined instruction exception as normal.
+    pub fn take_undefined_instruction(&mut self) -> Option<u32> {
+        self.undefined_instruction.take()
+    }
 }
 
 impl Default for Gba {
@@ -291,3 +373,67 @@ impl From<ButtonSet> for u16 {
 // Send should be safe to implement for the GBA because we never leak the RC's
 // that are used by the GBA and its other parts.
 unsafe impl Send for Gba {}
+
+/// Shared between the [`Gba`] and the CPU's exception handler, which has to be `Send + Sync`.
+/// Holds [`UndefinedInstruction::NONE`] if there is no address.
+#[derive(Clone)]
+struct UndefinedInstruction(Arc<AtomicU64>);
+
+impl UndefinedInstruction {
+    const NONE: u64 = u64::MAX;
+
+    /// Records the address unless there is already one that hasn't been taken.
+    fn record(&self, address: u32) {
+        let _ = self.0.compare_exchange(
+            Self::NONE,
+            u64::from(address),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        );
+    }
+
+    fn take(&self) -> Option<u32> {
+        match self.0.swap(Self::NONE, Ordering::Relaxed) {
+            Self::NONE => None,
+            address => Some(address as u32),
+        }
+    }
+}
+
+impl Default for UndefinedInstruction {
+    fn default() -> Self {
+        UndefinedInstruction(Arc::new(AtomicU64::new(Self::NONE)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn step_returns_without_events() {
+        let mut gba = Gba::new();
+        gba.set_bios(None);
+        gba.set_gamepak(0xEAFFFFFEu32.to_le_bytes().to_vec()); // b .
+        gba.reset(false);
+        gba.scheduler.clear();
+
+        let time = gba.mem.ioregs.time;
+        gba.step();
+        assert!(gba.mem.ioregs.time > time);
+    }
+
+    #[test]
+    fn first_undefined_instruction_is_kept() {
+        let undefined = UndefinedInstruction::default();
+        assert_eq!(undefined.take(), None);
+
+        undefined.record(0x08000010);
+        undefined.record(0x08000020);
+        assert_eq!(undefined.take(), Some(0x08000010));
+        assert_eq!(undefined.take(), None);
+
+        undefined.clone().record(0);
+        assert_eq!(undefined.take(), Some(0));
+    }
+}
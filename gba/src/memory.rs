@@ -1,9 +1,10 @@
 mod gamepak;
 pub mod io;
 pub mod palette;
+mod prefetch;
 mod sram;
 
-use arm::{AccessType, Memory, Waitstates};
+use arm::{AccessType, Memory, Waitstates, CODE_PAGE_SIZE};
 use log::debug;
 use util::{
     array,
@@ -12,7 +13,7 @@ use util::{
 
 use crate::scheduler::Scheduler;
 
-use self::{io::IoRegisters, palette::Palette};
+use self::{io::IoRegisters, palette::Palette, prefetch::Prefetcher};
 
 pub const REGION_BIOS: u32 = 0x0;
 pub const REGION_UNUSED_1: u32 = 0x1;
@@ -44,6 +45,9 @@ pub const PAL_MASK: u32 = 0x3FF;
 pub const OAM_MASK: u32 = 0x3FF;
 pub const ROM_MAX_MASK: u32 = 0xFFFFFF;
 
+/// Number of EWRAM and IWRAM pages that writes to code are tracked for.
+const CODE_PAGES: usize = ((EWRAM_SIZE + IWRAM_SIZE) / CODE_PAGE_SIZE) as usize;
+
 pub static CUSTOM_BIOS: &[u8] = include_bytes!("../../bios/bios.bin");
 
 pub struct GbaMemory {
@@ -57,12 +61,32 @@ pub struct GbaMemory {
 
     pub(crate) scheduler: Scheduler,
 
+    /// Set by stores and by reads of values that change over time without any events (timer
+    /// counters). Used for idle loop detection.
+    pub(crate) side_effects: bool,
+
     rom: Vec<u8>,
 
     allow_bios_access: bool,
     last_opcode: u32,
+    /// Address of the most recent opcode fetch and whether it was a THUMB fetch. Together with
+    /// `last_opcode` these decide what is read from unmapped memory.
+    last_fetch_address: u32,
+    last_fetch_thumb: bool,
+    /// The last word fetched from the BIOS. Reads from the BIOS while executing outside of it
+    /// return this instead of the BIOS contents.
+    bios_latch: u32,
+    /// The last value transferred by DMA. DMA reads from unmapped memory return this.
+    pub(crate) dma_latch: u32,
+
+    /// Versions of the EWRAM pages followed by the IWRAM pages, changed whenever they are written.
+    /// See [`Memory::code_version`].
+    code_versions: Box<[u32; CODE_PAGES]>,
+    /// Version of the BIOS and ROM, which only change when they are replaced or poked.
+    rom_code_version: u32,
 
     prefetch_enabled: bool,
+    prefetch: Prefetcher,
     gamepak_waitstates: [Waitstates; 6],
     ewram_waitstates: Waitstates,
     sram_waitstates: Waitstates,
@@ -83,11 +107,20 @@ impl GbaMemory {
             ioregs: Box::new(IoRegisters::default()),
 
             scheduler,
+            side_effects: false,
 
             allow_bios_access: false,
             last_opcode: 0,
+            last_fetch_address: 0,
+            last_fetch_thumb: false,
+            bios_latch: 0,
+            dma_latch: 0,
+
+            code_versions: array::boxed_copied(0),
+            rom_code_version: 0,
 
             prefetch_enabled: false,
+            prefetch: Prefetcher::default(),
             gamepak_waitstates: [0u8.into(); 6],
             ewram_waitstates: 2u8.into(),
             sram_waitstates: 8u8.into(),
@@ -99,15 +132,20 @@ impl GbaMemory {
         self.store16_io(io::WAITCNT, 0x4317);
         self.ewram_waitstates = 2.into();
         self.ioregs.init();
+
+        // The value left on the BIOS bus by the startup code.
+        self.bios_latch = 0xE129F000;
     }
 
     pub fn set_gamepak(&mut self, gamepak: Vec<u8>) {
         self.rom = gamepak;
+        self.rom_code_version = self.rom_code_version.wrapping_add(1);
     }
 
     pub fn set_bios(&mut self, mut bios: Vec<u8>) {
         bios.resize(BIOS_SIZE as usize, 0);
         self.bios = bios.into_boxed_slice().try_into().unwrap();
+        self.rom_code_version = self.rom_code_version.wrapping_add(1);
         self.using_custom_bios = false;
     }
 
@@ -126,6 +164,26 @@ impl GbaMemory {
         &mut self.ioregs
     }
 
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    pub fn palette_mut(&mut self) -> &mut Palette {
+        &mut self.palette
+    }
+
+    pub fn vram(&self) -> &[u8; VRAM_SIZE as usize] {
+        &self.vram
+    }
+
+    pub fn oam(&self) -> &[u8; OAM_SIZE as usize] {
+        &self.oam
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
     pub fn view8(&mut self, address: u32) -> u8 {
         match address >> 24 {
             REGION_BIOS => self.bios.get(address as usize).copied().unwrap_or(0),
@@ -215,28 +273,146 @@ impl GbaMemory {
         }
     }
 
+    /// Changes the code version of the EWRAM or IWRAM page containing the address so that any
+    /// instructions that were decoded from it are decoded again.
+    fn code_written(&mut self, address: u32) {
+        if let Some(page) = code_page(address) {
+            self.code_versions[page] = self.code_versions[page].wrapping_add(1);
+        }
+    }
+
+    /// Writes a byte directly to the underlying storage of the given address without triggering
+    /// any side effects or waitstates, unlike [`Memory::store8`]. This can be used to patch the
+    /// BIOS and ROM as well.
+    pub fn poke8(&mut self, address: u32, value: u8) {
+        match address >> 24 {
+            REGION_BIOS => {
+                if let Some(byte) = self.bios.get_mut(address as usize) {
+                    *byte = value;
+                }
+                self.rom_code_version = self.rom_code_version.wrapping_add(1);
+            }
+            REGION_EWRAM => {
+                self.ewram[(address & EWRAM_MASK) as usize] = value;
+                self.code_written(address);
+            }
+            REGION_IWRAM => {
+                self.iwram[(address & IWRAM_MASK) as usize] = value;
+                self.code_written(address);
+            }
+            REGION_IOREGS => {
+                let mut value16 = self.load16_io::<true>(address & !0x1);
+                let shift = (address & 1) * 8;
+                value16 &= !(0xFF << shift);
+                value16 |= (value as u16) << shift;
+                self.poke16_io(address & !0x1, value16);
+            }
+            REGION_PAL => self.palette.data[(address & PAL_MASK) as usize] = value,
+            REGION_VRAM => self.vram[vram_offset(address)] = value,
+            REGION_OAM => self.oam[(address & OAM_MASK) as usize] = value,
+
+            REGION_GAMEPAK0_LO | REGION_GAMEPAK0_HI | REGION_GAMEPAK1_LO | REGION_GAMEPAK1_HI
+            | REGION_GAMEPAK2_LO | REGION_GAMEPAK2_HI => {
+                if let Some(byte) = self.rom.get_mut((address & ROM_MAX_MASK) as usize) {
+                    *byte = value;
+                }
+                self.rom_code_version = self.rom_code_version.wrapping_add(1);
+            }
+
+            REGION_SRAM => self.poke8_sram(address, value),
+
+            _ => debug!("poke to invalid address 0x{:08X}=0x{:02X}", address, value),
+        }
+    }
+
+    /// Writes a halfword directly to the underlying storage of the given address without
+    /// triggering any side effects. See [`GbaMemory::poke8`].
+    pub fn poke16(&mut self, mut address: u32, value: u16) {
+        address &= !0x1;
+        if address >> 24 == REGION_IOREGS {
+            self.poke16_io(address, value);
+        } else {
+            self.poke8(address, value as u8);
+            self.poke8(address + 1, (value >> 8) as u8);
+        }
+    }
+
+    /// Writes a word directly to the underlying storage of the given address without triggering
+    /// any side effects. See [`GbaMemory::poke8`].
+    pub fn poke32(&mut self, mut address: u32, value: u32) {
+        address &= !0x3;
+        self.poke16(address, value as u16);
+        self.poke16(address + 2, (value >> 16) as u16);
+    }
+
+    /// Writes a byte the same way that the CPU would, including any IO side effects. Waitstates
+    /// are ignored.
+    pub fn write8_as_cpu(&mut self, address: u32, value: u8) {
+        self.store8(address, value, AccessType::NonSeq);
+    }
+
+    /// Writes a halfword the same way that the CPU would. See [`GbaMemory::write8_as_cpu`].
+    pub fn write16_as_cpu(&mut self, address: u32, value: u16) {
+        self.store16(address, value, AccessType::NonSeq);
+    }
+
+    /// Writes a word the same way that the CPU would. See [`GbaMemory::write8_as_cpu`].
+    pub fn write32_as_cpu(&mut self, address: u32, value: u32) {
+        self.store32(address, value, AccessType::NonSeq);
+    }
+
+    /// Lets the prefetcher use the GamePak bus while the CPU accesses other regions.
+    #[inline]
+    fn prefetch_bus_idle(&mut self, region: u32, wait: Waitstates) {
+        if region < REGION_GAMEPAK0_LO {
+            self.prefetch.advance(u32::from(wait) + 1);
+        }
+    }
+
+    /// Returns the value read from unmapped memory, which is whatever the last opcode fetch
+    /// left on the bus. In THUMB state this depends on the width of the bus of the region that
+    /// the CPU is executing from.
+    fn open_bus(&self) -> u32 {
+        if !self.last_fetch_thumb {
+            return self.last_opcode;
+        }
+
+        // Only the two most recently fetched halfwords ($+4 and $+2) are kept around.
+        let current = self.last_opcode & 0xFFFF;
+        let previous = self.last_opcode >> 16;
+        let aligned = self.last_fetch_address & 0x2 == 0;
+        match self.last_fetch_address >> 24 {
+            REGION_BIOS | REGION_OAM if aligned => {
+                let next = self.last_fetch_address.wrapping_add(2);
+                let next = if next >> 24 == REGION_BIOS {
+                    read_u16(&*self.bios, next as usize & (BIOS_SIZE as usize - 1))
+                } else {
+                    read_u16(&*self.oam, (next & OAM_MASK) as usize)
+                };
+                current | ((next as u32) << 16)
+            }
+            REGION_IWRAM if aligned => current | (previous << 16),
+            REGION_BIOS | REGION_OAM | REGION_IWRAM => previous | (current << 16),
+            _ => current | (current << 16),
+        }
+    }
+
     fn load32_bios(&self, address: u32) -> u32 {
-        if self.allow_bios_access && address <= 0x3FFC {
+        if address > 0x3FFF {
+            self.open_bus()
+        } else if self.allow_bios_access {
             read_u32(&*self.bios, address as usize)
         } else {
-            self.last_opcode
+            self.bios_latch
         }
     }
 
     fn load16_bios(&self, address: u32) -> u16 {
-        if self.allow_bios_access && address <= 0x3FFE {
-            read_u16(&*self.bios, address as usize)
-        } else {
-            self.last_opcode as u16
-        }
+        (self.load32_bios(address & !0x3) >> ((address & 0x2) * 8)) as u16
     }
 
     fn load8_bios(&self, address: u32) -> u8 {
-        if self.allow_bios_access && address <= 0x3FFF {
-            self.bios[address as usize]
-        } else {
-            self.last_opcode as u8
-        }
+        (self.load32_bios(address & !0x3) >> ((address & 0x3) * 8)) as u8
     }
 }
 
@@ -252,15 +428,33 @@ macro_rules! de_assign {
 impl Memory for GbaMemory {
     fn fetch32(&mut self, address: u32, access: AccessType) -> (u32, Waitstates) {
         self.allow_bios_access = address <= 0x4004;
-        let (opcode, wait) = self.load32(address, access);
+        let (opcode, wait) = if self.prefetch_enabled && is_rom_address(address) {
+            self.fetch32_gamepak(address, access)
+        } else {
+            self.load32(address, access)
+        };
         self.last_opcode = opcode;
+        self.last_fetch_address = address;
+        self.last_fetch_thumb = false;
+        if self.allow_bios_access {
+            self.bios_latch = opcode;
+        }
         (opcode, wait)
     }
 
     fn fetch16(&mut self, address: u32, access: AccessType) -> (u16, Waitstates) {
         self.allow_bios_access = address <= 0x4004;
-        let (opcode, wait) = self.load16(address, access);
+        let (opcode, wait) = if self.prefetch_enabled && is_rom_address(address) {
+            self.fetch16_gamepak(address, access)
+        } else {
+            self.load16(address, access)
+        };
         self.last_opcode = (self.last_opcode << 16) | opcode as u32;
+        self.last_fetch_address = address;
+        self.last_fetch_thumb = true;
+        if self.allow_bios_access {
+            self.bios_latch = self.load32_bios(address & !0x3);
+        }
         (opcode, wait)
     }
 
@@ -272,7 +466,7 @@ impl Memory for GbaMemory {
         address &= !0x3; // align address
         match region {
             REGION_BIOS => value = self.load32_bios(address),
-            REGION_UNUSED_1 => value = self.last_opcode,
+            REGION_UNUSED_1 => value = self.open_bus(),
             REGION_EWRAM => {
                 value = read_u32(&*self.ewram, (address & EWRAM_MASK) as usize);
                 wait = self.ewram_waitstates + self.ewram_waitstates;
@@ -299,9 +493,10 @@ impl Memory for GbaMemory {
             }
             REGION_SRAM => de_assign!(value, wait, self.load32_sram(address, access)),
 
-            _ => value = self.last_opcode,
+            _ => value = self.open_bus(),
         }
 
+        self.prefetch_bus_idle(region, wait);
         (value, wait)
     }
 
@@ -314,7 +509,7 @@ impl Memory for GbaMemory {
         address &= !0x1; // align address
         match region {
             REGION_BIOS => value = self.load16_bios(address),
-            REGION_UNUSED_1 => value = self.last_opcode as u16,
+            REGION_UNUSED_1 => value = (self.open_bus() >> ((address & 0x2) * 8)) as u16,
             REGION_EWRAM => {
                 value = read_u16(&*self.ewram, (address & EWRAM_MASK) as usize);
                 wait = self.ewram_waitstates;
@@ -334,13 +529,14 @@ impl Memory for GbaMemory {
                 de_assign!(value, wait, self.load16_gamepak(address, 2, access))
             }
             REGION_SRAM => de_assign!(value, wait, self.load16_sram(address, access)),
-            _ => value = self.last_opcode as u16,
+            _ => value = (self.open_bus() >> ((address & 0x2) * 8)) as u16,
         }
 
         // Addresses in load16 can be unaligned. In this case the GBA just rotates the value at the aligned
         // address. This is done the same way that instructions that LDR rotate unaligned accesses.
         value = value.rotate_right((unaligned_address as u32 & 1) * 8);
 
+        self.prefetch_bus_idle(region, wait);
         (value, wait)
     }
 
@@ -351,7 +547,7 @@ impl Memory for GbaMemory {
 
         match region {
             REGION_BIOS => value = self.load8_bios(address),
-            REGION_UNUSED_1 => value = self.last_opcode as u8,
+            REGION_UNUSED_1 => value = (self.open_bus() >> ((address & 0x3) * 8)) as u8,
             REGION_EWRAM => {
                 value = self.ewram[(address & EWRAM_MASK) as usize];
                 wait = self.ewram_waitstates;
@@ -371,13 +567,15 @@ impl Memory for GbaMemory {
                 de_assign!(value, wait, self.load8_gamepak(address, 2, access))
             }
             REGION_SRAM => de_assign!(value, wait, self.load8_sram(address, access)),
-            _ => value = self.last_opcode as u8,
+            _ => value = (self.open_bus() >> ((address & 0x3) * 8)) as u8,
         }
 
+        self.prefetch_bus_idle(region, wait);
         (value, wait)
     }
 
     fn store32(&mut self, mut address: u32, value: u32, access: AccessType) -> Waitstates {
+        self.side_effects = true;
         let mut wait = Waitstates::ZERO;
 
         address &= !0x3;
@@ -386,9 +584,13 @@ impl Memory for GbaMemory {
             REGION_UNUSED_1 => debug!("write to UNUSED 0x{:08X}=0x{:08X}", address, value),
             REGION_EWRAM => {
                 write_u32(&mut *self.ewram, (address & EWRAM_MASK) as usize, value);
+                self.code_written(address);
                 wait = self.ewram_waitstates + self.ewram_waitstates;
             }
-            REGION_IWRAM => write_u32(&mut *self.iwram, (address & IWRAM_MASK) as usize, value),
+            REGION_IWRAM => {
+                write_u32(&mut *self.iwram, (address & IWRAM_MASK) as usize, value);
+                self.code_written(address);
+            }
 
             REGION_IOREGS => self.store32_io(address, value),
             REGION_PAL => self.palette.store32(address, value),
@@ -409,10 +611,12 @@ impl Memory for GbaMemory {
             _ => debug!("write to invalid address 0x{:08X}=0x{:08X}", address, value),
         }
 
+        self.prefetch_bus_idle(address >> 24, wait);
         wait
     }
 
     fn store16(&mut self, mut address: u32, value: u16, access: AccessType) -> Waitstates {
+        self.side_effects = true;
         let mut wait = Waitstates::ZERO;
 
         address &= !0x1;
@@ -421,9 +625,13 @@ impl Memory for GbaMemory {
             REGION_UNUSED_1 => debug!("write to UNUSED 0x{:08X}=0x{:08X}", address, value),
             REGION_EWRAM => {
                 write_u16(&mut *self.ewram, (address & EWRAM_MASK) as usize, value);
+                self.code_written(address);
                 wait = self.ewram_waitstates;
             }
-            REGION_IWRAM => write_u16(&mut *self.iwram, (address & IWRAM_MASK) as usize, value),
+            REGION_IWRAM => {
+                write_u16(&mut *self.iwram, (address & IWRAM_MASK) as usize, value);
+                self.code_written(address);
+            }
 
             REGION_IOREGS => self.store16_io(address, value),
             REGION_PAL => self.palette.store16(address, value),
@@ -444,10 +652,12 @@ impl Memory for GbaMemory {
             _ => debug!("write to invalid address 0x{:08X}=0x{:04X}", address, value),
         }
 
+        self.prefetch_bus_idle(address >> 24, wait);
         wait
     }
 
     fn store8(&mut self, address: u32, value: u8, access: AccessType) -> Waitstates {
+        self.side_effects = true;
         let mut wait = Waitstates::ZERO;
 
         match address >> 24 {
@@ -455,9 +665,13 @@ impl Memory for GbaMemory {
             REGION_UNUSED_1 => debug!("write to UNUSED 0x{:08X}=0x{:08X}", address, value),
             REGION_EWRAM => {
                 self.ewram[(address & EWRAM_MASK) as usize] = value;
+                self.code_written(address);
                 wait = self.ewram_waitstates;
             }
-            REGION_IWRAM => self.iwram[(address & IWRAM_MASK) as usize] = value,
+            REGION_IWRAM => {
+                self.iwram[(address & IWRAM_MASK) as usize] = value;
+                self.code_written(address);
+            }
 
             REGION_IOREGS => self.store8_io(address, value),
 
@@ -500,12 +714,35 @@ impl Memory for GbaMemory {
             _ => debug!("write to invalid address 0x{:08X}=0x{:02X}", address, value),
         }
 
+        self.prefetch_bus_idle(address >> 24, wait);
         wait
     }
 
-    fn stall(&mut self, _cycles: arm::Cycles) {
-        /* NOP */
+    fn stall(&mut self, cycles: arm::Cycles) {
+        self.prefetch.advance(u32::from(cycles));
     }
+
+    fn code_version(&self, address: u32) -> Option<u32> {
+        match address >> 24 {
+            REGION_BIOS | REGION_GAMEPAK0_LO..=REGION_GAMEPAK2_HI => Some(self.rom_code_version),
+            _ => code_page(address).map(|page| self.code_versions[page]),
+        }
+    }
+}
+
+/// Returns the index of the EWRAM or IWRAM page containing the address in
+/// [`GbaMemory::code_versions`].
+const fn code_page(address: u32) -> Option<usize> {
+    match address >> 24 {
+        REGION_EWRAM => Some(((address & EWRAM_MASK) / CODE_PAGE_SIZE) as usize),
+        REGION_IWRAM => Some(((EWRAM_SIZE + (address & IWRAM_MASK)) / CODE_PAGE_SIZE) as usize),
+        _ => None,
+    }
+}
+
+/// Returns true if the address is in one of the GamePak ROM waitstate regions.
+const fn is_rom_address(address: u32) -> bool {
+    matches!(address >> 24, REGION_GAMEPAK0_LO..=REGION_GAMEPAK2_HI)
 }
 
 /// Converts an address in the range [0x06000000, 0x06FFFFFF] into an offset in VRAM accounting
@@ -527,6 +764,7 @@ const fn vram_offset(address: u32) -> usize {
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::scheduler::EventTag;
 
     /// Most internal memory regions are mirrored across their entire 24bit address spaces.
     /// This includes `EWRAM` at `0x02XXXXXX`, `IWRAM` at `0x03XXXXXX`, `Palette RAM` at
@@ -577,6 +815,113 @@ mod test {
         assert_eq!(memory.load32(0x07000400, AccessType::Seq).0, 0xCACBCDCE);
     }
 
+    /// Pokes write directly to storage, so 8bit writes to palette RAM, VRAM and OAM are not
+    /// duplicated/ignored, ROM can be patched, and IO writes don't schedule any events.
+    #[test]
+    pub fn poke_has_no_side_effects() {
+        let scheduler = Scheduler::default();
+        let mut memory = GbaMemory::new(scheduler.clone());
+        memory.set_gamepak(vec![0; 0x100]);
+
+        memory.poke8(0x05000001, 0xAB);
+        assert_eq!(memory.view16(0x05000000), 0xAB00);
+        memory.poke8(0x06010001, 0xAB);
+        assert_eq!(memory.view16(0x06010000), 0xAB00);
+        memory.poke8(0x07000001, 0xAB);
+        assert_eq!(memory.view16(0x07000000), 0xAB00);
+
+        memory.poke32(0x08000010, 0xCACBCDCE);
+        assert_eq!(memory.view32(0x08000010), 0xCACBCDCE);
+        assert_eq!(memory.view32(0x0A000010), 0xCACBCDCE);
+        memory.poke32(0x08001000, 0xCACBCDCE);
+        assert_eq!(memory.rom().len(), 0x100);
+
+        memory.poke16(io::DMA0CNT_H, 0x8000);
+        assert!(memory.ioregs().dma[0].control.enabled());
+        assert!(!scheduler.contains_tag(EventTag::DMA0));
+
+        memory.poke16(io::IF, 0x0001);
+        assert_eq!(memory.view16(io::IF), 0x0001);
+
+        memory.write16_as_cpu(io::DMA1CNT_H, 0x8000);
+        assert!(scheduler.contains_tag(EventTag::DMA1));
+    }
+
+    /// With the prefetch buffer enabled, sequential opcode fetches from ROM are free if the
+    /// prefetcher had time to read them during internal cycles. Data accesses to ROM invalidate
+    /// the buffer.
+    #[test]
+    pub fn gamepak_prefetch_buffer() {
+        fn fetch16(memory: &mut GbaMemory, address: u32, access: AccessType) -> u32 {
+            memory.fetch16(address, access).1.into()
+        }
+
+        let mut memory = GbaMemory::new(Scheduler::default());
+        memory.set_gamepak(vec![0; 0x100]);
+        // WS0 with the prefetch buffer enabled. Non-sequential accesses take 4 waitstates and
+        // sequential ones take 2, so the prefetcher reads a halfword every 3 cycles.
+        memory.store16(io::WAITCNT, 0x4000, AccessType::NonSeq);
+
+        assert_eq!(
+            u32::from(memory.fetch32(0x08000000, AccessType::NonSeq).1),
+            4
+        );
+        assert_eq!(fetch16(&mut memory, 0x08000010, AccessType::NonSeq), 4);
+        memory.stall(arm::Cycles::from(4u32));
+        assert_eq!(fetch16(&mut memory, 0x08000012, AccessType::Seq), 0);
+        // The next halfword is still being read, but only has one cycle left.
+        assert_eq!(fetch16(&mut memory, 0x08000014, AccessType::Seq), 0);
+        // Nothing else has been read yet, so this takes as long as a normal access.
+        assert_eq!(fetch16(&mut memory, 0x08000016, AccessType::Seq), 2);
+
+        // IWRAM accesses leave the GamePak bus free.
+        assert_eq!(fetch16(&mut memory, 0x08000020, AccessType::NonSeq), 4);
+        memory.load32(0x03000000, AccessType::NonSeq);
+        memory.load32(0x03000000, AccessType::Seq);
+        assert_eq!(fetch16(&mut memory, 0x08000022, AccessType::Seq), 0);
+
+        memory.load16(0x08000080, AccessType::NonSeq);
+        assert_eq!(fetch16(&mut memory, 0x08000024, AccessType::Seq), 2);
+
+        // Without the buffer every fetch is a normal access.
+        memory.store16(io::WAITCNT, 0x0000, AccessType::NonSeq);
+        memory.stall(arm::Cycles::from(4u32));
+        assert_eq!(fetch16(&mut memory, 0x08000028, AccessType::Seq), 2);
+    }
+
+    /// Reads from unmapped memory return the last fetched opcode, reads from the BIOS while
+    /// executing outside of it return the last word fetched from the BIOS, and reads past the
+    /// end of the ROM return the halfword address.
+    #[test]
+    pub fn open_bus_reads() {
+        let mut memory = GbaMemory::new(Scheduler::default());
+        let mut bios = vec![0; 8];
+        bios[4..].copy_from_slice(&0xE3A02004u32.to_le_bytes());
+        memory.set_bios(bios);
+        memory.set_gamepak(vec![0; 0x100]);
+        memory.poke32(0x08000000, 0xE1A00000);
+        memory.store32(0x03000000, 0x22221111, AccessType::NonSeq);
+
+        memory.fetch32(0x00000004, AccessType::NonSeq);
+        memory.fetch32(0x08000000, AccessType::NonSeq);
+        assert_eq!(memory.load32(0x10000000, AccessType::NonSeq).0, 0xE1A00000);
+        assert_eq!(memory.load16(0x00004002, AccessType::NonSeq).0, 0xE1A0);
+        assert_eq!(memory.load32(0x00000000, AccessType::NonSeq).0, 0xE3A02004);
+        assert_eq!(memory.load8(0x00000003, AccessType::NonSeq).0, 0xE3);
+
+        // THUMB code in IWRAM puts both of the last two fetched halfwords on the bus.
+        memory.fetch16(0x03000000, AccessType::NonSeq);
+        memory.fetch16(0x03000002, AccessType::Seq);
+        assert_eq!(memory.load32(0xF0000000, AccessType::NonSeq).0, 0x22221111);
+        // The GamePak bus is 16 bits wide so the same halfword shows up twice.
+        memory.fetch16(0x08000002, AccessType::NonSeq);
+        assert_eq!(memory.load32(0x10000000, AccessType::NonSeq).0, 0xE1A0E1A0);
+
+        assert_eq!(memory.load16(0x08001000, AccessType::NonSeq).0, 0x0800);
+        assert_eq!(memory.load32(0x08001000, AccessType::NonSeq).0, 0x08010800);
+        assert_eq!(memory.load8(0x08001003, AccessType::NonSeq).0, 0x08);
+    }
+
     /// Like other internal memory regions `VRAM` is also mirrored across its 24bit address space.
     /// `VRAM` is `96K` in size it is mirrored in `128K` steps where the last `32K` chunk of each
     /// step is a mirror of the previous `32K`.
@@ -609,4 +954,76 @@ mod test {
         assert_eq!(memory.load32(0x06010004, AccessType::Seq).0, 0xABACADAE);
         assert_eq!(memory.load32(0x06018004, AccessType::Seq).0, 0xABACADAE);
     }
+
+    #[test]
+    pub fn code_versions_track_writes() {
+        let mut memory = GbaMemory::new(Scheduler::default());
+        let iwram = memory.code_version(0x03000000);
+        let ewram = memory.code_version(0x02000000);
+        let rom = memory.code_version(0x08000000);
+        assert!(iwram.is_some() && ewram.is_some() && rom.is_some());
+        assert_eq!(memory.code_version(0x06000000), None);
+
+        // Only the written page changes, including through mirrors.
+        memory.store8(0x03008000, 1, AccessType::NonSeq);
+        assert_ne!(memory.code_version(0x03000000), iwram);
+        assert_eq!(memory.code_version(0x03000000 + CODE_PAGE_SIZE), iwram);
+        assert_eq!(memory.code_version(0x02000000), ewram);
+
+        memory.store16(0x02000010, 1, AccessType::NonSeq);
+        assert_ne!(memory.code_version(0x02000000), ewram);
+
+        let ewram = memory.code_version(0x02000000 + CODE_PAGE_SIZE);
+        memory.store8(0x02000000 + CODE_PAGE_SIZE, 1, AccessType::NonSeq);
+        assert_ne!(memory.code_version(0x02000000 + CODE_PAGE_SIZE), ewram);
+
+        memory.poke8(0x08000000, 1);
+        assert_ne!(memory.code_version(0x08000000), rom);
+        assert_eq!(
+            memory.code_version(0x00000000),
+            memory.code_version(0x08000000)
+        );
+    }
+
+    /// Instructions cached from IWRAM and EWRAM are decoded again after the code is overwritten.
+    #[test]
+    pub fn modified_code_is_decoded_again() {
+        // add r2, r0, r0, lsl #3
+        let r2 = run_modified_code(0x03000000, |memory| {
+            memory.store32(0x03000008, 0xE0802180, AccessType::NonSeq);
+        });
+        assert_eq!(r2, 9);
+
+        // add r2, r0, r3
+        let r2 = run_modified_code(0x02000000, |memory| {
+            memory.store8(0x0200000A, 0x80, AccessType::NonSeq);
+            memory.store8(0x0200000B, 0xE0, AccessType::NonSeq);
+        });
+        assert_eq!(r2, 1);
+    }
+
+    /// Runs a block of code at `base` that sets r2 to 3, modifies it, and returns the value of
+    /// r2 after running the block again.
+    fn run_modified_code(base: u32, modify: impl FnOnce(&mut GbaMemory)) -> u32 {
+        let mut memory = GbaMemory::new(Scheduler::default());
+        let code = [
+            0xE3A00001u32, // mov r0, #1
+            0xE3A01002,    // mov r1, #2
+            0xE3A02003,    // mov r2, #3
+            0xEAFFFFFE,    // b .
+        ];
+        for (index, opcode) in code.into_iter().enumerate() {
+            memory.store32(base + index as u32 * 4, opcode, AccessType::NonSeq);
+        }
+
+        let mut cpu = arm::Cpu::new(arm::Isa::Arm, arm::CpuMode::System, &mut memory);
+        cpu.branch(base, &mut memory);
+        cpu.run(&mut memory, arm::Cycles::from(100u32));
+        assert_eq!(cpu.registers.read(2), 3);
+
+        modify(&mut memory);
+        cpu.branch(base, &mut memory);
+        cpu.run(&mut memory, arm::Cycles::from(100u32));
+        cpu.registers.read(2)
+    }
 }
@@ -4,100 +4,142 @@ use util::mem::{read_u16, read_u32};
 
 impl GbaMemory {
     pub(super) fn load32_gamepak(
-        &self,
+        &mut self,
         address: u32,
         waitstate: u8,
-        mut access: AccessType,
+        access: AccessType,
     ) -> (u32, Waitstates) {
-        let masked = (address & ROM_MAX_MASK) as usize;
-        let value = if masked < self.rom.len() {
-            read_u32(&self.rom, masked)
-        } else {
-            0
-        };
-
-        gamepak_access_fix(address, &mut access);
-        let wait = self.gamepak_waitstates[((waitstate as usize) << 1) + (access as usize)]
-            + self.gamepak_waitstates[((waitstate as usize) << 1) + 1];
-
-        (value, wait)
+        self.prefetch.invalidate();
+        let wait = self.gamepak_wait(address, waitstate, access);
+        (self.rom_u32(address), wait)
     }
 
     pub(super) fn load16_gamepak(
-        &self,
+        &mut self,
         address: u32,
         waitstate: u8,
-        mut access: AccessType,
+        access: AccessType,
     ) -> (u16, Waitstates) {
-        let masked = (address & ROM_MAX_MASK) as usize;
-        let value = if masked < self.rom.len() {
-            read_u16(&self.rom, masked)
-        } else {
-            0
-        };
-
-        gamepak_access_fix(address, &mut access);
-        let wait = self.gamepak_waitstates[((waitstate as usize) << 1) + (access as usize)]
-            + self.gamepak_waitstates[((waitstate as usize) << 1) + 1];
-
-        (value, wait)
+        self.prefetch.invalidate();
+        let wait = self.gamepak_wait(address, waitstate, access);
+        (self.rom_u16(address), wait)
     }
 
     pub(super) fn load8_gamepak(
-        &self,
+        &mut self,
         address: u32,
         waitstate: u8,
-        mut access: AccessType,
+        access: AccessType,
     ) -> (u8, Waitstates) {
-        let masked = (address & ROM_MAX_MASK) as usize;
-        let value = if masked < self.rom.len() {
-            self.rom[masked]
-        } else {
-            0
-        };
-
-        gamepak_access_fix(address, &mut access);
-        let wait = self.gamepak_waitstates[((waitstate as usize) << 1) + (access as usize)]
-            + self.gamepak_waitstates[((waitstate as usize) << 1) + 1];
+        self.prefetch.invalidate();
+        let value = (self.rom_u16(address & !0x1) >> ((address & 0x1) * 8)) as u8;
 
-        (value, wait)
+        (value, self.gamepak_wait(address, waitstate, access))
     }
 
     pub(super) fn store32_gamepak(
-        &self,
+        &mut self,
         address: u32,
         _value: u32,
         waitstate: u8,
-        mut access: AccessType,
+        access: AccessType,
     ) -> Waitstates {
-        gamepak_access_fix(address, &mut access);
-        self.gamepak_waitstates[((waitstate as usize) << 1) + (access as usize)]
-            + self.gamepak_waitstates[((waitstate as usize) << 1) + 1]
+        self.prefetch.invalidate();
+        self.gamepak_wait(address, waitstate, access)
     }
 
     pub(super) fn store16_gamepak(
-        &self,
+        &mut self,
         address: u32,
         _value: u16,
         waitstate: u8,
-        mut access: AccessType,
+        access: AccessType,
     ) -> Waitstates {
-        gamepak_access_fix(address, &mut access);
-        self.gamepak_waitstates[((waitstate as usize) << 1) + (access as usize)]
-            + self.gamepak_waitstates[((waitstate as usize) << 1) + 1]
+        self.prefetch.invalidate();
+        self.gamepak_wait(address, waitstate, access)
     }
 
     pub(super) fn store8_gamepak(
-        &self,
+        &mut self,
         address: u32,
         _value: u8,
         waitstate: u8,
-        mut access: AccessType,
+        access: AccessType,
     ) -> Waitstates {
+        self.prefetch.invalidate();
+        self.gamepak_wait(address, waitstate, access)
+    }
+
+    /// Fetches an ARM opcode from ROM through the prefetch buffer.
+    pub(super) fn fetch32_gamepak(
+        &mut self,
+        address: u32,
+        access: AccessType,
+    ) -> (u32, Waitstates) {
+        let address = address & !0x3;
+        (
+            self.rom_u32(address),
+            self.prefetched_wait(address, access, 2),
+        )
+    }
+
+    /// Fetches a THUMB opcode from ROM through the prefetch buffer.
+    pub(super) fn fetch16_gamepak(
+        &mut self,
+        address: u32,
+        access: AccessType,
+    ) -> (u16, Waitstates) {
+        let address = address & !0x1;
+        (
+            self.rom_u16(address),
+            self.prefetched_wait(address, access, 1),
+        )
+    }
+
+    /// Returns the waitstates for a sequential opcode fetch that can be served by the prefetch
+    /// buffer. Anything else is a normal access after which prefetching restarts from the
+    /// following halfword.
+    fn prefetched_wait(&mut self, address: u32, access: AccessType, halfwords: u32) -> Waitstates {
+        if access.is_seq() {
+            if let Some(cycles) = self.prefetch.take(address, halfwords) {
+                return Waitstates::from((cycles - 1) as u8);
+            }
+        }
+
+        let waitstate = ((address >> 25) - 4) as u8;
+        let wait = self.gamepak_wait(address, waitstate, access);
+        // The prefetcher reads each halfword with a sequential access.
+        let next = address.wrapping_add(halfwords * 2);
+        let duration = u32::from(self.gamepak_wait(next, waitstate, AccessType::Seq)) + 1;
+        self.prefetch.restart(next, duration);
+        wait
+    }
+
+    fn gamepak_wait(&self, address: u32, waitstate: u8, mut access: AccessType) -> Waitstates {
         gamepak_access_fix(address, &mut access);
         self.gamepak_waitstates[((waitstate as usize) << 1) + (access as usize)]
             + self.gamepak_waitstates[((waitstate as usize) << 1) + 1]
     }
+
+    /// Reads past the end of the ROM return the halfword address (address / 2) because the
+    /// lower 16 address lines are shared with the data lines.
+    pub(super) fn rom_u32(&self, address: u32) -> u32 {
+        let masked = (address & ROM_MAX_MASK) as usize;
+        if masked + 3 < self.rom.len() {
+            read_u32(&self.rom, masked)
+        } else {
+            self.rom_u16(address) as u32 | ((self.rom_u16(address.wrapping_add(2)) as u32) << 16)
+        }
+    }
+
+    pub(super) fn rom_u16(&self, address: u32) -> u16 {
+        let masked = (address & ROM_MAX_MASK) as usize;
+        if masked + 1 < self.rom.len() {
+            read_u16(&self.rom, masked)
+        } else {
+            (address >> 1) as u16
+        }
+    }
 }
 
 /// Changes the given access type to nonsequential if the address is the start of
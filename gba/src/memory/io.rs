@@ -122,13 +122,13 @@ impl GbaMemory {
             DMA3CNT_H => self.ioregs.dma[3].control.into(),
 
             // Timers
-            TM0CNT_L => self.read_timer_counter(0),
+            TM0CNT_L => self.read_timer_counter::<VIEW>(0),
             TM0CNT_H => self.ioregs.timers[0].control.into(),
-            TM1CNT_L => self.read_timer_counter(1),
+            TM1CNT_L => self.read_timer_counter::<VIEW>(1),
             TM1CNT_H => self.ioregs.timers[1].control.into(),
-            TM2CNT_L => self.read_timer_counter(2),
+            TM2CNT_L => self.read_timer_counter::<VIEW>(2),
             TM2CNT_H => self.ioregs.timers[2].control.into(),
-            TM3CNT_L => self.read_timer_counter(3),
+            TM3CNT_L => self.read_timer_counter::<VIEW>(3),
             TM3CNT_H => self.ioregs.timers[3].control.into(),
 
             // Serial Communications (1)
@@ -159,12 +159,13 @@ impl GbaMemory {
             IME => self.ioregs.ime.lo(),
             IME_HI => self.ioregs.ime.hi(),
 
+            _ if VIEW => 0,
             _ => {
                 log::warn!(
                     "attempted to read from unused/readonly IO address 0x{:08X}",
                     address
                 );
-                0
+                (self.open_bus() >> ((address & 0x2) * 8)) as u16
             }
         }
     }
@@ -274,7 +275,11 @@ impl GbaMemory {
                         .schedule(audio::wave_stop_playback, 0, EventTag::None);
                 }
             }
-            SOUND3CNT_H => self.ioregs.sound3cnt_h.set_preserve_bits(value),
+            SOUND3CNT_H => {
+                self.ioregs.sound3cnt_h.set_preserve_bits(value);
+                self.scheduler
+                    .schedule(audio::wave_volume_changed, 0, EventTag::None);
+            }
             SOUND3CNT_X => {
                 self.ioregs.sound3cnt_x.set_lo(value);
                 self.scheduler
@@ -289,8 +294,16 @@ impl GbaMemory {
                     .schedule(audio::psg_freq_control_changed::<4>, 0, EventTag::None);
             }
             SOUND4CNT_H_H => self.ioregs.sound4cnt_h.set_hi(value),
-            SOUNDCNT_L => self.ioregs.soundcnt_l.set_preserve_bits(value),
-            SOUNDCNT_H => self.ioregs.soundcnt_h.set_preserve_bits(value),
+            SOUNDCNT_L => {
+                self.ioregs.soundcnt_l.set_preserve_bits(value);
+                self.scheduler
+                    .schedule(audio::psg_mixing_changed, 0, EventTag::None);
+            }
+            SOUNDCNT_H => {
+                self.ioregs.soundcnt_h.set_preserve_bits(value);
+                self.scheduler
+                    .schedule(audio::dma_sound_mixing_changed, 0, EventTag::None);
+            }
             SOUNDCNT_X => self.ioregs.soundcnt_x.set_lo(value),
             SOUNDCNT_X_H => self.ioregs.soundcnt_x.set_hi(value),
             SOUNDBIAS => {
@@ -426,6 +439,50 @@ impl GbaMemory {
         }
     }
 
+    /// Writes to an IO register without triggering any of the side effects of a CPU write.
+    /// Registers that schedule events, start DMAs or timers, or acknowledge interrupts when
+    /// written are set directly instead.
+    pub(super) fn poke16_io(&mut self, address: u32, value: u16) {
+        match address {
+            SOUND1CNT_L => self.ioregs.sound1cnt_l.set_preserve_bits(value),
+            SOUND1CNT_H => self.ioregs.sound1cnt_h.set_preserve_bits(value),
+            SOUND1CNT_X => self.ioregs.sound1cnt_x.set_lo(value),
+            SOUND2CNT_L => self.ioregs.sound2cnt_l.set_preserve_bits(value),
+            SOUND2CNT_H => self.ioregs.sound2cnt_h.set_lo(value),
+            SOUND3CNT_L => self.ioregs.sound3cnt_l.set_preserve_bits(value),
+            SOUND3CNT_H => self.ioregs.sound3cnt_h.set_preserve_bits(value),
+            SOUND3CNT_X => self.ioregs.sound3cnt_x.set_lo(value),
+            SOUND4CNT_H => self.ioregs.sound4cnt_h.set_lo(value),
+            SOUNDCNT_L => self.ioregs.soundcnt_l.set_preserve_bits(value),
+            SOUNDCNT_H => self.ioregs.soundcnt_h.set_preserve_bits(value),
+            SOUNDBIAS => self.ioregs.soundbias.set_lo(value),
+            WAVE_RAM0_L..=WAVE_RAM3_H => {
+                let bank = !self.ioregs.sound3cnt_l.bank_number() & 0x1;
+                self.ioregs
+                    .waveram
+                    .store16((address - WAVE_RAM0_L) / 2, value, bank)
+            }
+
+            // The FIFOs have no addressable storage.
+            FIFO_A_L | FIFO_A_H | FIFO_B_L | FIFO_B_H => {}
+
+            DMA0CNT_H => self.ioregs.dma[0].control.set_preserve_bits(value),
+            DMA1CNT_H => self.ioregs.dma[1].control.set_preserve_bits(value),
+            DMA2CNT_H => self.ioregs.dma[2].control.set_preserve_bits(value),
+            DMA3CNT_H => self.ioregs.dma[3].control.set_preserve_bits(value),
+
+            TM0CNT_H => self.ioregs.timers[0].control.set_preserve_bits(value),
+            TM1CNT_H => self.ioregs.timers[1].control.set_preserve_bits(value),
+            TM2CNT_H => self.ioregs.timers[2].control.set_preserve_bits(value),
+            TM3CNT_H => self.ioregs.timers[3].control.set_preserve_bits(value),
+
+            IF => self.ioregs.if_reg.set_preserve_bits(value),
+            POSTFLG => self.ioregs.postflg.set_preserve_bits(value as u8),
+
+            _ => self.store16_io(address, value),
+        }
+    }
+
     fn waveram_load16(&self, index: u32) -> u16 {
         let bank = !self.ioregs.sound3cnt_l.bank_number() & 0x1;
         self.ioregs.waveram.load16(index, bank)
@@ -481,7 +538,11 @@ impl GbaMemory {
         }
     }
 
-    fn read_timer_counter(&mut self, timer: usize) -> u16 {
+    fn read_timer_counter<const VIEW: bool>(&mut self, timer: usize) -> u16 {
+        // Debugger views shouldn't stop idle loops from being detected.
+        if !VIEW {
+            self.side_effects = true;
+        }
         crate::timers::flush(&mut self.ioregs.timers[timer], self.ioregs.time);
         self.ioregs.timers[timer].counter()
     }
@@ -563,6 +624,9 @@ impl GbaMemory {
         self.gamepak_waitstates[5] = WS2_SEQ_VALUES[waitcnt.bit(10) as usize].into();
 
         self.prefetch_enabled = waitcnt.is_bit_set(14);
+        if !self.prefetch_enabled {
+            self.prefetch.invalidate();
+        }
     }
 }
 
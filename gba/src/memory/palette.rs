@@ -2,6 +2,7 @@ use util::mem::{read_u16, read_u32, write_u16, write_u32};
 
 use super::{PAL_MASK, PAL_SIZE};
 
+#[derive(Clone)]
 pub struct Palette {
     pub(crate) data: [u8; PAL_SIZE as usize],
 }
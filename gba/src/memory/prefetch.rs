@@ -0,0 +1,120 @@
+/// Maximum number of halfwords held by the prefetch buffer.
+const CAPACITY: u32 = 8;
+
+/// The GamePak prefetch buffer (WAITCNT bit 14).
+///
+/// While the CPU is executing from ROM and isn't using the GamePak bus (internal cycles and
+/// accesses to other regions), the prefetcher reads the halfwords that follow the last opcode
+/// fetched from ROM into an 8 halfword buffer. Sequential opcode fetches that hit the buffer take
+/// a single cycle. If the opcode is still being read the fetch only waits for the rest of that
+/// read instead of starting a new access.
+#[derive(Default)]
+pub struct Prefetcher {
+    /// False if the buffer was invalidated and nothing is being prefetched.
+    active: bool,
+    /// Address of the first halfword in the buffer.
+    head: u32,
+    /// Number of halfwords in the buffer.
+    count: u32,
+    /// Cycles spent reading the halfword after the last one in the buffer.
+    progress: u32,
+    /// Cycles required to read a single halfword (the sequential access time).
+    duration: u32,
+}
+
+impl Prefetcher {
+    /// Empties the buffer and starts prefetching from `address`. `duration` is the number of
+    /// cycles required to read each halfword.
+    pub fn restart(&mut self, address: u32, duration: u32) {
+        self.active = true;
+        self.head = address;
+        self.count = 0;
+        self.progress = 0;
+        self.duration = duration;
+    }
+
+    /// Empties the buffer and stops prefetching until the next opcode is fetched from ROM.
+    #[inline]
+    pub fn invalidate(&mut self) {
+        self.active = false;
+    }
+
+    /// Lets the prefetcher use the GamePak bus for some number of cycles.
+    #[inline]
+    pub fn advance(&mut self, cycles: u32) {
+        if !self.active || self.count == CAPACITY {
+            return;
+        }
+
+        self.progress += cycles;
+        let filled = (self.progress / self.duration).min(CAPACITY - self.count);
+        self.count += filled;
+        self.progress -= filled * self.duration;
+        if self.count == CAPACITY {
+            self.progress = 0;
+        }
+    }
+
+    /// Takes the opcode made up of `halfwords` halfwords at `address` out of the buffer, waiting
+    /// for it to finish being read if necessary. Returns the number of cycles the fetch took, or
+    /// `None` if the opcode isn't the next one being prefetched.
+    pub fn take(&mut self, address: u32, halfwords: u32) -> Option<u32> {
+        if !self.active || self.head != address {
+            return None;
+        }
+
+        let cycles = if self.count >= halfwords {
+            self.count -= halfwords;
+            self.head = self.head.wrapping_add(halfwords * 2);
+            self.advance(1);
+            1
+        } else {
+            let remaining = (halfwords - self.count) * self.duration - self.progress;
+            self.count = 0;
+            self.progress = 0;
+            self.head = self.head.wrapping_add(halfwords * 2);
+            remaining.max(1)
+        };
+
+        Some(cycles)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn fills_during_stalls() {
+        let mut prefetcher = Prefetcher::default();
+        prefetcher.restart(0x08000002, 4);
+        prefetcher.advance(5);
+
+        // The first halfword is ready and the second one needs 3 more cycles.
+        assert_eq!(prefetcher.take(0x08000002, 2), Some(3));
+        assert_eq!(prefetcher.take(0x08000008, 1), None);
+        assert_eq!(prefetcher.take(0x08000006, 1), Some(4));
+    }
+
+    #[test]
+    pub fn stops_when_full() {
+        let mut prefetcher = Prefetcher::default();
+        prefetcher.restart(0x08000000, 10);
+        prefetcher.advance(100);
+
+        for index in 0..4 {
+            assert_eq!(prefetcher.take(0x08000000 + index * 4, 2), Some(1));
+        }
+        // Only 4 cycles were spent reading the next halfword while the buffer was emptied.
+        assert_eq!(prefetcher.take(0x08000010, 2), Some(16));
+    }
+
+    #[test]
+    pub fn invalidated_buffer_misses() {
+        let mut prefetcher = Prefetcher::default();
+        prefetcher.restart(0x08000000, 1);
+        prefetcher.advance(4);
+        prefetcher.invalidate();
+        assert_eq!(prefetcher.take(0x08000000, 1), None);
+    }
+}
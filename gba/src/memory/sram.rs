@@ -57,6 +57,11 @@ impl GbaMemory {
         unimplemented_sram_store();
         self.sram_waitstates
     }
+
+    pub(super) fn poke8_sram(&mut self, _address: u32, _value: u8) {
+        // There is no backup media storage to write to yet.
+        unimplemented_sram_store();
+    }
 }
 
 fn unimplemented_sram_load() {
@@ -2,7 +2,7 @@ use crate::memory::io::PSGChannel;
 
 use super::Gba;
 use arm::Cycles;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
 use std::collections::VecDeque;
 use std::rc::Rc;
 
@@ -84,66 +84,129 @@ impl EventTag {
 
 pub type EventFn = fn(gba: &mut Gba);
 
+/// Queue of events shared by all of the parts of the GBA that need to schedule them.
+///
+/// The current time and the time of the next event are kept outside of the event queue so that
+/// the hot path in [`Gba::step`] (advancing time and checking if an event is due) never has to
+/// borrow the queue.
 #[derive(Default, Clone)]
 pub struct Scheduler {
-    inner: Rc<RefCell<Inner>>,
+    shared: Rc<Shared>,
+}
+
+struct Shared {
+    inner: RefCell<Inner>,
+    time: Cell<u64>,
+    /// Time of the first event in the queue, or `u64::MAX` if there are none.
+    next_event_time: Cell<u64>,
+}
+
+impl Default for Shared {
+    fn default() -> Self {
+        Shared {
+            inner: RefCell::default(),
+            time: Cell::new(0),
+            next_event_time: Cell::new(u64::MAX),
+        }
+    }
 }
 
 impl Scheduler {
     pub fn schedule(&self, callback: EventFn, cycles: impl Into<Cycles>, tag: EventTag) {
-        self.inner
-            .borrow_mut()
-            .schedule(cycles.into(), callback, tag);
+        self.with_inner(|inner, time| inner.schedule(time, cycles.into(), callback, tag));
     }
 
     pub fn contains_tag(&self, tag: EventTag) -> bool {
-        self.inner
+        self.shared
+            .inner
             .borrow()
             .contains_matching(|event| event.tag == tag)
     }
 
     pub fn reschedule(&self, callback: EventFn, cycles: impl Into<Cycles>, tag: EventTag) {
-        let mut sched = self.inner.borrow_mut();
-        sched.unschedule(tag);
-        sched.schedule(cycles.into(), callback, tag);
+        self.with_inner(|inner, time| {
+            inner.unschedule(tag);
+            inner.schedule(time, cycles.into(), callback, tag);
+        });
     }
 
     pub fn reschedule_ealier(&self, callback: EventFn, cycles: impl Into<Cycles>, tag: EventTag) {
-        self.inner
-            .borrow_mut()
-            .reschedule_ealier(cycles.into(), callback, tag);
+        self.with_inner(|inner, time| inner.reschedule_ealier(time, cycles.into(), callback, tag));
     }
 
     pub fn unschedule(&self, tag: EventTag) {
-        self.inner.borrow_mut().unschedule(tag);
+        self.with_inner(|inner, _| inner.unschedule(tag));
     }
 
     pub fn unschedule_matching<F>(&self, predicate: F)
     where
         F: FnMut(&Event) -> bool,
     {
-        self.inner.borrow_mut().unschedule_matching(predicate);
+        self.with_inner(|inner, _| inner.unschedule_matching(predicate));
     }
 
+    /// Pops the next event that is due at or before `new_time` and returns it along with the time
+    /// that it was scheduled for. The scheduler's time is set to the time of the returned event,
+    /// or to `new_time` if there are no more events due.
     pub fn next(&self, new_time: u64) -> Option<(EventFn, u64)> {
-        self.inner.borrow_mut().next(new_time)
+        if new_time < self.shared.next_event_time.get() {
+            self.shared.time.set(new_time);
+            return None;
+        }
+
+        let event = self.with_inner(|inner, _| inner.events.pop_front())?;
+        self.shared.time.set(event.when);
+        Some((event.callback, event.when))
+    }
+
+    /// Advances the scheduler's time without running any events. Events that become due are run
+    /// by the next call to [`Scheduler::next`].
+    #[inline]
+    pub fn set_time(&self, now: u64) {
+        self.shared.time.set(now);
+    }
+
+    /// Returns true if there is an event due at or before `now`.
+    #[inline]
+    pub fn is_event_due(&self, now: u64) -> bool {
+        now >= self.shared.next_event_time.get()
+    }
+
+    /// Returns true if there are any events scheduled.
+    #[inline]
+    pub fn has_events(&self) -> bool {
+        self.shared.next_event_time.get() != u64::MAX
     }
 
     pub fn cycles_until_next_event(&self, now: u64) -> Option<Cycles> {
-        self.inner.borrow().cycles_until_next_event(now)
+        let next_event_time = self.shared.next_event_time.get();
+        if next_event_time == u64::MAX {
+            None
+        } else {
+            Some(Cycles::from(next_event_time.saturating_sub(now) as u32))
+        }
     }
 
     pub fn time(&self) -> u64 {
-        self.inner.borrow().time
+        self.shared.time.get()
     }
 
     pub(crate) fn clear(&self) {
-        self.inner.borrow_mut().clear();
+        self.with_inner(|inner, _| inner.clear());
+    }
+
+    /// Modifies the event queue and updates the cached time of the next event afterwards.
+    fn with_inner<T>(&self, f: impl FnOnce(&mut Inner, u64) -> T) -> T {
+        let mut inner = self.shared.inner.borrow_mut();
+        let result = f(&mut inner, self.shared.time.get());
+        let next_event_time = inner.events.front().map_or(u64::MAX, |event| event.when);
+        self.shared.next_event_time.set(next_event_time);
+        result
     }
 
     #[cfg(test)]
     pub fn dump(&self) {
-        for (idx, event) in self.inner.borrow().events.iter().enumerate() {
+        for (idx, event) in self.shared.inner.borrow().events.iter().enumerate() {
             println!("EVENT{idx}: {}", event.when);
         }
     }
@@ -158,12 +221,11 @@ pub struct Event {
 #[derive(Default)]
 struct Inner {
     events: VecDeque<Event>,
-    time: u64,
 }
 
 impl Inner {
-    fn schedule(&mut self, cycles: arm::Cycles, cb: EventFn, tag: EventTag) {
-        let when = self.time + u32::from(cycles) as u64;
+    fn schedule(&mut self, time: u64, cycles: arm::Cycles, cb: EventFn, tag: EventTag) {
+        let when = time + u32::from(cycles) as u64;
 
         let mut insert_idx = self.events.len();
         for (idx, event) in self.events.iter().enumerate() {
@@ -182,8 +244,8 @@ impl Inner {
         self.events.insert(insert_idx, event);
     }
 
-    fn reschedule_ealier(&mut self, cycles: arm::Cycles, cb: EventFn, tag: EventTag) {
-        let when = self.time + u32::from(cycles) as u64;
+    fn reschedule_ealier(&mut self, time: u64, cycles: arm::Cycles, cb: EventFn, tag: EventTag) {
+        let when = time + u32::from(cycles) as u64;
 
         let mut found_matching_tag = false;
         let mut unscheduled_matching = false;
@@ -196,7 +258,7 @@ impl Inner {
         });
 
         if unscheduled_matching || !found_matching_tag {
-            self.schedule(cycles, cb, tag);
+            self.schedule(time, cycles, cb, tag);
         }
     }
 
@@ -218,34 +280,6 @@ impl Inner {
         self.events.retain(|event| event.tag != tag);
     }
 
-    fn next(&mut self, new_time: u64) -> Option<(EventFn, u64)> {
-        if let Some(event) = self.events.front() {
-            if event.when > new_time {
-                self.time = new_time;
-                return None;
-            }
-        } else {
-            self.time = new_time;
-            return None;
-        }
-
-        let event = self.events.pop_front().unwrap();
-        self.time = event.when;
-        Some((event.callback, event.when))
-    }
-
-    fn cycles_until_next_event(&self, now: u64) -> Option<Cycles> {
-        if let Some(event) = self.events.front() {
-            if event.when > now {
-                Some(Cycles::from((event.when - now) as u32))
-            } else {
-                Some(Cycles::ZERO)
-            }
-        } else {
-            None
-        }
-    }
-
     fn clear(&mut self) {
         self.events.clear();
     }
@@ -299,4 +333,29 @@ mod test {
             unsafe { &mut DATA }
         }
     }
+
+    #[test]
+    fn scheduling_after_set_time() {
+        let scheduler = Scheduler::default();
+        assert!(!scheduler.is_event_due(u64::MAX - 1));
+        assert!(!scheduler.has_events());
+        assert_eq!(scheduler.cycles_until_next_event(0), None);
+
+        scheduler.set_time(100);
+        scheduler.schedule(|_| {}, 10u32, EventTag::None);
+        assert!(!scheduler.is_event_due(109));
+        assert!(scheduler.is_event_due(110));
+        assert_eq!(scheduler.cycles_until_next_event(105), Some(5u32.into()));
+
+        // Scheduling an earlier event updates the next event time.
+        scheduler.schedule(|_| {}, 0u32, EventTag::None);
+        assert!(scheduler.is_event_due(100));
+
+        assert_eq!(scheduler.next(120).map(|(_, when)| when), Some(100));
+        assert_eq!(scheduler.next(120).map(|(_, when)| when), Some(110));
+        assert!(scheduler.next(120).is_none());
+        assert_eq!(scheduler.time(), 120);
+        assert!(!scheduler.has_events());
+        assert_eq!(scheduler.cycles_until_next_event(120), None);
+    }
 }
@@ -5,10 +5,11 @@ mod mode2;
 mod mode3;
 mod mode4;
 mod mode5;
-mod obj;
+pub mod obj;
 mod text;
 
 use arm::Cycles;
+use util::{array, primitive_enum};
 
 use crate::{
     dma::dma_on_timing,
@@ -18,7 +19,7 @@ use crate::{
     Gba, GbaMemory, State,
 };
 
-use self::line::LineBuffer;
+use self::{line::LineBuffer, obj::ObjLineStats};
 
 pub const SCREEN_WIDTH: usize = 240;
 pub const SCREEN_HEIGHT: usize = 160;
@@ -27,10 +28,57 @@ pub const SCREEN_PIXEL_COUNT: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
 const HDRAW_CYCLES: Cycles = Cycles::new(960);
 const HBLANK_CYCLES: Cycles = Cycles::new(272);
 
+primitive_enum! {
+    /// A layer of the final image that can be force-disabled for debugging, regardless of the
+    /// value of DISPCNT.
+    pub enum Layer: u8 {
+        Bg0,
+        Bg1,
+        Bg2,
+        Bg3,
+        Obj,
+        Windows,
+        Effects,
+    }
+}
+
+impl Layer {
+    pub const ALL: [Layer; 7] = [
+        Layer::Bg0,
+        Layer::Bg1,
+        Layer::Bg2,
+        Layer::Bg3,
+        Layer::Obj,
+        Layer::Windows,
+        Layer::Effects,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Layer::Bg0 => "BG0",
+            Layer::Bg1 => "BG1",
+            Layer::Bg2 => "BG2",
+            Layer::Bg3 => "BG3",
+            Layer::Obj => "OBJ",
+            Layer::Windows => "Windows",
+            Layer::Effects => "Effects",
+        }
+    }
+}
+
+/// The individual BG0-BG3 and OBJ layers of a frame, before windows, priorities, and special
+/// effects are applied. Opaque pixels have bit 15 set.
+pub type LayerBuffers = [[u16; SCREEN_PIXEL_COUNT]; 5];
+
 pub struct GbaVideo {
     scheduler: Scheduler,
     pub(crate) screen: [u16; SCREEN_PIXEL_COUNT],
+    obj_line_stats: [ObjLineStats; SCREEN_HEIGHT],
     skip_render: bool,
+
+    /// Bit N is set if [`Layer`] N has been forced off.
+    disabled_layers: u8,
+    layer_buffers: Option<Box<LayerBuffers>>,
 }
 
 impl GbaVideo {
@@ -39,7 +87,10 @@ impl GbaVideo {
         GbaVideo {
             scheduler,
             screen,
+            obj_line_stats: [ObjLineStats::default(); SCREEN_HEIGHT],
             skip_render: false,
+            disabled_layers: 0,
+            layer_buffers: None,
         }
     }
 
@@ -105,7 +156,13 @@ impl GbaVideo {
             let output_buf = &mut self.screen[output_buf_start..output_buf_end];
 
             if !self.skip_render {
-                Self::render_line(line, output_buf, mem);
+                let layer_outputs = self.layer_buffers.as_deref_mut().map(|buffers| {
+                    buffers
+                        .each_mut()
+                        .map(|buffer| &mut buffer[output_buf_start..output_buf_end])
+                });
+                self.obj_line_stats[line as usize] =
+                    Self::render_line(line, output_buf, layer_outputs, self.disabled_layers, mem);
             }
         }
 
@@ -127,8 +184,15 @@ impl GbaVideo {
         }
     }
 
-    fn render_line(line: u16, output: &mut [u16], mem: &GbaMemory) {
+    fn render_line(
+        line: u16,
+        output: &mut [u16],
+        layer_outputs: Option<[&mut [u16]; 5]>,
+        disabled_layers: u8,
+        mem: &GbaMemory,
+    ) -> ObjLineStats {
         let mut buf = LineBuffer::default();
+        buf.set_disabled_layers(disabled_layers);
 
         match mem.ioregs.dispcnt.bg_mode() {
             0 => mode0::render(line, &mut buf, &mem.ioregs, &mem.vram),
@@ -140,18 +204,57 @@ impl GbaVideo {
             _ => {}
         }
 
+        let mut obj_stats = ObjLineStats::default();
         if mem.ioregs.dispcnt.display_obj() {
-            obj::render(line, &mut buf, &mem.ioregs, &mem.oam, &mem.vram);
+            obj_stats = obj::render(line, &mut buf, &mem.ioregs, &mem.oam, &mem.vram);
         }
 
         buf.render(output, &mem.ioregs, &mem.palette);
+        if let Some(layer_outputs) = layer_outputs {
+            buf.render_layers(layer_outputs, &mem.palette);
+        }
+        obj_stats
     }
 
     pub fn screen(&self) -> &[u16; SCREEN_PIXEL_COUNT] {
         &self.screen
     }
 
+    /// Returns the OBJ cycle usage of each line from the most recently rendered frame.
+    pub fn obj_line_stats(&self) -> &[ObjLineStats; SCREEN_HEIGHT] {
+        &self.obj_line_stats
+    }
+
     pub fn set_skip_render(&mut self, skip: bool) {
         self.skip_render = skip;
     }
+
+    /// Forces a layer off (or allows it to be displayed again) independent of DISPCNT. Disabling
+    /// [`Layer::Windows`] makes every layer visible everywhere and disabling [`Layer::Effects`]
+    /// turns off blending, brightness effects, and semi-transparent OBJs.
+    pub fn set_layer_enabled(&mut self, layer: Layer, enabled: bool) {
+        if enabled {
+            self.disabled_layers &= !(1 << layer as u8);
+        } else {
+            self.disabled_layers |= 1 << layer as u8;
+        }
+    }
+
+    pub fn layer_enabled(&self, layer: Layer) -> bool {
+        (self.disabled_layers & (1 << layer as u8)) == 0
+    }
+
+    /// Enables or disables capturing each BG and OBJ layer into its own buffer while rendering.
+    pub fn set_layer_capture(&mut self, capture: bool) {
+        if !capture {
+            self.layer_buffers = None;
+        } else if self.layer_buffers.is_none() {
+            self.layer_buffers = Some(array::boxed_copied([0; SCREEN_PIXEL_COUNT]));
+        }
+    }
+
+    /// Returns the captured layers if layer capture is enabled.
+    pub fn layer_buffers(&self) -> Option<&LayerBuffers> {
+        self.layer_buffers.as_deref()
+    }
 }
@@ -1,10 +1,15 @@
 use util::bits::Bits as _;
 
-use crate::memory::{
-    io::{AlphaBlendingCoeff, BrightnessCoeff, Effect, IoRegisters},
-    palette::Palette,
+use crate::{
+    memory::{
+        io::{AlphaBlendingCoeff, BrightnessCoeff, Effect, IoRegisters},
+        palette::Palette,
+    },
+    SCREEN_WIDTH,
 };
 
+use super::Layer;
+
 pub const OBJ: usize = 4;
 pub const BACKDROP: usize = 5;
 
@@ -13,6 +18,9 @@ pub struct LineBuffer {
     pixels: [[u16; 240]; 5],
     objwin: LineBits,
     layer_attrs: [LayerAttrs; 5],
+
+    /// Bit N is set if [`Layer`] N has been forced off and should not be composited.
+    disabled_layers: u8,
 }
 
 impl Default for LineBuffer {
@@ -21,11 +29,20 @@ impl Default for LineBuffer {
             pixels: [[0; 240]; 5],
             objwin: LineBits::zeroes(),
             layer_attrs: [LayerAttrs::default(); 5],
+            disabled_layers: 0,
         }
     }
 }
 
 impl LineBuffer {
+    pub(crate) fn set_disabled_layers(&mut self, disabled_layers: u8) {
+        self.disabled_layers = disabled_layers;
+    }
+
+    fn layer_enabled(&self, layer: Layer) -> bool {
+        (self.disabled_layers & (1 << layer as u8)) == 0
+    }
+
     pub(crate) fn put(&mut self, layer: usize, x: usize, pixel: u16) {
         self.pixels[layer][x] = pixel | 0x8000;
     }
@@ -143,7 +160,7 @@ impl LineBuffer {
     }
 
     fn generate_window_mask(&self, layer: usize, ioregs: &IoRegisters) -> WindowMask {
-        if !ioregs.dispcnt.windows_enabled() {
+        if !ioregs.dispcnt.windows_enabled() || !self.layer_enabled(Layer::Windows) {
             return WindowMask::new_all_enabled();
         }
 
@@ -228,11 +245,15 @@ impl LineBuffer {
     }
 
     pub fn render(&self, output: &mut [u16], ioregs: &IoRegisters, palette: &Palette) {
+        let effects_enabled = self.layer_enabled(Layer::Effects);
         let mut pixels = [Self::backdrop_pixel(ioregs, palette); 240];
 
         for priority in (0..4).rev() {
             for bg in (0usize..4).rev() {
-                if !ioregs.dispcnt.display_bg(bg as _) || ioregs.bgcnt[bg].priority() != priority {
+                if !ioregs.dispcnt.display_bg(bg as _)
+                    || !self.layer_enabled(Layer::from(bg as u8))
+                    || ioregs.bgcnt[bg].priority() != priority
+                {
                     continue;
                 }
 
@@ -253,26 +274,36 @@ impl LineBuffer {
                     }
 
                     if let Some(color) = self.color_bg(bg, x, palette) {
-                        pixels[x].push(color, attrs.effects_mask(mask.effects(x)));
+                        pixels[x].push(
+                            color,
+                            attrs.effects_mask(effects_enabled && mask.effects(x)),
+                        );
                     }
                 });
             }
         }
 
         let obj_mask = self.generate_window_mask(OBJ, ioregs);
-        if ioregs.dispcnt.display_obj() {
+        if ioregs.dispcnt.display_obj() && self.layer_enabled(Layer::Obj) {
             (0..240).for_each(|x| {
                 if !obj_mask.visible(x) {
                     return;
                 }
 
                 if let Some((color, attrs)) = self.color_obj(x, palette) {
-                    pixels[x].place_obj(color, attrs.effects_mask(obj_mask.effects(x)));
+                    pixels[x].place_obj(
+                        color,
+                        attrs.effects_mask(effects_enabled && obj_mask.effects(x)),
+                    );
                 }
             });
         }
 
-        let effect = ioregs.bldcnt.effect();
+        let effect = if effects_enabled {
+            ioregs.bldcnt.effect()
+        } else {
+            Effect::None
+        };
         let bldalpha = ioregs.bldalpha;
         let bldy = ioregs.bldy;
 
@@ -281,6 +312,22 @@ impl LineBuffer {
             .for_each(|(x, p)| output[x] = p);
     }
 
+    /// Writes the colors of each individual BG and OBJ layer to its own output line without any
+    /// windows, priorities, or special effects applied. Opaque pixels have bit 15 set and
+    /// transparent pixels are written as 0.
+    pub fn render_layers(&self, outputs: [&mut [u16]; 5], palette: &Palette) {
+        for (layer, output) in outputs.into_iter().enumerate() {
+            for (x, output) in output.iter_mut().enumerate().take(SCREEN_WIDTH) {
+                let color = if layer == OBJ {
+                    self.color_obj(x, palette).map(|(color, _)| color)
+                } else {
+                    self.color_bg(layer, x, palette)
+                };
+                *output = color.map(|color| color | 0x8000).unwrap_or(0);
+            }
+        }
+    }
+
     fn render_pixel(
         &self,
         pixel: Pixel,
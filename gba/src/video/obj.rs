@@ -9,24 +9,28 @@ use util::{
 use super::line::LineBuffer;
 use crate::{
     memory::{
-        io::{IoRegisters, ObjCharVramMapping},
+        io::{IoRegisters, LCDControl, ObjCharVramMapping},
+        palette::Palette,
         OAM_SIZE, VRAM_SIZE,
     },
     video::line::{PixelAttrs, OBJ},
 };
 
-pub fn render(
+/// Renders all of the objects on a single line and returns the number of OBJ rendering cycles
+/// that were used along with the objects that could not be drawn because the cycle budget for the
+/// line was exhausted.
+pub(crate) fn render(
     line: u16,
     buf: &mut LineBuffer,
     ioregs: &IoRegisters,
     oam: &[u8; OAM_SIZE as usize],
     vram: &[u8; VRAM_SIZE as usize],
-) {
-    let mut cycles = if ioregs.dispcnt.hblank_interval_free() {
-        954
-    } else {
-        1210
+) -> ObjLineStats {
+    let mut stats = ObjLineStats {
+        cycles_available: obj_cycle_budget(ioregs.dispcnt),
+        ..ObjLineStats::default()
     };
+    let mut cycles = stats.cycles_available;
 
     let mut objects = [0u8; 128];
     let mut visible_objects = 0;
@@ -56,10 +60,6 @@ pub fn render(
     });
 
     for &mut obj_idx in objects {
-        if cycles == 0 {
-            break;
-        }
-
         let attrs_index = obj_idx as usize * 8;
         let attr0 = ObjAttr0::new(read_u16(oam, attrs_index));
         let attr1 = ObjAttr1::new(read_u16(oam, attrs_index + 2));
@@ -82,12 +82,18 @@ pub fn render(
             continue;
         }
 
-        let mut right = if let Some(right) =
+        // Objects that would have been on this line are still counted after the budget runs out
+        // so that they can be reported as dropped.
+        let right = if cycles > 0 {
             consume_obj_cycles(&mut cycles, attr0.rotscale(), display_width, left)
-        {
+        } else {
+            None
+        };
+        let mut right = if let Some(right) = right {
             right
         } else {
-            break;
+            stats.set_dropped(obj_idx as usize);
+            continue;
         };
 
         let in_bounds_horizontal = left < 240 || right < 240;
@@ -285,6 +291,87 @@ pub fn render(
             }
         }
     }
+
+    stats.cycles_used = stats.cycles_available - cycles;
+    stats
+}
+
+/// Returns the number of cycles that are available for rendering objects on a single line.
+pub fn obj_cycle_budget(dispcnt: LCDControl) -> u16 {
+    if dispcnt.hblank_interval_free() {
+        954
+    } else {
+        1210
+    }
+}
+
+/// OBJ rendering statistics for a single line.
+#[derive(Default, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct ObjLineStats {
+    /// The number of OBJ rendering cycles that were used by this line.
+    pub cycles_used: u16,
+
+    /// The number of OBJ rendering cycles that were available for this line.
+    /// This is 954 if "H-Blank Interval Free" is set in DISPCNT and 1210 otherwise.
+    pub cycles_available: u16,
+
+    /// Bit N is set if object N was on this line but could not be rendered at all because there
+    /// were not enough cycles left.
+    pub dropped: u128,
+}
+
+impl ObjLineStats {
+    fn set_dropped(&mut self, obj: usize) {
+        self.dropped |= 1 << obj;
+    }
+
+    pub fn is_dropped(&self, obj: usize) -> bool {
+        (self.dropped & (1 << obj)) != 0
+    }
+}
+
+/// Draws the characters of an object into `output` without applying any transformations or
+/// flipping. The output is `width * height` pixels (see [`ObjAttr1::size`]) in BGR555 format with
+/// bit 15 set for opaque pixels and cleared for transparent pixels.
+pub fn render_obj_preview(
+    attrs: (ObjAttr0, ObjAttr1, ObjAttr2),
+    mapping: ObjCharVramMapping,
+    vram: &[u8; VRAM_SIZE as usize],
+    palette: &Palette,
+    output: &mut Vec<u16>,
+) {
+    let (attr0, attr1, attr2) = attrs;
+    let (width, height) = attr1.size(attr0.shape());
+    let (width, height) = (width as usize, height as usize);
+    let tile_data = &vram[0x10000..];
+
+    let char_stride: usize = if mapping == ObjCharVramMapping::OneDimensional {
+        width / 8
+    } else if attr0.palette256() {
+        16
+    } else {
+        32
+    };
+    let first_tile_index = attr2.character_name() as usize;
+
+    output.clear();
+    output.reserve(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let color = if attr0.palette256() {
+                let tile = ((first_tile_index / 2) + ((y / 8) * char_stride) + (x / 8)) & 0x3FF;
+                let entry = tile_data[((tile * 64) + ((y % 8) * 8) + (x % 8)) & 0x7FFF];
+                (entry != 0).then(|| palette.get_obj256(entry))
+            } else {
+                let tile = (first_tile_index + ((y / 8) * char_stride) + (x / 8)) & 0x3FF;
+                let entry = (tile_data[((tile * 32) + ((y % 8) * 4) + (x % 8) / 2) & 0x7FFF]
+                    >> ((x % 2) << 2))
+                    & 0xF;
+                (entry != 0).then(|| palette.get_obj16(attr2.palette() as u8, entry))
+            };
+            output.push(color.map(|c| c | 0x8000).unwrap_or(0));
+        }
+    }
 }
 
 /// Consumes the cycles required to render and object line and returns the rightmost pixel's
@@ -317,7 +404,7 @@ fn consume_obj_cycles(cycles: &mut u16, rs: bool, width: u16, left: u16) -> Opti
 }
 
 bitfields! {
-    struct ObjAttr0: u16 {
+    pub struct ObjAttr0: u16 {
         [0,7]   y, set_y: u16,
         [8]     rotscale, set_rotscale: bool,
 
@@ -335,7 +422,7 @@ bitfields! {
 }
 
 bitfields! {
-    struct ObjAttr1: u16 {
+    pub struct ObjAttr1: u16 {
         [0,8]   x, set_x: u16,
 
         // When rotsccale flag is set in attr0:
@@ -376,7 +463,7 @@ impl ObjAttr1 {
 }
 
 bitfields! {
-    struct ObjAttr2: u16 {
+    pub struct ObjAttr2: u16 {
         [0,9]   character_name, set_character_name: u16,
         [10,11] priority, set_priority: u16,
         [12,15] palette, set_palette: u16,
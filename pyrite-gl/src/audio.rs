@@ -6,14 +6,25 @@ use cpal::{
 use crossbeam::queue::SegQueue;
 use gba::{Command, GbaAudioSampler};
 use glutin::event_loop::EventLoopProxy;
-use pyrite::GbaHandle;
-use std::sync::Arc;
+use pyrite::{
+    config::{AudioConfig, AudioSync},
+    sync::{self, DynamicRateControl, WaitScaler},
+    GbaHandle, Speed,
+};
+use std::sync::{
+    atomic::{AtomicBool, AtomicI64, Ordering},
+    Arc,
+};
 
 use crate::PyriteEvent;
 
 const COMMANDS_CHUNK_SIZE: usize = 64;
 
-pub(crate) fn run(gba: GbaHandle, proxy: EventLoopProxy<PyriteEvent>) -> anyhow::Result<Stream> {
+pub(crate) fn run(
+    gba: GbaHandle,
+    proxy: EventLoopProxy<PyriteEvent>,
+    audio_config: &AudioConfig,
+) -> anyhow::Result<Stream> {
     let host = cpal::default_host();
     let device = host
         .default_output_device()
@@ -23,14 +34,20 @@ pub(crate) fn run(gba: GbaHandle, proxy: EventLoopProxy<PyriteEvent>) -> anyhow:
         .context("error retrieving default output configuration")?;
     let commands_buffer_queue: Arc<SegQueue<[Command; COMMANDS_CHUNK_SIZE]>> =
         Arc::new(SegQueue::new());
+    let queued_cycles = Arc::new(AtomicI64::new(0));
+    let skipping = Arc::new(AtomicBool::new(false));
 
     log::debug!("audio sample rate: {}", config.sample_rate().0);
     log::debug!("audio channels: {}", config.channels());
     log::debug!("audio sample format: {:?}", config.sample_format());
     log::debug!("audio buffer size: {:?}", config.buffer_size());
 
-    let mut sound_source =
-        GbaSoundSource::new(config.sample_rate().0, Arc::clone(&commands_buffer_queue));
+    let mut sound_source = GbaSoundSource::new(
+        config.sample_rate().0,
+        Arc::clone(&commands_buffer_queue),
+        Arc::clone(&queued_cycles),
+        Arc::clone(&skipping),
+    );
     let channels = config.channels() as usize;
     let stream = device
         .build_output_stream(
@@ -43,23 +60,58 @@ pub(crate) fn run(gba: GbaHandle, proxy: EventLoopProxy<PyriteEvent>) -> anyhow:
         .context("failed to build output stream")?;
     stream.play().context("failed to play audio stream")?;
 
+    let sync = audio_config.sync.unwrap_or(AudioSync::DynamicRate);
+    let drc = DynamicRateControl::from_config(audio_config);
     let mut last_chunk_count = 0usize;
-    gba.on_frame(move |gba, _| {
-        let gba_audio_commands = gba.audio().commands();
-
-        // Check to make sure that we have at most 2 frames worth of commands
-        // waiting to be processed. If there are already two, then we just miss this
-        // frame of audio commands.
-        if commands_buffer_queue.len() <= last_chunk_count {
-            last_chunk_count = 0;
-            gba_audio_commands
-                .chunks(COMMANDS_CHUNK_SIZE)
-                .for_each(|src_chunk| {
-                    let mut chunk = [Command::Wait(0); COMMANDS_CHUNK_SIZE];
-                    (&mut chunk[..src_chunk.len()]).copy_from_slice(src_chunk);
-                    commands_buffer_queue.push(chunk);
-                    last_chunk_count += 1;
-                });
+    let mut wait_scaler = WaitScaler::default();
+    let mut scaled_commands = Vec::new();
+    gba.on_frame(move |gba, state| {
+        // Audio can't keep up with uncapped speeds so it is skipped, but everything other than
+        // the waits is still sent so that the channels are in the right state once it stops.
+        // Fixed speeds play back in real time instead, which changes the pitch.
+        scaled_commands.clear();
+        let speed = match state.speed {
+            Speed::Multiplier(speed) => speed,
+            Speed::Uncapped => {
+                skipping.store(true, Ordering::Release);
+                state.rate_adjustment = 1.0;
+                sync::skip_waits(gba.audio().commands(), &mut scaled_commands);
+                push_commands(&commands_buffer_queue, &scaled_commands);
+                return;
+            }
+        };
+        skipping.store(false, Ordering::Release);
+
+        wait_scaler.scale(gba.audio().commands(), speed, &mut scaled_commands);
+        let gba_audio_commands = &scaled_commands[..];
+
+        let send = match sync {
+            // Check to make sure that we have at most 2 frames worth of commands
+            // waiting to be processed. If there are already two, then we just miss this
+            // frame of audio commands.
+            AudioSync::Video => commands_buffer_queue.len() <= last_chunk_count,
+
+            // Adjust the emulation speed to keep the queue at the target latency instead. Audio
+            // is only dropped if far too much has been queued, e.g. while skipping frames.
+            AudioSync::DynamicRate => {
+                let fill = queued_cycles.load(Ordering::Acquire).max(0) as f64
+                    / gba::Gba::CYCLES_PER_FRAME as f64;
+                state.rate_adjustment = drc.rate(fill);
+                !drc.overfilled(fill)
+            }
+        };
+
+        if send {
+            let cycles = gba_audio_commands
+                .iter()
+                .map(|command| match command {
+                    Command::Wait(cycles) => *cycles as i64,
+                    _ => 0,
+                })
+                .sum::<i64>();
+            queued_cycles.fetch_add(cycles, Ordering::AcqRel);
+
+            last_chunk_count = push_commands(&commands_buffer_queue, gba_audio_commands);
         }
     });
 
@@ -72,20 +124,45 @@ pub(crate) fn run(gba: GbaHandle, proxy: EventLoopProxy<PyriteEvent>) -> anyhow:
     Ok(stream)
 }
 
+/// Queues commands for the audio thread in fixed size chunks, padding the last one with empty
+/// waits. Returns the number of chunks that were queued.
+fn push_commands(queue: &SegQueue<[Command; COMMANDS_CHUNK_SIZE]>, commands: &[Command]) -> usize {
+    commands
+        .chunks(COMMANDS_CHUNK_SIZE)
+        .map(|src_chunk| {
+            let mut chunk = [Command::Wait(0); COMMANDS_CHUNK_SIZE];
+            chunk[..src_chunk.len()].copy_from_slice(src_chunk);
+            queue.push(chunk);
+        })
+        .count()
+}
+
 struct GbaSoundSource {
     sampler: GbaAudioSampler,
     commands: Box<[Command; COMMANDS_CHUNK_SIZE]>,
     commands_idx: usize,
     commands_buffer: Arc<SegQueue<[Command; COMMANDS_CHUNK_SIZE]>>,
+    /// GBA cycles worth of audio that have been queued but not played yet.
+    queued_cycles: Arc<AtomicI64>,
+    /// Set while audio is being skipped. Silence is output and queued commands are applied to
+    /// the sampler without waiting.
+    skipping: Arc<AtomicBool>,
 }
 
 impl GbaSoundSource {
-    fn new(frequency: u32, commands_buffer: Arc<SegQueue<[Command; COMMANDS_CHUNK_SIZE]>>) -> Self {
+    fn new(
+        frequency: u32,
+        commands_buffer: Arc<SegQueue<[Command; COMMANDS_CHUNK_SIZE]>>,
+        queued_cycles: Arc<AtomicI64>,
+        skipping: Arc<AtomicBool>,
+    ) -> Self {
         GbaSoundSource {
             sampler: GbaAudioSampler::new(frequency),
             commands: Box::new([Command::Wait(0); COMMANDS_CHUNK_SIZE]),
             commands_idx: 0,
             commands_buffer,
+            queued_cycles,
+            skipping,
         }
     }
 
@@ -110,10 +187,31 @@ impl GbaSoundSource {
         }
     }
 
+    /// Applies everything that has been queued to the sampler without playing any of it.
+    fn skip_queued_commands(&mut self) {
+        while let Some(command) = self.next_command() {
+            if let Command::Wait(cycles) = command {
+                self.queued_cycles
+                    .fetch_sub(cycles as i64, Ordering::AcqRel);
+            } else {
+                self.sampler.command(command);
+            }
+        }
+    }
+
     fn output(&mut self, channels: usize, samples: &mut [f32]) {
+        let skipping = self.skipping.load(Ordering::Acquire);
+        if skipping {
+            self.skip_queued_commands();
+        }
+
         samples.chunks_exact_mut(channels).for_each(|frame| {
-            while self.sampler.needs_commands() {
+            while !skipping && self.sampler.needs_commands() {
                 if let Some(command) = self.next_command() {
+                    if let Command::Wait(cycles) = command {
+                        self.queued_cycles
+                            .fetch_sub(cycles as i64, Ordering::AcqRel);
+                    }
                     self.sampler.command(command);
                 } else {
                     break;
@@ -121,7 +219,11 @@ impl GbaSoundSource {
             }
 
             const VOLUME: f32 = 0.25;
-            let (out_l, out_r) = self.sampler.frame();
+            let (out_l, out_r) = if skipping {
+                (0.0, 0.0)
+            } else {
+                self.sampler.frame()
+            };
 
             // protect my ears, ty
             assert!((-1.0..=1.0).contains(&out_l),);
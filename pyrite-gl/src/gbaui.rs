@@ -1,4 +1,5 @@
 use std::{
+    collections::HashSet,
     rc::Rc,
     sync::{
         atomic::{self, AtomicBool, AtomicU16, Ordering},
@@ -7,13 +8,17 @@ use std::{
 };
 
 use anyhow::Context as _;
-use gba::{Button, ButtonSet};
+use gba::ButtonSet;
 use glutin::{
-    event::{ElementState, ModifiersState, VirtualKeyCode, WindowEvent},
+    event::{ElementState, ModifiersState, WindowEvent},
     PossiblyCurrent, WindowedContext,
 };
 use parking_lot::Mutex;
-use pyrite::{config::Config, GbaHandle};
+use pyrite::{
+    config::{Config, InputAction},
+    input::{InputMacro, TurboPattern},
+    GbaHandle, Speed,
+};
 
 use crate::{
     glutil::{
@@ -21,6 +26,7 @@ use crate::{
         PixelDataType, Program, Shader, ShaderType, Texture, TextureFormat, UnlinkedProgram,
         VertexArray,
     },
+    input::{Bindings, Gamepads, Key},
     pyrite_window::PyriteWindow,
 };
 
@@ -29,8 +35,14 @@ pub struct GbaWindow {
     gl: Rc<glow::Context>,
 
     gba: GbaHandle,
-    buttons: ButtonSet,
     buttons_u16: Arc<AtomicU16>,
+    turbo_u16: Arc<AtomicU16>,
+    keyboard_bindings: Bindings<Key>,
+    gamepads: Gamepads,
+    /// Actions held down using the keyboard.
+    keyboard_held: HashSet<InputAction>,
+    /// Actions held down using either the keyboard or a gamepad.
+    held: HashSet<InputAction>,
 
     screen_ready: Arc<AtomicBool>,
     screen: Arc<Mutex<[u16; 240 * 160]>>,
@@ -43,6 +55,13 @@ pub struct GbaWindow {
     wants_exit: bool,
     wants_debugger: bool,
 
+    /// The speed selected with the speed hotkeys, which is restored after fast-forwarding.
+    speed: Speed,
+    fast_forwarding: bool,
+
+    /// The most recently recorded input macro.
+    last_macro: Arc<Mutex<Option<InputMacro>>>,
+
     config: Arc<Config>,
 }
 
@@ -64,12 +83,32 @@ impl GbaWindow {
 
         let buttons_u16 = Arc::new(AtomicU16::new(ButtonSet::default().into()));
         let buttons_u16_gba = buttons_u16.clone();
+        let turbo_u16 = Arc::new(AtomicU16::new(ButtonSet::default().into()));
+        let turbo_u16_gba = turbo_u16.clone();
+
+        let turbo_patterns: Vec<_> = config
+            .input
+            .turbo
+            .iter()
+            .filter_map(|(action, [pressed, released])| {
+                Some((action.button()?, TurboPattern { pressed, released }))
+            })
+            .collect();
+        gba.after_frame(move |_, state| {
+            for (button, pattern) in turbo_patterns {
+                state.input.set_turbo_pattern(button, pattern);
+            }
+        });
 
         gba.on_frame(move |gba, state| {
             let buttons = buttons_u16_gba.load(atomic::Ordering::Acquire);
-            gba.set_buttons(ButtonSet::from(buttons));
+            let turbo = turbo_u16_gba.load(atomic::Ordering::Acquire);
+            let buttons = state
+                .input
+                .apply(ButtonSet::from(buttons), ButtonSet::from(turbo));
+            gba.set_buttons(buttons);
 
-            if !state.paused {
+            if !state.paused && state.frame_rendered() {
                 let mut screen = gba_buffer.lock();
                 screen.copy_from_slice(gba.video().screen());
                 drop(screen);
@@ -174,8 +213,12 @@ impl GbaWindow {
             gl,
 
             gba,
-            buttons: ButtonSet::default(),
             buttons_u16,
+            turbo_u16,
+            keyboard_bindings: Bindings::keyboard(&config.input),
+            gamepads: Gamepads::new(&config.input),
+            keyboard_held: HashSet::new(),
+            held: HashSet::new(),
 
             screen_ready: buffer_ready,
             screen: buffer,
@@ -188,35 +231,148 @@ impl GbaWindow {
             wants_exit: false,
             wants_debugger: false,
 
+            speed: Speed::NORMAL,
+            fast_forwarding: false,
+
+            last_macro: Arc::new(Mutex::new(None)),
+
             config,
         })
     }
 
     fn on_keyboard_input(&mut self, input: glutin::event::KeyboardInput) {
-        let pressed = input.state == ElementState::Pressed;
-        match input.virtual_keycode {
-            Some(VirtualKeyCode::Escape) if pressed => self.wants_exit = true,
-            Some(VirtualKeyCode::P) if pressed && self.modifiers.ctrl() => self
+        let keycode = match input.virtual_keycode {
+            Some(keycode) => keycode,
+            None => return,
+        };
+
+        if input.state == ElementState::Pressed {
+            if let Some(action) = self.keyboard_bindings.key_pressed(keycode, self.modifiers) {
+                self.keyboard_held.insert(action);
+            }
+        } else {
+            for action in self.keyboard_bindings.key_released(keycode) {
+                self.keyboard_held.remove(&action);
+            }
+        }
+        self.update_held_actions();
+    }
+
+    /// Combines the actions held on the keyboard and on gamepads, triggering any action that
+    /// wasn't already held and releasing the ones that aren't anymore.
+    fn update_held_actions(&mut self) {
+        let held: HashSet<InputAction> = self
+            .keyboard_held
+            .union(self.gamepads.held())
+            .copied()
+            .collect();
+
+        let pressed: Vec<InputAction> = held.difference(&self.held).copied().collect();
+        let released: Vec<InputAction> = self.held.difference(&held).copied().collect();
+        pressed
+            .into_iter()
+            .for_each(|action| self.on_action(action, true));
+        released
+            .into_iter()
+            .for_each(|action| self.on_action(action, false));
+
+        let mut buttons = ButtonSet::default();
+        let mut turbo = ButtonSet::default();
+        for &action in held.iter() {
+            if let Some(button) = action.button() {
+                buttons.set_pressed(button, true);
+            }
+            if let Some(button) = action.turbo_button() {
+                turbo.set_pressed(button, true);
+            }
+        }
+        self.buttons_u16.store(buttons.into(), Ordering::Relaxed);
+        self.turbo_u16.store(turbo.into(), Ordering::Relaxed);
+        self.held = held;
+    }
+
+    fn on_action(&mut self, action: InputAction, pressed: bool) {
+        match action {
+            InputAction::Exit if pressed => self.wants_exit = true,
+            InputAction::Pause if pressed => self
                 .gba
                 .after_frame(|_, state| state.paused = !state.paused),
-            Some(VirtualKeyCode::R) if pressed && self.modifiers.ctrl() => {
+            InputAction::Reset if pressed => {
                 let boot_from_bios = self.config.gba.boot_from_bios.unwrap_or(true);
                 self.gba
                     .after_frame(move |gba, _| gba.reset(boot_from_bios))
             }
-            Some(VirtualKeyCode::D) if pressed && self.modifiers.ctrl() => {
-                self.wants_debugger = true
+            InputAction::Debugger if pressed => self.wants_debugger = true,
+
+            // Hold to fast-forward as fast as possible.
+            InputAction::FastForward => {
+                self.fast_forwarding = pressed;
+                self.gba
+                    .set_speed(if pressed { Speed::Uncapped } else { self.speed });
             }
+            InputAction::SpeedUp if pressed => self.set_speed(self.speed.faster()),
+            InputAction::SlowDown if pressed => self.set_speed(self.speed.slower()),
+            InputAction::NormalSpeed if pressed => self.set_speed(Speed::NORMAL),
 
-            Some(keycode) => {
-                if let Some(button) = keycode_to_button(keycode) {
-                    self.buttons.set_pressed(button, pressed);
-                    self.buttons_u16
-                        .store(self.buttons.into(), Ordering::Relaxed);
+            InputAction::RecordMacro if pressed => self.toggle_macro_recording(),
+            InputAction::PlayMacro if pressed => self.toggle_macro_playback(),
+
+            _ => {}
+        }
+    }
+
+    fn toggle_macro_recording(&self) {
+        let last_macro = Arc::clone(&self.last_macro);
+        let macro_path = self.config.input.macro_path.clone();
+        self.gba.after_frame(move |_, state| {
+            let input_macro = match state.input.stop_recording() {
+                Some(input_macro) => input_macro,
+                None => {
+                    log::info!("recording input macro");
+                    state.input.start_recording();
+                    return;
+                }
+            };
+
+            log::info!("recorded input macro ({} frames)", input_macro.len());
+            if let Some(path) = macro_path {
+                match input_macro.save(&path) {
+                    Ok(()) => log::info!("saved input macro to `{}`", path.display()),
+                    Err(err) => log::error!("{:#}", anyhow::Error::from(err)),
                 }
             }
+            *last_macro.lock() = Some(input_macro);
+        });
+    }
 
-            _ => {}
+    fn toggle_macro_playback(&self) {
+        let last_macro = Arc::clone(&self.last_macro);
+        let macro_path = self.config.input.macro_path.clone();
+        self.gba.after_frame(move |_, state| {
+            if state.input.is_playing() {
+                log::info!("stopped playing input macro");
+                state.input.stop_playback();
+                return;
+            }
+
+            // Macros recorded in an earlier session can be played back from the macro file.
+            let input_macro = last_macro.lock().clone().or_else(|| {
+                InputMacro::load(macro_path?)
+                    .map_err(|err| log::error!("{:#}", anyhow::Error::from(err)))
+                    .ok()
+            });
+            if let Some(input_macro) = input_macro {
+                log::info!("playing input macro ({} frames)", input_macro.len());
+                state.input.play(input_macro);
+            }
+        });
+    }
+
+    fn set_speed(&mut self, speed: Speed) {
+        self.speed = speed;
+        log::info!("emulation speed: {speed:?}");
+        if !self.fast_forwarding {
+            self.gba.set_speed(speed);
         }
     }
 
@@ -236,6 +392,13 @@ impl PyriteWindow for GbaWindow {
         }
     }
 
+    fn update(&mut self) -> bool {
+        if self.gamepads.poll() {
+            self.update_held_actions();
+        }
+        true
+    }
+
     fn render(&mut self) {
         glutil::clear(&self.gl, 0.5, 0.2, 0.5);
 
@@ -277,19 +440,3 @@ impl PyriteWindow for GbaWindow {
         &self.gl
     }
 }
-
-fn keycode_to_button(keycode: VirtualKeyCode) -> Option<Button> {
-    match keycode {
-        VirtualKeyCode::Z => Some(Button::A),
-        VirtualKeyCode::X => Some(Button::B),
-        VirtualKeyCode::Left => Some(Button::Left),
-        VirtualKeyCode::Right => Some(Button::Right),
-        VirtualKeyCode::Up => Some(Button::Up),
-        VirtualKeyCode::Down => Some(Button::Down),
-        VirtualKeyCode::A => Some(Button::L),
-        VirtualKeyCode::S => Some(Button::R),
-        VirtualKeyCode::Return => Some(Button::Start),
-        VirtualKeyCode::Back => Some(Button::Select),
-        _ => None,
-    }
-}
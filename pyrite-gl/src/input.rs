@@ -0,0 +1,282 @@
+use std::{
+    collections::{HashMap, HashSet},
+    str::FromStr,
+};
+
+use anyhow::Context as _;
+use gilrs::{Axis, Gilrs};
+use glutin::event::{ModifiersState, VirtualKeyCode};
+use pyrite::config::{InputAction, InputBindings, InputConfig};
+use serde::{de::IntoDeserializer as _, Deserialize};
+
+/// How far an analog stick has to be pushed before it counts as pressing a direction.
+const AXIS_THRESHOLD: f32 = 0.5;
+
+const DEFAULT_KEYBOARD_BINDINGS: &[(InputAction, &[&str])] = &[
+    (InputAction::A, &["Z"]),
+    (InputAction::B, &["X"]),
+    (InputAction::L, &["A"]),
+    (InputAction::R, &["S"]),
+    (InputAction::Start, &["Return"]),
+    (InputAction::Select, &["Back"]),
+    (InputAction::Up, &["Up"]),
+    (InputAction::Down, &["Down"]),
+    (InputAction::Left, &["Left"]),
+    (InputAction::Right, &["Right"]),
+    (InputAction::Pause, &["Ctrl+P"]),
+    (InputAction::Reset, &["Ctrl+R"]),
+    (InputAction::Debugger, &["Ctrl+D"]),
+    (InputAction::Exit, &["Escape"]),
+    (InputAction::FastForward, &["Tab"]),
+    (InputAction::SpeedUp, &["Ctrl+Equals", "Ctrl+NumpadAdd"]),
+    (
+        InputAction::SlowDown,
+        &["Ctrl+Minus", "Ctrl+NumpadSubtract"],
+    ),
+    (InputAction::NormalSpeed, &["Ctrl+Key0", "Ctrl+Numpad0"]),
+    (InputAction::TurboA, &["C"]),
+    (InputAction::TurboB, &["V"]),
+    (InputAction::RecordMacro, &["Ctrl+M"]),
+    (InputAction::PlayMacro, &["Ctrl+Shift+M"]),
+];
+
+const DEFAULT_GAMEPAD_BINDINGS: &[(InputAction, &[&str])] = &[
+    (InputAction::A, &["East"]),
+    (InputAction::B, &["South"]),
+    (InputAction::L, &["LeftTrigger"]),
+    (InputAction::R, &["RightTrigger"]),
+    (InputAction::Start, &["Start"]),
+    (InputAction::Select, &["Select"]),
+    (InputAction::Up, &["DPadUp", "LeftStickY+"]),
+    (InputAction::Down, &["DPadDown", "LeftStickY-"]),
+    (InputAction::Left, &["DPadLeft", "LeftStickX-"]),
+    (InputAction::Right, &["DPadRight", "LeftStickX+"]),
+    (InputAction::FastForward, &["RightTrigger2"]),
+    (InputAction::TurboA, &["North"]),
+    (InputAction::TurboB, &["West"]),
+];
+
+/// Inputs of type `I` mapped to the actions that they trigger.
+pub struct Bindings<I> {
+    bindings: Vec<(I, InputAction)>,
+}
+
+impl<I> Bindings<I>
+where
+    I: FromStr<Err = anyhow::Error>,
+{
+    /// Creates bindings from the defaults with each set of `overrides` replacing the bindings
+    /// for the actions that it lists. Invalid bindings are logged and ignored.
+    fn new(defaults: &[(InputAction, &[&str])], overrides: &[&InputBindings]) -> Self {
+        let mut inputs: HashMap<InputAction, Vec<&str>> = defaults
+            .iter()
+            .map(|&(action, inputs)| (action, inputs.to_vec()))
+            .collect();
+        for overrides in overrides {
+            for (action, binding) in overrides.iter() {
+                inputs.insert(
+                    action,
+                    binding.inputs().iter().map(|s| s.as_str()).collect(),
+                );
+            }
+        }
+
+        let mut bindings = Vec::new();
+        for (action, inputs) in inputs {
+            for input in inputs {
+                match input.parse() {
+                    Ok(input) => bindings.push((input, action)),
+                    Err(err) => log::error!("invalid binding `{input}` for {action:?}: {err:#}"),
+                }
+            }
+        }
+        Bindings { bindings }
+    }
+}
+
+impl Bindings<Key> {
+    pub fn keyboard(config: &InputConfig) -> Self {
+        Self::new(DEFAULT_KEYBOARD_BINDINGS, &[&config.keyboard])
+    }
+
+    /// Returns the action bound to a key press. If the key is bound with several different
+    /// modifiers the binding that uses the most of the held modifiers wins, so `Ctrl+A` can do
+    /// something other than `A`.
+    pub fn key_pressed(
+        &self,
+        code: VirtualKeyCode,
+        modifiers: ModifiersState,
+    ) -> Option<InputAction> {
+        self.bindings
+            .iter()
+            .filter(|(key, _)| key.code == code && modifiers.contains(key.modifiers))
+            .max_by_key(|(key, _)| key.modifiers.bits().count_ones())
+            .map(|&(_, action)| action)
+    }
+
+    /// Returns every action bound to a key regardless of modifiers, so that releasing a key
+    /// always releases whatever it was holding down.
+    pub fn key_released(&self, code: VirtualKeyCode) -> impl '_ + Iterator<Item = InputAction> {
+        self.bindings
+            .iter()
+            .filter(move |(key, _)| key.code == code)
+            .map(|&(_, action)| action)
+    }
+}
+
+/// A key with the modifiers that must be held with it, written as e.g. `Ctrl+Shift+P`. Key
+/// names are the names of winit's `VirtualKeyCode` variants.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Key {
+    code: VirtualKeyCode,
+    modifiers: ModifiersState,
+}
+
+impl FromStr for Key {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Key> {
+        let mut parts = s.split('+').map(str::trim);
+        let code = parts.next_back().context("missing key")?;
+        let code = deserialize_name(code).context("unknown key")?;
+
+        let mut modifiers = ModifiersState::empty();
+        for modifier in parts {
+            modifiers |= match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => ModifiersState::CTRL,
+                "shift" => ModifiersState::SHIFT,
+                "alt" => ModifiersState::ALT,
+                "logo" | "super" | "cmd" => ModifiersState::LOGO,
+                _ => anyhow::bail!("unknown modifier `{modifier}`"),
+            };
+        }
+
+        Ok(Key { code, modifiers })
+    }
+}
+
+/// A gamepad button, written using the names of gilrs' `Button` variants, or an analog axis
+/// pushed in one direction, written as the name of a gilrs `Axis` followed by `+` or `-`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum GamepadInput {
+    Button(gilrs::Button),
+    Axis { axis: Axis, positive: bool },
+}
+
+impl FromStr for GamepadInput {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<GamepadInput> {
+        let s = s.trim();
+        let axis = |name: &str, positive| -> anyhow::Result<GamepadInput> {
+            let axis = deserialize_name(name).context("unknown gamepad axis")?;
+            Ok(GamepadInput::Axis { axis, positive })
+        };
+
+        if let Some(name) = s.strip_suffix('+') {
+            axis(name, true)
+        } else if let Some(name) = s.strip_suffix('-') {
+            axis(name, false)
+        } else {
+            deserialize_name(s)
+                .map(GamepadInput::Button)
+                .context("unknown gamepad button")
+        }
+    }
+}
+
+impl GamepadInput {
+    fn is_active(self, gamepad: &gilrs::Gamepad) -> bool {
+        match self {
+            GamepadInput::Button(button) => gamepad.is_pressed(button),
+            GamepadInput::Axis { axis, positive } => {
+                let value = gamepad.value(axis);
+                if positive {
+                    value >= AXIS_THRESHOLD
+                } else {
+                    value <= -AXIS_THRESHOLD
+                }
+            }
+        }
+    }
+}
+
+/// Polls all connected gamepads and keeps track of which actions they are holding down.
+pub struct Gamepads {
+    gilrs: Option<Gilrs>,
+    bindings: Bindings<GamepadInput>,
+    device_bindings: HashMap<String, Bindings<GamepadInput>>,
+    held: HashSet<InputAction>,
+}
+
+impl Gamepads {
+    pub fn new(config: &InputConfig) -> Gamepads {
+        let gilrs = Gilrs::new()
+            .map_err(|err| log::error!("failed to initialize gamepad support: {err}"))
+            .ok();
+
+        let device_bindings = config
+            .devices
+            .iter()
+            .map(|(name, device)| {
+                let bindings = Bindings::new(DEFAULT_GAMEPAD_BINDINGS, &[&config.gamepad, device]);
+                (name.clone(), bindings)
+            })
+            .collect();
+
+        Gamepads {
+            gilrs,
+            bindings: Bindings::new(DEFAULT_GAMEPAD_BINDINGS, &[&config.gamepad]),
+            device_bindings,
+            held: HashSet::new(),
+        }
+    }
+
+    /// Processes pending gamepad events. Returns true if the set of held actions changed.
+    pub fn poll(&mut self) -> bool {
+        let gilrs = match self.gilrs {
+            Some(ref mut gilrs) => gilrs,
+            None => return false,
+        };
+
+        while let Some(event) = gilrs.next_event() {
+            match event.event {
+                gilrs::EventType::Connected => {
+                    log::info!("gamepad connected: {}", gilrs.gamepad(event.id).name())
+                }
+                gilrs::EventType::Disconnected => {
+                    log::info!("gamepad disconnected: {}", gilrs.gamepad(event.id).name())
+                }
+                _ => {}
+            }
+        }
+
+        let mut held = HashSet::new();
+        for (_, gamepad) in gilrs.gamepads() {
+            let bindings = self
+                .device_bindings
+                .get(gamepad.name())
+                .unwrap_or(&self.bindings);
+            held.extend(
+                bindings
+                    .bindings
+                    .iter()
+                    .filter(|(input, _)| input.is_active(&gamepad))
+                    .map(|&(_, action)| action),
+            );
+        }
+
+        let changed = held != self.held;
+        self.held = held;
+        changed
+    }
+
+    pub fn held(&self) -> &HashSet<InputAction> {
+        &self.held
+    }
+}
+
+fn deserialize_name<'de, T: Deserialize<'de>>(name: &'de str) -> anyhow::Result<T> {
+    T::deserialize(name.into_deserializer())
+        .map_err(|err: serde::de::value::Error| anyhow::anyhow!(err))
+}
@@ -2,6 +2,7 @@ mod audio;
 mod debuggerui;
 mod gbaui;
 mod glutil;
+mod input;
 mod pyrite_window;
 
 use std::path::{Path, PathBuf};
@@ -16,7 +17,7 @@ use glutin::{
     window::{WindowBuilder, WindowId},
     PossiblyCurrent, WindowedContext,
 };
-use pyrite::{config::Config, GbaHandle};
+use pyrite::{config::Config, FrameSkip, GbaHandle, Speed};
 use pyrite_window::PyriteWindow;
 
 struct Windows {
@@ -200,22 +201,30 @@ fn run(event_loop: EventLoop<PyriteEvent>) -> anyhow::Result<()> {
 
     let gba = pyrite::GbaHandle::new();
 
+    let frame_skip = FrameSkip::from_config(&config.graphics);
+    gba.after_frame(move |_gba, state| state.frame_skip = frame_skip);
+
     let mut skip_frames = args.skip_to_frame.unwrap_or(0);
     let skipping_frames = skip_frames > 0;
     if skip_frames > 0 {
-        gba.after_frame_wait(|_gba, state| {
-            state.target_fps = 10000.0;
+        gba.after_frame_wait(move |_gba, state| {
+            state.speed = Speed::Uncapped;
+            if !args.profiling {
+                state.frame_skip = FrameSkip::Fixed(1);
+            }
         });
 
-        gba.on_frame(move |gba, state| {
+        gba.on_frame(move |_gba, state| {
             skip_frames -= 1;
 
-            let render_frame =
-                args.profiling || (skip_frames <= 1 || (state.frame_count() % 2 == 0));
-            gba.video_mut().set_skip_render(!render_frame);
+            // Make sure that the last frame before we stop skipping is rendered.
+            if skip_frames <= 1 {
+                state.frame_skip = FrameSkip::NONE;
+            }
 
             if skip_frames == 0 {
-                state.target_fps = 60.0;
+                state.speed = Speed::NORMAL;
+                state.frame_skip = frame_skip;
                 state.paused = args.pause_on_startup;
                 state.remove_callback();
 
@@ -246,8 +255,8 @@ fn run(event_loop: EventLoop<PyriteEvent>) -> anyhow::Result<()> {
         gba.set_bios(bios);
         gba.reset(boot_from_bios);
     });
-    let mut stream =
-        audio::run(gba.clone(), event_loop.create_proxy()).context("error while starting audio")?;
+    let mut stream = audio::run(gba.clone(), event_loop.create_proxy(), &config.audio)
+        .context("error while starting audio")?;
 
     let window = WindowBuilder::new()
         .with_title("Pyrite")
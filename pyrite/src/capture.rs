@@ -0,0 +1,347 @@
+//! Records the GBA's audio output to a file by rendering the per-frame command stream from
+//! [`GbaAudio::commands`](gba::GbaAudio::commands) with a [`GbaAudioSampler`].
+//!
+//! This does not depend on an audio device so it can also be used when running headless:
+//!
+//! ```ignore
+//! let mut capture = AudioCapture::create("music.flac", CaptureFormat::Flac, 48000)?;
+//! for _ in 0..(60 * 60) {
+//!     gba.frame();
+//!     capture.push_commands(gba.audio().commands())?;
+//! }
+//! capture.finish()?;
+//! ```
+
+mod flac;
+
+use std::{
+    fmt,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+
+use gba::{AudioChannel, ChannelMask, Command, GbaAudioSampler};
+
+use crate::{CallbackId, GbaHandle};
+
+use self::flac::FlacWriter;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CaptureFormat {
+    Wav,
+    Flac,
+}
+
+impl CaptureFormat {
+    /// Guesses the format from a file extension (`wav` or `flac`).
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<CaptureFormat> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        if extension.eq_ignore_ascii_case("wav") {
+            Some(CaptureFormat::Wav)
+        } else if extension.eq_ignore_ascii_case("flac") {
+            Some(CaptureFormat::Flac)
+        } else {
+            None
+        }
+    }
+}
+
+enum CaptureWriter {
+    Wav(hound::WavWriter<BufWriter<File>>),
+    Flac(FlacWriter<BufWriter<File>>),
+}
+
+/// A single audio file that is being written to.
+struct Track {
+    path: PathBuf,
+    writer: CaptureWriter,
+}
+
+impl Track {
+    fn create(path: &Path, format: CaptureFormat, sample_rate: u32) -> Result<Self, Error> {
+        let path = path.to_path_buf();
+        let writer = match format {
+            CaptureFormat::Wav => {
+                let spec = hound::WavSpec {
+                    channels: AudioCapture::CHANNELS,
+                    sample_rate,
+                    bits_per_sample: AudioCapture::BITS_PER_SAMPLE,
+                    sample_format: hound::SampleFormat::Int,
+                };
+                let writer = hound::WavWriter::create(&path, spec)
+                    .map_err(|err| Error::Wav(path.clone(), err))?;
+                CaptureWriter::Wav(writer)
+            }
+
+            CaptureFormat::Flac => {
+                let file = File::create(&path).map_err(|err| Error::Io(path.clone(), err))?;
+                let writer = FlacWriter::new(BufWriter::new(file), sample_rate)
+                    .map_err(|err| Error::Io(path.clone(), err))?;
+                CaptureWriter::Flac(writer)
+            }
+        };
+        Ok(Track { path, writer })
+    }
+
+    fn write_frame(&mut self, (left, right): (f32, f32)) -> Result<(), Error> {
+        let (left, right) = (to_pcm16(left), to_pcm16(right));
+        match self.writer {
+            CaptureWriter::Wav(ref mut writer) => writer
+                .write_sample(left)
+                .and_then(|_| writer.write_sample(right))
+                .map_err(|err| Error::Wav(self.path.clone(), err)),
+            CaptureWriter::Flac(ref mut writer) => writer
+                .write_frame(left, right)
+                .map_err(|err| Error::Io(self.path.clone(), err)),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self.writer {
+            CaptureWriter::Wav(writer) => {
+                writer.finalize().map_err(|err| Error::Wav(self.path, err))
+            }
+            CaptureWriter::Flac(writer) => writer.finish().map_err(|err| Error::Io(self.path, err)),
+        }
+    }
+}
+
+/// Renders GBA audio commands to 16bit stereo PCM and writes them to a WAV or FLAC file.
+/// Individual channels can also be written to their own files with
+/// [`AudioCapture::add_channel_track`].
+pub struct AudioCapture {
+    sampler: GbaAudioSampler,
+    track: Track,
+    channel_tracks: Vec<(AudioChannel, Track)>,
+    sample_rate: u32,
+    frames_written: u64,
+}
+
+impl AudioCapture {
+    pub const CHANNELS: u16 = 2;
+    pub const BITS_PER_SAMPLE: u16 = 16;
+
+    pub fn create<P>(path: P, format: CaptureFormat, sample_rate: u32) -> Result<Self, Error>
+    where
+        P: AsRef<Path>,
+    {
+        Ok(AudioCapture {
+            sampler: GbaAudioSampler::new(sample_rate),
+            track: Track::create(path.as_ref(), format, sample_rate)?,
+            channel_tracks: Vec::new(),
+            sample_rate,
+            frames_written: 0,
+        })
+    }
+
+    /// Also writes the output of a single channel to its own file, starting from the next frame.
+    /// The channel is rendered as if it were the only one playing, even if it is muted.
+    pub fn add_channel_track<P>(
+        &mut self,
+        channel: AudioChannel,
+        path: P,
+        format: CaptureFormat,
+    ) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+    {
+        let track = Track::create(path.as_ref(), format, self.sample_rate)?;
+        self.channel_tracks.push((channel, track));
+        self.sampler.set_channel_outputs_enabled(true);
+        Ok(())
+    }
+
+    /// Mutes or solos channels in the main output.
+    pub fn set_channel_mask(&mut self, mask: ChannelMask) {
+        self.sampler.set_channel_mask(mask);
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.track.path
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    /// The number of stereo frames that have been written so far.
+    pub fn frames_written(&self) -> u64 {
+        self.frames_written
+    }
+
+    /// Renders a frame's worth of audio commands and writes the output.
+    pub fn push_commands(&mut self, commands: &[Command]) -> Result<(), Error> {
+        for &command in commands {
+            self.sampler.command(command);
+            while !self.sampler.needs_commands() {
+                let frame = self.sampler.frame();
+                self.track.write_frame(frame)?;
+                for (channel, track) in self.channel_tracks.iter_mut() {
+                    track.write_frame(self.sampler.channel_frame(*channel))?;
+                }
+                self.frames_written += 1;
+            }
+        }
+        Ok(())
+    }
+
+    /// Flushes any buffered audio and updates the headers of all of the files. Dropping the
+    /// capture will also do this but errors are ignored.
+    pub fn finish(self) -> Result<(), Error> {
+        let mut result = self.track.finish();
+        for (_, track) in self.channel_tracks {
+            let track_result = track.finish();
+            result = result.and(track_result);
+        }
+        result
+    }
+}
+
+/// Converts the sampler's unsigned 10bit output level (0.0 to 1.0) to signed 16bit PCM centered
+/// on the default bias level.
+fn to_pcm16(level: f32) -> i16 {
+    ((level * 1024.0 - 512.0) * 64.0).clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+impl GbaHandle {
+    /// Writes the audio output of every frame to `capture` until the returned callback is
+    /// removed with [`GbaHandle::remove_on_frame`], which also finishes the file.
+    pub fn capture_audio(&self, mut capture: AudioCapture) -> CallbackId {
+        self.on_frame(move |gba, state| {
+            if let Err(err) = capture.push_commands(gba.audio().commands()) {
+                log::error!("error occurred while capturing audio: {err}");
+                state.remove_callback();
+            }
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, std::io::Error),
+    Wav(PathBuf, hound::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, _) | Error::Wav(path, _) => {
+                write!(f, "error occurred writing audio to `{}`", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, err) => Some(err),
+            Error::Wav(_, err) => Some(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Creates a GBA playing a 1kHz-ish square wave from Sound 2.
+    fn square_gba() -> gba::Gba {
+        let mut gba = gba::Gba::new();
+        gba.reset(false);
+        let mem = gba.memory_mut();
+        mem.write16_as_cpu(0x04000084, 0x0080); // SOUNDCNT_X
+        mem.write16_as_cpu(0x04000080, 0x2277); // SOUNDCNT_L: Sound 2 left/right
+        mem.write16_as_cpu(0x04000082, 0x0002); // SOUNDCNT_H: 100% PSG volume
+        mem.write16_as_cpu(0x04000068, 0xF080); // SOUND2CNT_L
+        mem.write16_as_cpu(0x0400006C, 0x8000 | 1920); // SOUND2CNT_H
+        gba
+    }
+
+    fn read_wav(path: &Path) -> Vec<i16> {
+        let samples = hound::WavReader::open(path)
+            .unwrap()
+            .samples::<i16>()
+            .map(|s| s.unwrap())
+            .collect();
+        let _ = std::fs::remove_file(path);
+        samples
+    }
+
+    /// Renders a second of audio from [`square_gba`] through the capture.
+    fn capture_square(format: CaptureFormat, path: &Path) -> u64 {
+        let mut gba = square_gba();
+        let mut capture = AudioCapture::create(path, format, 32768).unwrap();
+        for _ in 0..60 {
+            gba.frame();
+            capture.push_commands(gba.audio().commands()).unwrap();
+        }
+        let frames = capture.frames_written();
+        capture.finish().unwrap();
+        frames
+    }
+
+    #[test]
+    fn wav_and_flac_contain_the_same_audio() {
+        let dir = std::env::temp_dir();
+        let wav_path = dir.join(format!("pyrite-capture-{}.wav", std::process::id()));
+        let flac_path = dir.join(format!("pyrite-capture-{}.flac", std::process::id()));
+
+        let wav_frames = capture_square(CaptureFormat::Wav, &wav_path);
+        let flac_frames = capture_square(CaptureFormat::Flac, &flac_path);
+        assert_eq!(wav_frames, flac_frames);
+        assert!(wav_frames > 32000, "{wav_frames}");
+
+        let mut wav = hound::WavReader::open(&wav_path).unwrap();
+        assert_eq!(wav.spec().channels, 2);
+        assert_eq!(wav.spec().sample_rate, 32768);
+        let wav_samples: Vec<i32> = wav.samples::<i16>().map(|s| s.unwrap() as i32).collect();
+
+        let mut flac = claxon::FlacReader::open(&flac_path).unwrap();
+        assert_eq!(flac.streaminfo().samples, Some(flac_frames));
+        let flac_samples: Vec<i32> = flac.samples().map(|s| s.unwrap()).collect();
+
+        let _ = std::fs::remove_file(&wav_path);
+        let _ = std::fs::remove_file(&flac_path);
+
+        assert_eq!(wav_samples.len() as u64, wav_frames * 2);
+        assert_eq!(wav_samples, flac_samples);
+        assert!(wav_samples.iter().any(|&s| s != wav_samples[0]));
+    }
+
+    #[test]
+    fn channel_tracks_ignore_muting() {
+        let dir = std::env::temp_dir();
+        let path =
+            |name: &str| dir.join(format!("pyrite-capture-{}-{name}.wav", std::process::id()));
+        let (main_path, sound2_path, fifo_a_path) = (path("main"), path("psg2"), path("fifo-a"));
+
+        let mut gba = square_gba();
+        let mut capture = AudioCapture::create(&main_path, CaptureFormat::Wav, 32768).unwrap();
+        capture
+            .add_channel_track(AudioChannel::Sound2, &sound2_path, CaptureFormat::Wav)
+            .unwrap();
+        capture
+            .add_channel_track(AudioChannel::FifoA, &fifo_a_path, CaptureFormat::Wav)
+            .unwrap();
+        let mut mask = ChannelMask::default();
+        mask.set_soloed(AudioChannel::FifoA, true);
+        capture.set_channel_mask(mask);
+        for _ in 0..10 {
+            gba.frame();
+            capture.push_commands(gba.audio().commands()).unwrap();
+        }
+        capture.finish().unwrap();
+
+        let main = read_wav(&main_path);
+        let sound2 = read_wav(&sound2_path);
+        let fifo_a = read_wav(&fifo_a_path);
+        assert_eq!(main.len(), sound2.len());
+        assert_eq!(main.len(), fifo_a.len());
+        // Only the (silent) soloed FIFO can be heard so the output sits at the bias level.
+        assert!(main.iter().all(|&s| s == main[0]));
+        assert!(fifo_a.iter().all(|&s| s == main[0]));
+        assert!(sound2.iter().any(|&s| s != main[0]));
+    }
+}
@@ -0,0 +1,302 @@
+//! A small FLAC encoder for 16bit stereo audio. Each block is encoded using FLAC's fixed linear
+//! predictors with a single Rice partition, which is enough to compress the GBA's mostly
+//! quiet and repetitive output without pulling in a native FLAC library.
+
+use std::io::{self, Seek, SeekFrom, Write};
+
+const BLOCK_SIZE: usize = 4096;
+const BITS_PER_SAMPLE: u32 = 16;
+const CHANNELS: u32 = 2;
+
+/// Byte offset of the STREAMINFO block's "total samples" field within the file.
+const TOTAL_SAMPLES_OFFSET: u64 = 4 + 4 + 13;
+
+pub struct FlacWriter<W: Write + Seek> {
+    inner: Option<W>,
+    left: Vec<i32>,
+    right: Vec<i32>,
+    frame_number: u64,
+    total_samples: u64,
+}
+
+impl<W: Write + Seek> FlacWriter<W> {
+    pub fn new(mut inner: W, sample_rate: u32) -> io::Result<Self> {
+        inner.write_all(b"fLaC")?;
+
+        let mut streaminfo = BitWriter::default();
+        streaminfo.write(1, 1); // last metadata block
+        streaminfo.write(0, 7); // STREAMINFO
+        streaminfo.write(34, 24);
+        streaminfo.write(BLOCK_SIZE as u64, 16); // min block size
+        streaminfo.write(BLOCK_SIZE as u64, 16); // max block size
+        streaminfo.write(0, 24); // min frame size (unknown)
+        streaminfo.write(0, 24); // max frame size (unknown)
+        streaminfo.write(sample_rate as u64, 20);
+        streaminfo.write((CHANNELS - 1) as u64, 3);
+        streaminfo.write((BITS_PER_SAMPLE - 1) as u64, 5);
+        streaminfo.write(0, 36); // total samples, filled in by `finish`
+        streaminfo.write(0, 64); // MD5 (unknown)
+        streaminfo.write(0, 64);
+        inner.write_all(&streaminfo.bytes)?;
+
+        Ok(FlacWriter {
+            inner: Some(inner),
+            left: Vec::with_capacity(BLOCK_SIZE),
+            right: Vec::with_capacity(BLOCK_SIZE),
+            frame_number: 0,
+            total_samples: 0,
+        })
+    }
+
+    pub fn write_frame(&mut self, left: i16, right: i16) -> io::Result<()> {
+        self.left.push(left as i32);
+        self.right.push(right as i32);
+        if self.left.len() == BLOCK_SIZE {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finish_internal()
+    }
+
+    fn finish_internal(&mut self) -> io::Result<()> {
+        if self.inner.is_none() {
+            return Ok(());
+        }
+
+        if !self.left.is_empty() {
+            self.flush_block()?;
+        }
+
+        let mut inner = self.inner.take().unwrap();
+        let end = inner.stream_position()?;
+
+        // The 36bit total sample count starts 4 bits into this byte, after the low bits of the
+        // bits per sample.
+        inner.seek(SeekFrom::Start(TOTAL_SAMPLES_OFFSET))?;
+        let mut bits = BitWriter::default();
+        bits.write(((BITS_PER_SAMPLE - 1) & 0xF) as u64, 4);
+        bits.write(self.total_samples, 36);
+        inner.write_all(&bits.bytes)?;
+
+        inner.seek(SeekFrom::Start(end))?;
+        inner.flush()
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        let block_size = self.left.len();
+        let mut frame = BitWriter::default();
+
+        frame.write(0b11111111111110, 14); // sync code
+        frame.write(0, 1); // reserved
+        frame.write(0, 1); // fixed block size
+        frame.write(0b0111, 4); // 16bit block size at the end of the header
+        frame.write(0b0000, 4); // sample rate from STREAMINFO
+        frame.write(0b0001, 4); // independent left and right channels
+        frame.write(0b100, 3); // 16 bits per sample
+        frame.write(0, 1); // reserved
+        write_utf8(&mut frame, self.frame_number);
+        frame.write((block_size - 1) as u64, 16);
+        let crc = crc8(&frame.bytes);
+        frame.write(crc as u64, 8);
+
+        write_subframe(&mut frame, &self.left);
+        write_subframe(&mut frame, &self.right);
+        frame.align();
+        let crc = crc16(&frame.bytes);
+        frame.write(crc as u64, 16);
+
+        self.inner
+            .as_mut()
+            .expect("FLAC writer already finished")
+            .write_all(&frame.bytes)?;
+        self.left.clear();
+        self.right.clear();
+        self.frame_number += 1;
+        self.total_samples += block_size as u64;
+        Ok(())
+    }
+}
+
+impl<W: Write + Seek> Drop for FlacWriter<W> {
+    fn drop(&mut self) {
+        if let Err(err) = self.finish_internal() {
+            log::error!("error occurred while finishing FLAC file: {err}");
+        }
+    }
+}
+
+fn write_subframe(out: &mut BitWriter, samples: &[i32]) {
+    if samples.iter().all(|&s| s == samples[0]) {
+        out.write(0, 1);
+        out.write(0b000000, 6); // CONSTANT
+        out.write(0, 1);
+        out.write_signed(samples[0], BITS_PER_SAMPLE);
+        return;
+    }
+
+    let verbatim_bits = samples.len() as u64 * BITS_PER_SAMPLE as u64;
+    let best = (0..=4usize.min(samples.len() - 1))
+        .map(|order| {
+            let residuals = fixed_residuals(samples, order);
+            let (rice_parameter, bits) = best_rice_parameter(&residuals);
+            let bits = bits + (order as u64 * BITS_PER_SAMPLE as u64);
+            (order, residuals, rice_parameter, bits)
+        })
+        .min_by_key(|&(_, _, _, bits)| bits);
+
+    match best {
+        Some((order, residuals, rice_parameter, bits)) if bits < verbatim_bits => {
+            out.write(0, 1);
+            out.write(0b001000 | order as u64, 6); // FIXED
+            out.write(0, 1);
+            samples[..order]
+                .iter()
+                .for_each(|&s| out.write_signed(s, BITS_PER_SAMPLE));
+            out.write(0b00, 2); // Rice coding with 4bit parameters
+            out.write(0, 4); // partition order
+            out.write(rice_parameter as u64, 4);
+            residuals
+                .iter()
+                .for_each(|&r| out.write_rice(r, rice_parameter));
+        }
+
+        _ => {
+            out.write(0, 1);
+            out.write(0b000001, 6); // VERBATIM
+            out.write(0, 1);
+            samples
+                .iter()
+                .for_each(|&s| out.write_signed(s, BITS_PER_SAMPLE));
+        }
+    }
+}
+
+/// Residuals of FLAC's fixed polynomial predictors of orders 0 to 4.
+fn fixed_residuals(samples: &[i32], order: usize) -> Vec<i32> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |offset: usize| samples[i - offset];
+            match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                4 => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+                _ => unreachable!(),
+            }
+        })
+        .collect()
+}
+
+/// Returns the Rice parameter that encodes the residuals in the fewest bits and the number of
+/// bits required. Parameter 15 is reserved as an escape code.
+fn best_rice_parameter(residuals: &[i32]) -> (u32, u64) {
+    (0..15)
+        .map(|k| {
+            let bits = residuals
+                .iter()
+                .map(|&r| (zigzag(r) >> k) as u64 + 1 + k as u64)
+                .sum::<u64>();
+            (k, bits)
+        })
+        .min_by_key(|&(_, bits)| bits)
+        .unwrap()
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+/// Writes the frame number using FLAC's extended UTF-8 style coding.
+fn write_utf8(out: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        out.write(value, 8);
+        return;
+    }
+
+    let mut continuation_bytes = 1;
+    while value >= 1 << (6 + 5 * continuation_bytes) && continuation_bytes < 6 {
+        continuation_bytes += 1;
+    }
+    let first_bits = 6 - continuation_bytes;
+    let prefix = (0xFF << (7 - continuation_bytes)) & 0xFF;
+    let first = (value >> (6 * continuation_bytes)) & ((1 << first_bits) - 1);
+    out.write(prefix | first, 8);
+    for idx in (0..continuation_bytes).rev() {
+        out.write(0x80 | ((value >> (6 * idx)) & 0x3F), 8);
+    }
+}
+
+fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |mut crc, &byte| {
+        crc ^= byte;
+        for _ in 0..8 {
+            crc = if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(0u16, |mut crc, &byte| {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x8005
+            } else {
+                crc << 1
+            };
+        }
+        crc
+    })
+}
+
+/// Writes values MSB first.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    /// Number of bits used in the last byte, 0 if a new byte must be started.
+    used: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for bit in (0..bits).rev() {
+            self.write_bit((value >> bit) & 1 != 0);
+        }
+    }
+
+    fn write_bit(&mut self, bit: bool) {
+        if self.used == 0 {
+            self.bytes.push(0);
+        }
+        if bit {
+            *self.bytes.last_mut().unwrap() |= 0x80 >> self.used;
+        }
+        self.used = (self.used + 1) % 8;
+    }
+
+    fn write_signed(&mut self, value: i32, bits: u32) {
+        self.write(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    fn write_rice(&mut self, value: i32, k: u32) {
+        let value = zigzag(value);
+        for _ in 0..(value >> k) {
+            self.write_bit(false);
+        }
+        self.write_bit(true);
+        self.write((value & ((1 << k) - 1)) as u64, k);
+    }
+
+    fn align(&mut self) {
+        self.used = 0;
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
 
@@ -6,6 +7,10 @@ use std::path::{Path, PathBuf};
 pub struct Config {
     pub graphics: GraphicsConfig,
     pub gba: GbaConfig,
+    #[serde(default)]
+    pub audio: AudioConfig,
+    #[serde(default)]
+    pub input: InputConfig,
 }
 
 #[derive(serde::Deserialize)]
@@ -13,6 +18,11 @@ pub struct Config {
 pub struct GraphicsConfig {
     pub vsync: Option<bool>,
     pub fps: Option<u32>,
+    /// Number of frames to skip after each rendered frame, or the maximum number of frames
+    /// to skip in a row if `auto_frameskip` is enabled.
+    pub frameskip: Option<u32>,
+    /// Skip frames only when emulation can't keep up with the target frame rate.
+    pub auto_frameskip: Option<bool>,
 }
 
 impl Default for GraphicsConfig {
@@ -20,6 +30,8 @@ impl Default for GraphicsConfig {
         GraphicsConfig {
             vsync: Some(true),
             fps: None,
+            frameskip: None,
+            auto_frameskip: Some(false),
         }
     }
 }
@@ -40,6 +52,204 @@ impl Default for GbaConfig {
     }
 }
 
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AudioConfig {
+    pub sync: Option<AudioSync>,
+    /// Amount of audio to keep queued for the audio device, in milliseconds.
+    pub latency: Option<f64>,
+    /// The maximum amount that dynamic rate control may speed up or slow down emulation by,
+    /// as a fraction of the normal rate.
+    pub max_rate_delta: Option<f64>,
+}
+
+impl Default for AudioConfig {
+    fn default() -> Self {
+        AudioConfig {
+            sync: Some(AudioSync::DynamicRate),
+            latency: Some(64.0),
+            max_rate_delta: Some(0.005),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Copy, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioSync {
+    /// Emulation is paced by the frame rate and audio is dropped if too much of it is queued.
+    Video,
+    /// Emulation speed is adjusted slightly to keep the audio queue at the configured latency.
+    DynamicRate,
+}
+
+/// Input bindings. Bindings are stored as the names used by the frontend (e.g. `"Ctrl+P"` for a
+/// key or `"South"` for a gamepad button) and only replace the frontend's default bindings
+/// for the actions that are listed.
+#[derive(serde::Deserialize, Default)]
+#[serde(deny_unknown_fields, default)]
+pub struct InputConfig {
+    pub keyboard: InputBindings,
+    /// Bindings used by every gamepad.
+    pub gamepad: InputBindings,
+    /// Bindings for specific gamepads by name. These are applied on top of `gamepad`.
+    pub devices: HashMap<String, InputBindings>,
+    /// Turbo patterns for buttons as `[pressed frames, released frames]`, e.g. `a = [2, 2]`.
+    /// Buttons that aren't listed alternate every frame.
+    pub turbo: TurboConfig,
+    /// File that recorded input macros are saved to and played back from.
+    pub macro_path: Option<PathBuf>,
+}
+
+#[derive(serde::Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputAction {
+    A,
+    B,
+    L,
+    R,
+    Start,
+    Select,
+    Up,
+    Down,
+    Left,
+    Right,
+
+    Pause,
+    Reset,
+    Debugger,
+    Exit,
+    /// Held to run as fast as possible.
+    FastForward,
+    SpeedUp,
+    SlowDown,
+    NormalSpeed,
+
+    TurboA,
+    TurboB,
+    TurboL,
+    TurboR,
+    TurboStart,
+    TurboSelect,
+    /// Starts recording an input macro, or stops and saves the current recording.
+    RecordMacro,
+    /// Plays the last recorded input macro, or stops it if one is already playing.
+    PlayMacro,
+}
+
+impl InputAction {
+    pub fn button(self) -> Option<gba::Button> {
+        match self {
+            InputAction::A => Some(gba::Button::A),
+            InputAction::B => Some(gba::Button::B),
+            InputAction::L => Some(gba::Button::L),
+            InputAction::R => Some(gba::Button::R),
+            InputAction::Start => Some(gba::Button::Start),
+            InputAction::Select => Some(gba::Button::Select),
+            InputAction::Up => Some(gba::Button::Up),
+            InputAction::Down => Some(gba::Button::Down),
+            InputAction::Left => Some(gba::Button::Left),
+            InputAction::Right => Some(gba::Button::Right),
+            _ => None,
+        }
+    }
+
+    /// Returns the button that this action holds down with turbo.
+    pub fn turbo_button(self) -> Option<gba::Button> {
+        match self {
+            InputAction::TurboA => Some(gba::Button::A),
+            InputAction::TurboB => Some(gba::Button::B),
+            InputAction::TurboL => Some(gba::Button::L),
+            InputAction::TurboR => Some(gba::Button::R),
+            InputAction::TurboStart => Some(gba::Button::Start),
+            InputAction::TurboSelect => Some(gba::Button::Select),
+            _ => None,
+        }
+    }
+}
+
+/// Bindings for a set of actions, e.g. `a = "Z"` or `speed-up = ["Ctrl+Equals", "Ctrl+NumpadAdd"]`.
+#[derive(serde::Deserialize, Default, Clone, Debug)]
+#[serde(try_from = "HashMap<String, Binding>")]
+pub struct InputBindings(HashMap<InputAction, Binding>);
+
+impl InputBindings {
+    pub fn get(&self, action: InputAction) -> Option<&Binding> {
+        self.0.get(&action)
+    }
+
+    pub fn iter(&self) -> impl '_ + Iterator<Item = (InputAction, &Binding)> {
+        self.0.iter().map(|(&action, binding)| (action, binding))
+    }
+}
+
+impl TryFrom<HashMap<String, Binding>> for InputBindings {
+    type Error = serde::de::value::Error;
+
+    fn try_from(bindings: HashMap<String, Binding>) -> Result<Self, Self::Error> {
+        action_map(bindings).map(InputBindings)
+    }
+}
+
+#[derive(serde::Deserialize, Default, Clone, Debug)]
+#[serde(try_from = "HashMap<String, [u32; 2]>")]
+pub struct TurboConfig(HashMap<InputAction, [u32; 2]>);
+
+impl TurboConfig {
+    /// Returns the `[pressed frames, released frames]` pattern configured for a button.
+    pub fn get(&self, button: InputAction) -> Option<[u32; 2]> {
+        self.0.get(&button).copied()
+    }
+
+    pub fn iter(&self) -> impl '_ + Iterator<Item = (InputAction, [u32; 2])> {
+        self.0.iter().map(|(&action, &pattern)| (action, pattern))
+    }
+}
+
+impl TryFrom<HashMap<String, [u32; 2]>> for TurboConfig {
+    type Error = serde::de::value::Error;
+
+    fn try_from(patterns: HashMap<String, [u32; 2]>) -> Result<Self, Self::Error> {
+        let patterns = action_map(patterns)?;
+        match patterns.keys().find(|action| action.button().is_none()) {
+            Some(action) => Err(serde::de::Error::custom(format!(
+                "turbo can't be configured for {action:?}"
+            ))),
+            None => Ok(TurboConfig(patterns)),
+        }
+    }
+}
+
+/// Converts a map keyed by action names. TOML tables can't be deserialized into maps with enum
+/// keys directly, so the action names are deserialized one at a time instead.
+fn action_map<T>(
+    map: HashMap<String, T>,
+) -> Result<HashMap<InputAction, T>, serde::de::value::Error> {
+    use serde::{de::IntoDeserializer as _, Deserialize as _};
+
+    map.into_iter()
+        .map(|(action, value)| {
+            InputAction::deserialize(action.into_deserializer()).map(|action| (action, value))
+        })
+        .collect()
+}
+
+/// One or more inputs bound to the same action. An empty list unbinds the action.
+#[derive(serde::Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Binding {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl Binding {
+    pub fn inputs(&self) -> &[String] {
+        match self {
+            Binding::One(input) => std::slice::from_ref(input),
+            Binding::Many(inputs) => inputs,
+        }
+    }
+}
+
 pub fn from_toml_str(config_source: &str) -> Result<Config, Error> {
     toml::from_str(config_source).map_err(Into::into)
 }
@@ -10,12 +10,18 @@ use crossbeam::{
 };
 use gba::Gba;
 
+use crate::{
+    frameskip::{FrameSkip, FrameSkipper},
+    input::InputProcessor,
+};
+
 type GbaThreadCallback = Box<dyn 'static + Send + FnMut(&mut Gba, &mut GbaThreadState, GbaEvent)>;
 type GbaThreadCallbackOnce = Box<dyn 'static + Send + FnOnce(&mut Gba, &mut GbaThreadState)>;
 
 fn gba_thread_fn(rx: Receiver<GbaMessage>) {
     let mut ctx = Context::default();
     ctx.state.target_fps = 60.0;
+    ctx.state.rate_adjustment = 1.0;
 
     log::trace!("waiting for GBA start");
     ctx.state.paused = true;
@@ -27,25 +33,53 @@ fn gba_thread_fn(rx: Receiver<GbaMessage>) {
     while !ctx.state.stopped {
         let frame_start_time = Instant::now();
 
+        let render = ctx.frame_skipper.next_frame(ctx.state.frame_skip);
+        ctx.gba.video_mut().set_skip_render(!render);
+        ctx.state.frame_rendered = render;
+
         ctx.gba.frame();
         ctx.state.frame_duration = frame_start_time.elapsed();
         ctx.state.frame_count += 1;
 
+        if let Some(address) = ctx.gba.take_undefined_instruction() {
+            log::warn!("undefined instruction at 0x{address:08X}");
+            ctx.state.undefined_instruction = Some(address);
+            if ctx.state.break_on_undefined {
+                ctx.state.paused = true;
+            }
+            ctx.on_event(GbaEvent::UNDEFINED_INSTRUCTION);
+        }
+
         ctx.on_event(GbaEvent::FRAME_READY);
 
         empty_gba_message_queue(&mut ctx, &rx);
 
+        let mut was_paused = false;
         if ctx.state.paused {
             ctx.on_event(GbaEvent::PAUSED);
             wait_for_gba_unpause(&mut ctx, &rx);
             ctx.on_event(GbaEvent::UNPAUSED);
+            was_paused = true;
         }
 
         let frame_duration = frame_start_time.elapsed();
         ctx.state.frame_processing_duration = frame_duration;
-        let target_frame_duration = Duration::from_secs_f64(1.0 / ctx.state.target_fps);
-        if frame_duration < target_frame_duration {
-            spin_sleeper.sleep(target_frame_duration - frame_duration);
+        let target_frame_duration = ctx.state.speed.multiplier().map(|multiplier| {
+            let target_fps = ctx.state.target_fps * ctx.state.rate_adjustment * multiplier;
+            Duration::from_secs_f64(1.0 / target_fps)
+        });
+
+        if was_paused {
+            ctx.frame_skipper.reset();
+        } else {
+            ctx.frame_skipper
+                .frame_finished(frame_duration, target_frame_duration);
+        }
+
+        if let Some(target_frame_duration) = target_frame_duration {
+            if frame_duration < target_frame_duration {
+                spin_sleeper.sleep(target_frame_duration - frame_duration);
+            }
         }
     }
     log::trace!("exited GBA thread loop");
@@ -94,6 +128,7 @@ fn process_gba_message(ctx: &mut Context, msg: GbaMessage) {
 struct Context {
     gba: Gba,
     state: GbaThreadState,
+    frame_skipper: FrameSkipper,
 
     on_event: Vec<(CallbackId, GbaThreadCallback, GbaEvent)>,
 }
@@ -121,6 +156,20 @@ pub struct GbaThreadState {
     pub paused: bool,
     stopped: bool,
     pub target_fps: f64,
+    /// Fast-forward or slow-motion relative to `target_fps`.
+    pub speed: Speed,
+    pub frame_skip: FrameSkip,
+    frame_rendered: bool,
+    /// Turbo buttons and input macros, applied by frontends to the buttons they pass to
+    /// [`Gba::set_buttons`].
+    pub input: InputProcessor,
+    /// Multiplier applied to `target_fps`, used to make small corrections to the emulation
+    /// speed (e.g. to keep audio in sync with the audio device).
+    pub rate_adjustment: f64,
+    /// Pause at the end of any frame in which an undefined instruction was executed. The frame
+    /// still runs to completion, so the CPU will have kept going after the instruction.
+    pub break_on_undefined: bool,
+    undefined_instruction: Option<u32>,
 
     frame_count: u64,
 
@@ -151,18 +200,84 @@ impl GbaThreadState {
         self.frame_count
     }
 
+    /// Returns false if rendering was skipped for the current frame because of frame skipping.
+    /// The screen and anything else produced by the video renderer still contain the last
+    /// rendered frame.
+    pub fn frame_rendered(&self) -> bool {
+        self.frame_rendered
+    }
+
     /// Returns the amount of time required to render the previous frame
     /// and run all of the callbacks.
     pub fn frame_processing_duration(&self) -> Duration {
         self.frame_processing_duration
     }
 
+    /// Returns the address of the first undefined instruction executed in the most recent frame
+    /// that executed one, if any.
+    pub fn undefined_instruction(&self) -> Option<u32> {
+        self.undefined_instruction
+    }
+
     // Remove the current callback.
     pub fn remove_callback(&mut self) {
         self.remove_callback = true;
     }
 }
 
+/// How fast the emulator runs relative to its normal speed.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Speed {
+    /// Runs at a multiple of the normal speed, e.g. 0.5 for half speed.
+    Multiplier(f64),
+    /// Runs as fast as possible.
+    Uncapped,
+}
+
+impl Speed {
+    pub const NORMAL: Speed = Speed::Multiplier(1.0);
+
+    /// The fixed multipliers that [`Speed::faster`] and [`Speed::slower`] step through.
+    pub const STEPS: [f64; 5] = [0.25, 0.5, 1.0, 2.0, 4.0];
+
+    /// Returns the speed multiplier, or `None` if the speed is uncapped.
+    pub fn multiplier(self) -> Option<f64> {
+        match self {
+            Speed::Multiplier(multiplier) => Some(multiplier),
+            Speed::Uncapped => None,
+        }
+    }
+
+    /// The next fixed speed above this one, or uncapped after the fastest.
+    pub fn faster(self) -> Speed {
+        match self {
+            Speed::Multiplier(current) => Self::STEPS
+                .into_iter()
+                .find(|&step| step > current)
+                .map_or(Speed::Uncapped, Speed::Multiplier),
+            Speed::Uncapped => Speed::Uncapped,
+        }
+    }
+
+    /// The next fixed speed below this one.
+    pub fn slower(self) -> Speed {
+        match self {
+            Speed::Multiplier(current) => Self::STEPS
+                .into_iter()
+                .rev()
+                .find(|&step| step < current)
+                .map_or(self, Speed::Multiplier),
+            Speed::Uncapped => Speed::Multiplier(Self::STEPS[Self::STEPS.len() - 1]),
+        }
+    }
+}
+
+impl Default for Speed {
+    fn default() -> Self {
+        Speed::NORMAL
+    }
+}
+
 /// A handle to a GBA instance running in its own thread.
 pub struct GbaHandle {
     tx: Sender<GbaMessage>,
@@ -261,6 +376,10 @@ impl GbaHandle {
     pub fn set_paused(&self, paused: bool) {
         self.after_frame(move |_, state| state.paused = paused);
     }
+
+    pub fn set_speed(&self, speed: Speed) {
+        self.after_frame(move |_, state| state.speed = speed);
+    }
 }
 
 impl Clone for GbaHandle {
@@ -301,5 +420,23 @@ bitflags::bitflags! {
         const FRAME_READY = 0x1;
         const PAUSED = 0x2;
         const UNPAUSED = 0x04;
+        /// An undefined instruction was executed during the last frame.
+        const UNDEFINED_INSTRUCTION = 0x08;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn speed_steps() {
+        assert_eq!(Speed::NORMAL.faster(), Speed::Multiplier(2.0));
+        assert_eq!(Speed::Multiplier(4.0).faster(), Speed::Uncapped);
+        assert_eq!(Speed::Uncapped.faster(), Speed::Uncapped);
+        assert_eq!(Speed::Uncapped.slower(), Speed::Multiplier(4.0));
+        assert_eq!(Speed::NORMAL.slower(), Speed::Multiplier(0.5));
+        assert_eq!(Speed::Multiplier(0.25).slower(), Speed::Multiplier(0.25));
+        assert_eq!(Speed::Multiplier(3.0).slower(), Speed::Multiplier(2.0));
     }
 }
@@ -0,0 +1,130 @@
+use std::time::Duration;
+
+use crate::config::GraphicsConfig;
+
+/// Controls which frames are rendered. Skipped frames are still fully emulated (and produce
+/// audio) but the video renderer doesn't draw any lines, so the previous frame stays on screen.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FrameSkip {
+    /// Skips `N` frames after every rendered frame. `Fixed(0)` renders every frame.
+    Fixed(u32),
+    /// Skips frames while emulation is running behind the target frame rate, but never more
+    /// than `max` frames in a row.
+    Auto { max: u32 },
+}
+
+impl FrameSkip {
+    pub const NONE: FrameSkip = FrameSkip::Fixed(0);
+
+    pub fn from_config(config: &GraphicsConfig) -> FrameSkip {
+        if config.auto_frameskip.unwrap_or(false) {
+            FrameSkip::Auto {
+                max: config.frameskip.unwrap_or(4),
+            }
+        } else {
+            FrameSkip::Fixed(config.frameskip.unwrap_or(0))
+        }
+    }
+}
+
+impl Default for FrameSkip {
+    fn default() -> Self {
+        FrameSkip::NONE
+    }
+}
+
+/// Decides which frames to skip.
+#[derive(Default)]
+pub(crate) struct FrameSkipper {
+    /// Number of frames that have been skipped in a row, `None` if no frame has been rendered
+    /// yet.
+    skipped: Option<u32>,
+    /// How far emulation has fallen behind the target frame rate.
+    lag: Duration,
+}
+
+impl FrameSkipper {
+    /// Returns true if the next frame should be rendered.
+    pub(crate) fn next_frame(&mut self, mode: FrameSkip) -> bool {
+        let skip = match (mode, self.skipped) {
+            (_, None) => false,
+            (FrameSkip::Fixed(n), Some(skipped)) => skipped < n,
+            (FrameSkip::Auto { max }, Some(skipped)) => !self.lag.is_zero() && skipped < max,
+        };
+
+        self.skipped = Some(if skip {
+            self.skipped.unwrap_or(0) + 1
+        } else {
+            0
+        });
+        !skip
+    }
+
+    /// Records how long a frame took compared to how long it should have taken. `target` is
+    /// `None` if there is no target frame rate (e.g. fast-forwarding), in which case frames are
+    /// never considered late.
+    pub(crate) fn frame_finished(&mut self, duration: Duration, target: Option<Duration>) {
+        match target {
+            Some(target) => {
+                // Lag is capped so that a single very slow frame (e.g. loading a save state)
+                // doesn't result in frames being skipped for a long time afterwards.
+                let max_lag = target * 4;
+                self.lag = (self.lag + duration).saturating_sub(target).min(max_lag);
+            }
+            None => self.lag = Duration::ZERO,
+        }
+    }
+
+    /// Forgets about any lag, e.g. after the emulator has been paused.
+    pub(crate) fn reset(&mut self) {
+        self.lag = Duration::ZERO;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TARGET: Duration = Duration::from_millis(16);
+
+    #[test]
+    fn fixed_frameskip() {
+        let mut skipper = FrameSkipper::default();
+        let rendered: Vec<bool> = (0..7)
+            .map(|_| skipper.next_frame(FrameSkip::Fixed(2)))
+            .collect();
+        assert_eq!(rendered, [true, false, false, true, false, false, true]);
+
+        assert!((0..4).all(|_| skipper.next_frame(FrameSkip::NONE)));
+    }
+
+    #[test]
+    fn auto_frameskip_follows_lag() {
+        let mode = FrameSkip::Auto { max: 2 };
+        let mut skipper = FrameSkipper::default();
+
+        // Keeping up: every frame is rendered.
+        for _ in 0..4 {
+            assert!(skipper.next_frame(mode));
+            skipper.frame_finished(TARGET / 2, Some(TARGET));
+        }
+
+        // Falling behind: frames are skipped, but never more than `max` in a row.
+        let rendered: Vec<bool> = (0..6)
+            .map(|_| {
+                let rendered = skipper.next_frame(mode);
+                skipper.frame_finished(TARGET * 2, Some(TARGET));
+                rendered
+            })
+            .collect();
+        assert_eq!(rendered, [true, false, false, true, false, false]);
+
+        // Catching up again.
+        skipper.frame_finished(Duration::ZERO, Some(TARGET));
+        skipper.frame_finished(Duration::ZERO, Some(TARGET));
+        skipper.frame_finished(Duration::ZERO, Some(TARGET));
+        skipper.frame_finished(Duration::ZERO, Some(TARGET));
+        assert!(skipper.next_frame(mode));
+        assert!(skipper.next_frame(mode));
+    }
+}
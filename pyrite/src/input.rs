@@ -0,0 +1,364 @@
+//! Input processing that happens on the GBA thread right before the buttons are passed to
+//! [`Gba::set_buttons`](gba::Gba::set_buttons): turbo buttons and input macros.
+
+use std::{
+    fmt,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use gba::{Button, ButtonSet};
+
+const BUTTON_COUNT: usize = 10;
+
+/// Names of the buttons indexed by their [`Button`] value, as used in macro files.
+const BUTTON_NAMES: [&str; BUTTON_COUNT] = [
+    "A", "B", "Select", "Start", "Right", "Left", "Up", "Down", "R", "L",
+];
+
+/// How a turbo button alternates while held: pressed for `pressed` frames, then released for
+/// `released` frames.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TurboPattern {
+    pub pressed: u32,
+    pub released: u32,
+}
+
+impl TurboPattern {
+    pub const DEFAULT: TurboPattern = TurboPattern {
+        pressed: 1,
+        released: 1,
+    };
+
+    /// Returns true if the button is pressed on the given frame, counting from the frame that
+    /// turbo was first held on.
+    fn is_pressed(self, frame: u32) -> bool {
+        let period = self.pressed + self.released;
+        period == 0 || frame % period < self.pressed
+    }
+}
+
+impl Default for TurboPattern {
+    fn default() -> Self {
+        TurboPattern::DEFAULT
+    }
+}
+
+/// A sequence of button states, one for each frame.
+#[derive(Clone, Default)]
+pub struct InputMacro {
+    frames: Vec<ButtonSet>,
+}
+
+impl InputMacro {
+    pub fn new() -> InputMacro {
+        InputMacro::default()
+    }
+
+    pub fn push(&mut self, buttons: ButtonSet) {
+        self.frames.push(buttons);
+    }
+
+    pub fn frames(&self) -> &[ButtonSet] {
+        &self.frames
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Loads a macro written by [`InputMacro::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<InputMacro, Error> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).map_err(|err| Error::Io(path.into(), err))?;
+        Self::read(BufReader::new(file)).map_err(|err| err.with_path(path))
+    }
+
+    /// Saves the macro as text. Each line contains a number of frames followed by the buttons
+    /// held during those frames, e.g. `12 A+Right`, or `-` if no buttons are held.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        std::fs::File::create(path)
+            .and_then(|file| self.write(BufWriter::new(file)))
+            .map_err(|err| Error::Io(path.into(), err))
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        let mut frames = self.frames.iter().peekable();
+        while let Some(&buttons) = frames.next() {
+            let mut count = 1;
+            while frames.next_if(|&&next| next == buttons).is_some() {
+                count += 1;
+            }
+
+            let names: Vec<&str> = (0..BUTTON_COUNT)
+                .filter(|&idx| buttons.is_pressed(Button::from(idx)))
+                .map(|idx| BUTTON_NAMES[idx])
+                .collect();
+            if names.is_empty() {
+                writeln!(out, "{count} -")?;
+            } else {
+                writeln!(out, "{count} {}", names.join("+"))?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Reads a macro in the format written by [`InputMacro::write`]. Empty lines and anything
+    /// after a `#` are ignored.
+    pub fn read<R: BufRead>(input: R) -> Result<InputMacro, Error> {
+        let mut frames = Vec::new();
+        for (idx, line) in input.lines().enumerate() {
+            let line = line.map_err(|err| Error::Io(PathBuf::new(), err))?;
+            let parse_error = |message: String| Error::Parse {
+                path: PathBuf::new(),
+                line: idx + 1,
+                message,
+            };
+
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let (count, names) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| parse_error("expected a frame count and buttons".into()))?;
+            let count: usize = count
+                .parse()
+                .map_err(|_| parse_error(format!("invalid frame count `{count}`")))?;
+
+            let mut buttons = ButtonSet::default();
+            match names.trim() {
+                "-" => {}
+                names => {
+                    for name in names.split('+').map(str::trim) {
+                        let idx = BUTTON_NAMES
+                            .iter()
+                            .position(|n| n.eq_ignore_ascii_case(name))
+                            .ok_or_else(|| parse_error(format!("unknown button `{name}`")))?;
+                        buttons.set_pressed(Button::from(idx), true);
+                    }
+                }
+            }
+            frames.extend(std::iter::repeat_n(buttons, count));
+        }
+        Ok(InputMacro { frames })
+    }
+}
+
+/// Applies turbo buttons and records or plays back input macros.
+#[derive(Default)]
+pub struct InputProcessor {
+    turbo_patterns: [TurboPattern; BUTTON_COUNT],
+    /// The number of frames that turbo has been held for each button, `None` if it isn't held.
+    turbo_frames: [Option<u32>; BUTTON_COUNT],
+    recording: Option<InputMacro>,
+    playback: Option<(InputMacro, usize)>,
+}
+
+impl InputProcessor {
+    pub fn set_turbo_pattern(&mut self, button: Button, pattern: TurboPattern) {
+        self.turbo_patterns[button as usize] = pattern;
+    }
+
+    pub fn turbo_pattern(&self, button: Button) -> TurboPattern {
+        self.turbo_patterns[button as usize]
+    }
+
+    /// Returns the buttons that should be passed to the GBA for the next frame. `buttons` are
+    /// the buttons held normally and `turbo` are the buttons held with turbo. While a macro is
+    /// playing its buttons are used instead of either.
+    pub fn apply(&mut self, buttons: ButtonSet, turbo: ButtonSet) -> ButtonSet {
+        let mut output = buttons;
+        for idx in 0..BUTTON_COUNT {
+            let button = Button::from(idx);
+            if turbo.is_pressed(button) {
+                let frame = self.turbo_frames[idx].map_or(0, |frame| frame + 1);
+                self.turbo_frames[idx] = Some(frame);
+                if self.turbo_patterns[idx].is_pressed(frame) {
+                    output.set_pressed(button, true);
+                }
+            } else {
+                self.turbo_frames[idx] = None;
+            }
+        }
+
+        if let Some((ref input_macro, ref mut position)) = self.playback {
+            output = input_macro.frames[*position];
+            *position += 1;
+            if *position >= input_macro.len() {
+                log::info!("finished playing input macro");
+                self.playback = None;
+            }
+        }
+
+        if let Some(ref mut recording) = self.recording {
+            recording.push(output);
+        }
+
+        output
+    }
+
+    /// Starts recording the buttons passed to the GBA, discarding any unfinished recording.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(InputMacro::new());
+    }
+
+    pub fn stop_recording(&mut self) -> Option<InputMacro> {
+        self.recording.take()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    /// Plays a macro starting with the next frame, replacing any macro that is already playing.
+    pub fn play(&mut self, input_macro: InputMacro) {
+        if input_macro.is_empty() {
+            self.playback = None;
+        } else {
+            self.playback = Some((input_macro, 0));
+        }
+    }
+
+    pub fn stop_playback(&mut self) {
+        self.playback = None;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(PathBuf, io::Error),
+    Parse {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl Error {
+    fn with_path(self, path: &Path) -> Error {
+        match self {
+            Error::Io(_, err) => Error::Io(path.into(), err),
+            Error::Parse { line, message, .. } => Error::Parse {
+                path: path.into(),
+                line,
+                message,
+            },
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(path, _) => {
+                write!(f, "error occurred accessing macro `{}`", path.display())
+            }
+            Error::Parse {
+                path,
+                line,
+                message,
+            } => write!(f, "{}:{line}: {message}", path.display()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(_, err) => Some(err),
+            Error::Parse { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn buttons(pressed: &[Button]) -> ButtonSet {
+        let mut buttons = ButtonSet::default();
+        pressed
+            .iter()
+            .for_each(|&button| buttons.set_pressed(button, true));
+        buttons
+    }
+
+    #[test]
+    fn turbo_follows_pattern() {
+        let mut input = InputProcessor::default();
+        input.set_turbo_pattern(
+            Button::A,
+            TurboPattern {
+                pressed: 2,
+                released: 1,
+            },
+        );
+
+        let a: Vec<bool> = (0..7)
+            .map(|_| {
+                input
+                    .apply(buttons(&[Button::B]), buttons(&[Button::A]))
+                    .is_pressed(Button::A)
+            })
+            .collect();
+        assert_eq!(a, [true, true, false, true, true, false, true]);
+
+        // Releasing turbo restarts the pattern and holding the button normally still works.
+        let output = input.apply(buttons(&[Button::A]), ButtonSet::default());
+        assert!(output.is_pressed(Button::A));
+        assert!(!output.is_pressed(Button::B));
+        assert!(input
+            .apply(ButtonSet::default(), buttons(&[Button::A]))
+            .is_pressed(Button::A));
+    }
+
+    #[test]
+    fn record_and_replay_macro() {
+        let mut input = InputProcessor::default();
+        input.start_recording();
+        let recorded: Vec<u16> = [
+            buttons(&[]),
+            buttons(&[Button::A]),
+            buttons(&[Button::A]),
+            buttons(&[Button::Up, Button::R]),
+        ]
+        .into_iter()
+        .map(|b| u16::from(input.apply(b, ButtonSet::default())))
+        .collect();
+        let input_macro = input.stop_recording().unwrap();
+
+        let mut text = Vec::new();
+        input_macro.write(&mut text).unwrap();
+        assert_eq!(String::from_utf8_lossy(&text), "1 -\n2 A\n1 Up+R\n",);
+        let input_macro = InputMacro::read(&text[..]).unwrap();
+
+        input.play(input_macro);
+        let replayed: Vec<u16> = (0..4)
+            .map(|_| u16::from(input.apply(buttons(&[Button::B]), ButtonSet::default())))
+            .collect();
+        assert_eq!(replayed, recorded);
+        assert!(!input.is_playing());
+        assert!(input
+            .apply(buttons(&[Button::B]), ButtonSet::default())
+            .is_pressed(Button::B));
+    }
+
+    #[test]
+    fn macro_parse_errors() {
+        let err = InputMacro::read(&b"# comment\n3 A+Start\n2 Q\n"[..])
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), ":3: unknown button `Q`");
+    }
+}
@@ -1,4 +1,9 @@
+pub mod capture;
 pub mod config;
 mod core;
+mod frameskip;
+pub mod input;
+pub mod sync;
 
 pub use self::core::*;
+pub use frameskip::FrameSkip;
@@ -0,0 +1,149 @@
+use gba::Command;
+
+use crate::config::AudioConfig;
+
+/// Dynamic rate control: keeps the amount of queued audio close to a target by making small
+/// adjustments to the emulation speed. Running slightly faster when the queue is running low
+/// and slightly slower when it is filling up avoids both underruns (crackles) and having to
+/// throw audio away, without the pitch changes that resampling would cause.
+#[derive(Copy, Clone, Debug)]
+pub struct DynamicRateControl {
+    /// Target amount of queued audio, in GBA frames.
+    target_fill: f64,
+    max_delta: f64,
+}
+
+impl DynamicRateControl {
+    pub fn new(target_fill: f64, max_delta: f64) -> Self {
+        DynamicRateControl {
+            target_fill: target_fill.max(1.0),
+            max_delta: max_delta.abs(),
+        }
+    }
+
+    pub fn from_config(config: &AudioConfig) -> Self {
+        let latency = config.latency.unwrap_or(64.0) / 1000.0;
+        let max_delta = config.max_rate_delta.unwrap_or(0.005);
+        Self::new(latency * gba::Gba::FRAMES_PER_SECOND, max_delta)
+    }
+
+    /// The target amount of queued audio, in GBA frames.
+    pub fn target_fill(&self) -> f64 {
+        self.target_fill
+    }
+
+    /// Returns the multiplier that should be applied to the emulation speed given the amount
+    /// of audio that is currently queued, in GBA frames.
+    pub fn rate(&self, fill: f64) -> f64 {
+        let error = ((self.target_fill - fill) / self.target_fill).clamp(-1.0, 1.0);
+        1.0 + error * self.max_delta
+    }
+
+    /// Returns true if so much audio has been queued that more should be dropped instead of
+    /// waiting for the rate adjustment to catch up, e.g. after fast forwarding.
+    pub fn overfilled(&self, fill: f64) -> bool {
+        fill > self.target_fill * 4.0
+    }
+}
+
+/// Scales the waits between audio commands by the emulation speed so that audio generated while
+/// running faster or slower than normal plays back in real time instead of piling up or running
+/// dry. Nothing is time-stretched: all of the audio, including the pitch of every channel, is sped
+/// up or slowed down along with the emulation.
+#[derive(Default)]
+pub struct WaitScaler {
+    /// Fractional cycles left over from previous waits.
+    remainder: f64,
+}
+
+impl WaitScaler {
+    /// Appends `commands` to `output` with every wait divided by `speed`.
+    pub fn scale(&mut self, commands: &[Command], speed: f64, output: &mut Vec<Command>) {
+        if speed == 1.0 {
+            output.extend_from_slice(commands);
+            return;
+        }
+
+        output.extend(commands.iter().map(|&command| match command {
+            Command::Wait(cycles) => {
+                let cycles = cycles as f64 / speed + self.remainder;
+                self.remainder = cycles.fract();
+                Command::Wait(cycles as u32)
+            }
+            command => command,
+        }));
+    }
+}
+
+/// Appends every command except for the waits to `output`. Audio that is skipped this way takes
+/// no time to play back, but the channel state it sets up is still there once playback resumes.
+pub fn skip_waits(commands: &[Command], output: &mut Vec<Command>) {
+    output.extend(
+        commands
+            .iter()
+            .filter(|command| !matches!(command, Command::Wait(_))),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn total_wait(commands: &[Command]) -> u32 {
+        commands
+            .iter()
+            .map(|command| match command {
+                Command::Wait(cycles) => *cycles,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    #[test]
+    fn wait_scaler_scales_waits() {
+        let commands = [
+            Command::Wait(1001),
+            Command::PlaySampleFifoA(4),
+            Command::Wait(1001),
+            Command::PlaySampleFifoA(8),
+        ];
+
+        let mut output = Vec::new();
+        WaitScaler::default().scale(&commands, 2.0, &mut output);
+        assert_eq!(output.len(), commands.len());
+        assert_eq!(total_wait(&output), 1001);
+        assert!(matches!(output[3], Command::PlaySampleFifoA(8)));
+
+        let mut output = Vec::new();
+        WaitScaler::default().scale(&commands, 0.25, &mut output);
+        assert_eq!(total_wait(&output), 8008);
+    }
+
+    #[test]
+    fn skipped_waits_keep_other_commands() {
+        let commands = [
+            Command::Wait(1001),
+            Command::SetWaveVolume(50),
+            Command::Wait(1001),
+            Command::PlaySampleFifoA(8),
+        ];
+
+        let mut output = Vec::new();
+        skip_waits(&commands, &mut output);
+        assert_eq!(output.len(), 2);
+        assert!(matches!(output[0], Command::SetWaveVolume(50)));
+        assert!(matches!(output[1], Command::PlaySampleFifoA(8)));
+    }
+
+    #[test]
+    fn rate_follows_fill_level() {
+        let drc = DynamicRateControl::new(4.0, 0.005);
+        assert_eq!(drc.rate(4.0), 1.0);
+        assert_eq!(drc.rate(0.0), 1.005);
+        assert_eq!(drc.rate(8.0), 0.995);
+        assert_eq!(drc.rate(100.0), 0.995);
+        assert!(drc.rate(3.0) > 1.0 && drc.rate(3.0) < 1.005);
+        assert!(!drc.overfilled(16.0));
+        assert!(drc.overfilled(16.5));
+    }
+}